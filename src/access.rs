@@ -0,0 +1,109 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use chrono::Utc;
+use git2::Repository;
+use rustc_hash::FxHashMap as HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::{CacheLocation, access_log_path};
+use crate::pipeline::{ScoreMap, scoped_path};
+use crate::{AnalyzeOptions, open_repo, to_git_path};
+
+/// One line of the access log: a file opened in an editor
+#[derive(Serialize, Deserialize)]
+struct AccessEvent {
+    /// Unix seconds
+    time: i64,
+    /// Relative to the work tree root
+    #[serde(with = "crate::paths")]
+    path: PathBuf,
+}
+
+/// Log that `file` (absolute, or relative to the current directory) was opened now, for
+/// [`AnalyzeOptions::access_weight`]. The log is kept next to the commit statistics cache.
+pub fn record_access(repo_path: &Path, file: &Path, location: &CacheLocation) -> Result<()> {
+    let repo = open_repo(repo_path)?;
+    let Some(workdir) = repo.workdir() else {
+        bail!("cannot record file accesses in a bare repository");
+    };
+    let workdir = workdir
+        .canonicalize()
+        .with_context(|| format!("failed to canonicalize {}", workdir.display()))?;
+    let file = file
+        .canonicalize()
+        .with_context(|| format!("failed to canonicalize {}", file.display()))?;
+    let Ok(path) = file.strip_prefix(&workdir) else {
+        bail!("{} is outside the repository", file.display());
+    };
+
+    let event = AccessEvent {
+        time: Utc::now().timestamp(),
+        path: to_git_path(path),
+    };
+    let mut line = serde_json::to_vec(&event)?;
+    line.push(b'\n');
+
+    let log = access_log_path(&repo, location)?;
+    if let Some(parent) = log.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create directory {}", parent.display()))?;
+    }
+    // A single append of a short line, so concurrent editors don't interleave
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log)
+        .and_then(|mut handle| handle.write_all(&line))
+        .with_context(|| format!("failed to write access log {}", log.display()))
+}
+
+/// Access weight of each logged file (relative to `prefix`): every access counts like
+/// `access_weight` commits at that time, with the same quadratic recency decay
+pub(crate) fn access_scores(
+    repo: &Repository,
+    options: &AnalyzeOptions,
+) -> Result<HashMap<PathBuf, f64>> {
+    let mut scores = HashMap::default();
+    let Some(weight) = options.access_weight.filter(|&weight| weight != 0.0) else {
+        return Ok(scores);
+    };
+    let log = access_log_path(repo, &options.cache_location)?;
+    let file = match File::open(&log) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(scores),
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("failed to read access log {}", log.display()));
+        }
+    };
+
+    let now = options.as_of.unwrap_or_else(|| Utc::now().timestamp());
+    for line in BufReader::new(file).split(b'\n') {
+        // A line cut short by a crash is skipped rather than failing the analysis
+        let Ok(event) = serde_json::from_slice::<AccessEvent>(&line?) else {
+            continue;
+        };
+        if event.time > now || !options.in_time_window(event.time) {
+            continue;
+        }
+        let Some(path) = scoped_path(&event.path, options) else {
+            continue;
+        };
+        let age_days = ((now - event.time) / 86_400) as f64;
+        *scores.entry(path).or_default() += weight / (age_days + 1.0).powi(2);
+    }
+    Ok(scores)
+}
+
+/// Add per-file weights (of accesses or other local signals) to the scores of files that have
+/// git history
+pub(crate) fn blend(scores: &mut ScoreMap, access: &HashMap<PathBuf, f64>) {
+    for (path, weight) in access {
+        if let Some(stats) = scores.get_mut(path) {
+            stats.score += weight;
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+use rustc_hash::FxHashMap as HashMap;
+use serde::Serialize;
+
+use crate::FileScore;
+use crate::paths::path_bytes;
+
+/// Combined score of the files below a directory
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DirectoryScore {
+    /// Directory relative to the repository root; `.` for files at the root
+    #[serde(serialize_with = "crate::paths::serialize")]
+    pub path: PathBuf,
+    /// Sum of the file scores
+    pub score: f64,
+    pub files: usize,
+}
+
+/// Combined score of the files in a group, e.g. those of one code owner
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GroupScore {
+    pub group: String,
+    /// Sum of the file scores
+    pub score: f64,
+    pub files: usize,
+}
+
+/// Sum file scores per group; `groups` names the groups of a file, which counts fully toward
+/// each of them. Hottest first.
+pub fn group_scores<'a>(
+    scores: &'a [FileScore],
+    groups: impl Fn(&'a FileScore) -> Vec<String>,
+) -> Vec<GroupScore> {
+    let mut totals: HashMap<String, GroupScore> = HashMap::default();
+    for file in scores {
+        for group in groups(file) {
+            let entry = totals.entry(group.clone()).or_insert_with(|| GroupScore {
+                group,
+                score: 0.0,
+                files: 0,
+            });
+            entry.score += file.score;
+            entry.files += 1;
+        }
+    }
+
+    let mut groups: Vec<GroupScore> = totals.into_values().collect();
+    groups.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.group.cmp(&b.group))
+    });
+    groups
+}
+
+/// Sum file scores per directory, truncated to `depth` path components; files at a
+/// shallower level count toward their parent directory. Hottest first.
+pub fn directory_scores(scores: &[FileScore], depth: usize) -> Vec<DirectoryScore> {
+    let mut directories: HashMap<PathBuf, DirectoryScore> = HashMap::default();
+    for file in scores {
+        let mut path = group_path(&file.path, depth);
+        if path.as_os_str().is_empty() {
+            path = PathBuf::from(".");
+        }
+        let entry = directories
+            .entry(path.clone())
+            .or_insert_with(|| DirectoryScore {
+                path,
+                score: 0.0,
+                files: 0,
+            });
+        entry.score += file.score;
+        entry.files += 1;
+    }
+
+    let mut directories: Vec<DirectoryScore> = directories.into_values().collect();
+    directories.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    directories
+}
+
+/// The directory made of the first `depth` components of `path`; files at a shallower
+/// level are grouped under their parent directory
+pub(crate) fn group_path(path: &Path, depth: usize) -> PathBuf {
+    let parent = path.parent().unwrap_or(Path::new(""));
+    parent.components().take(depth).collect()
+}
+
+/// Directory hierarchy of file scores, in the `{name, value, children}` shape d3's
+/// `hierarchy`/`treemap` expect: only files carry a `value`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScoreTree {
+    /// File or directory name, in the JSON path form
+    #[serde(serialize_with = "crate::paths::serialize")]
+    pub name: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ScoreTree>,
+}
+
+/// Nest file scores by directory below a root node called `root_name`; children are
+/// sorted by name
+pub fn score_tree(scores: &[FileScore], root_name: &OsStr) -> ScoreTree {
+    #[derive(Default)]
+    struct Node {
+        value: Option<f64>,
+        children: BTreeMap<OsString, Node>,
+    }
+
+    fn finish(name: OsString, node: Node) -> ScoreTree {
+        ScoreTree {
+            name: PathBuf::from(name),
+            value: node.value,
+            children: node
+                .children
+                .into_iter()
+                .map(|(name, child)| finish(name, child))
+                .collect(),
+        }
+    }
+
+    let mut root = Node::default();
+    for file in scores {
+        let node = file.path.iter().fold(&mut root, |node, component| {
+            node.children.entry(component.to_os_string()).or_default()
+        });
+        *node.value.get_or_insert(0.0) += file.score;
+    }
+    finish(root_name.to_os_string(), root)
+}
+
+/// Folded-stack lines (`dir;subdir;file weight`) for flamegraph tools such as inferno, with
+/// the raw bytes of the path components.
+/// Weights are integers: each file's share of the total score in parts per million.
+pub fn folded_stacks(scores: &[FileScore]) -> Vec<u8> {
+    let total: f64 = scores.iter().map(|f| f.score).sum();
+    let mut lines: Vec<(Vec<u8>, u64)> = scores
+        .iter()
+        .filter(|_| total > 0.0)
+        .map(|file| {
+            let stack: Vec<_> = file.path.iter().map(|c| path_bytes(Path::new(c))).collect();
+            (
+                stack.join(&b';'),
+                (file.score / total * 1_000_000.0).round() as u64,
+            )
+        })
+        .filter(|(_, weight)| *weight > 0)
+        .collect();
+    lines.sort();
+
+    let mut out = Vec::new();
+    for (stack, weight) in lines {
+        out.extend_from_slice(&stack);
+        out.push(b' ');
+        out.extend_from_slice(weight.to_string().as_bytes());
+        out.push(b'\n');
+    }
+    out
+}
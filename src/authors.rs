@@ -0,0 +1,92 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Result, anyhow};
+use git2::{Commit, Mailmap, Signature};
+use regex::Regex;
+
+use crate::open_repo;
+
+/// Multiplies the weight of commits whose author matches `pattern`
+#[derive(Debug, Clone)]
+pub struct AuthorBoost {
+    pub pattern: Regex,
+    pub factor: f64,
+}
+
+/// Parses `PATTERN=FACTOR`, splitting on the last `=` so patterns may contain one
+impl FromStr for AuthorBoost {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pattern, factor) = s
+            .rsplit_once('=')
+            .ok_or_else(|| anyhow!("expected PATTERN=FACTOR, got {s:?}"))?;
+        let factor: f64 = factor
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid boost factor in {s:?}"))?;
+        if !factor.is_finite() || factor < 0.0 {
+            return Err(anyhow!(
+                "boost factor must be a non-negative number, got {factor}"
+            ));
+        }
+        let pattern =
+            Regex::new(pattern).with_context(|| format!("invalid author pattern {pattern:?}"))?;
+        Ok(Self { pattern, factor })
+    }
+}
+
+/// Formats a signature as `Name <email>`, the form author patterns are matched against
+pub(crate) fn identity(sig: &Signature) -> String {
+    format!(
+        "{} <{}>",
+        String::from_utf8_lossy(sig.name_bytes()),
+        String::from_utf8_lossy(sig.email_bytes())
+    )
+}
+
+/// Email of the configured git user (`user.email`), with `.mailmap` applied, for matching the
+/// user's own commits
+pub fn user_email(repo_path: &Path) -> Result<String> {
+    let repo = open_repo(repo_path)?;
+    let config = repo.config()?;
+    let email = config
+        .get_string("user.email")
+        .context("user.email is not set in the git config")?;
+    let name = config.get_string("user.name").unwrap_or_default();
+    let signature = Signature::now(&name, &email)?;
+    let resolved = repo
+        .mailmap()
+        .ok()
+        .and_then(|map| map.resolve_signature(&signature).ok());
+    let signature = resolved.as_ref().unwrap_or(&signature);
+    Ok(String::from_utf8_lossy(signature.email_bytes()).into_owned())
+}
+
+/// Canonical author identity of a commit, with `.mailmap` applied when available
+pub(crate) fn commit_author(commit: &Commit, mailmap: Option<&Mailmap>) -> String {
+    let author = commit.author();
+    match mailmap.and_then(|map| map.resolve_signature(&author).ok()) {
+        Some(resolved) => identity(&resolved),
+        None => identity(&author),
+    }
+}
+
+/// Weight multiplier for a commit author; None when the author is filtered out
+pub(crate) fn author_weight(
+    author: &str,
+    filters: &[Regex],
+    boosts: &[AuthorBoost],
+) -> Option<f64> {
+    if !filters.is_empty() && !filters.iter().any(|re| re.is_match(author)) {
+        return None;
+    }
+    Some(
+        boosts
+            .iter()
+            .filter(|boost| boost.pattern.is_match(author))
+            .map(|boost| boost.factor)
+            .product(),
+    )
+}
@@ -0,0 +1,32 @@
+//! `git frecenfile`: git runs `git-<name>` executables found on `PATH` for `git <name>`. This
+//! forwards to the `frecenfile` binary installed next to it, under the name it was run as.
+
+use std::env;
+use std::path::PathBuf;
+use std::process::{self, Command};
+
+fn main() {
+    let frecenfile = env::current_exe()
+        .map(|exe| exe.with_file_name(format!("frecenfile{}", env::consts::EXE_SUFFIX)))
+        .ok()
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from("frecenfile"));
+    let mut command = Command::new(&frecenfile);
+    command.args(env::args_os().skip(1));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let err = command.arg0("git-frecenfile").exec();
+        eprintln!("git-frecenfile: failed to run {}: {err}", frecenfile.display());
+        process::exit(127);
+    }
+    #[cfg(not(unix))]
+    match command.status() {
+        Ok(status) => process::exit(status.code().unwrap_or(1)),
+        Err(err) => {
+            eprintln!("git-frecenfile: failed to run {}: {err}", frecenfile.display());
+            process::exit(127);
+        }
+    }
+}
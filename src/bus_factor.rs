@@ -0,0 +1,71 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use rustc_hash::FxHashMap as HashMap;
+use serde::Serialize;
+
+use crate::AnalyzeOptions;
+use crate::aggregate::group_path;
+use crate::owners::OwnershipMap;
+use crate::pipeline;
+
+/// A file or directory dominated by a single author
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BusFactorRisk {
+    #[serde(serialize_with = "crate::paths::serialize")]
+    pub path: PathBuf,
+    /// `Name <email>` of the dominant author, after applying `.mailmap`
+    pub author: String,
+    /// The author's fraction of the recency-weighted changes, 0–1
+    pub share: f64,
+    /// Total recency-weighted changes, on the same scale as file scores
+    pub weight: f64,
+}
+
+/// Files (or, with `depth`, directories truncated to that many components) where one
+/// author holds more than `threshold` (0–1) of the recency-weighted changes.
+///
+/// Sorted by total weight, most active first.
+pub fn analyze_bus_factor(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+    threshold: f64,
+    depth: Option<usize>,
+) -> Result<Vec<BusFactorRisk>> {
+    let OwnershipMap(map) = pipeline::run(repo_path, options)?;
+
+    let mut groups: HashMap<PathBuf, HashMap<String, f64>> = HashMap::default();
+    for (path, authors) in map {
+        let key = match depth {
+            Some(depth) => group_path(&path, depth),
+            None => path,
+        };
+        let group = groups.entry(key).or_default();
+        for (author, weight) in authors {
+            *group.entry(author).or_default() += weight;
+        }
+    }
+
+    let mut risks: Vec<BusFactorRisk> = groups
+        .into_iter()
+        .filter_map(|(path, authors)| {
+            let total: f64 = authors.values().sum();
+            let (author, top) = authors
+                .into_iter()
+                .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.cmp(&a.0)))?;
+            let share = if total > 0.0 { top / total } else { 0.0 };
+            (share > threshold).then_some(BusFactorRisk {
+                path,
+                author,
+                share,
+                weight: total,
+            })
+        })
+        .collect();
+    risks.sort_by(|a, b| {
+        b.weight
+            .total_cmp(&a.weight)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    Ok(risks)
+}
@@ -0,0 +1,672 @@
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::{fs, io};
+
+use anyhow::{Context, Result, anyhow, bail};
+use directories::ProjectDirs;
+use git2::{Oid, Repository};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::{debug, debug_span, trace, warn};
+
+use crate::intern::{PathId, PathTable, RunPathId};
+use crate::paths::{path_bytes, path_from_bytes};
+use crate::store::{CommitCache, FileStore, MemoryStore, SledStore};
+use crate::{AnalyzeOptions, open_repo, revwalk};
+
+/// Version of everything stored in the cache: bump it whenever `CommitStatics`, the
+/// patch-id entries or the way they are computed change, so stale entries are discarded
+const CACHE_VERSION: u32 = 7;
+
+/// Key holding the `CACHE_VERSION` a DB was written with; never a commit id
+const VERSION_KEY: &str = "__frecenfile_cache_version";
+
+/// Path table: `path:<id>` holds the bytes of a path, `path-id:<path bytes>` its id, and
+/// `PATH_COUNT_KEY` the number of ids handed out (a `u64`)
+const PATH_KEY_PREFIX: &str = "path:";
+const PATH_ID_KEY_PREFIX: &[u8] = b"path-id:";
+const PATH_COUNT_KEY: &str = "__frecenfile_path_count";
+
+/// Header of compressed values; values without it were stored as is (as all values were
+/// before compression)
+const COMPRESSED_HEADER: &[u8; 3] = b"\xffFZ";
+/// Follows `COMPRESSED_HEADER`: the value after the header is a zstd frame
+const ZSTD_FORMAT: u8 = 1;
+
+/// Values shorter than this are stored as is: compression would barely pay for the header
+const MIN_COMPRESSED_LEN: usize = 256;
+
+/// How the on-disk commit statistics cache is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CacheMode {
+    /// Read cached entries and store new ones
+    #[default]
+    ReadWrite,
+    /// Read an existing cache but never create or modify it. sled cannot open a database
+    /// read-only, so a sled cache is read from a private copy, taken while no writer holds it
+    ReadOnly,
+    /// Compute everything fresh and touch nothing on disk
+    Disabled,
+}
+
+/// Where the cache DB of a repository is stored
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum CacheLocation {
+    /// The per-user cache directory of the OS, one DB per repository (shared by its worktrees)
+    #[default]
+    UserCacheDir,
+    /// This directory, one DB per repository (shared by its worktrees)
+    Dir(PathBuf),
+    /// `frecenfile/` inside the repository's common git directory, removed along with it
+    GitDir,
+}
+
+/// Storage format of the on-disk cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CacheBackend {
+    /// A sled database directory
+    #[default]
+    Sled,
+    /// A single append-only log file, loaded into memory: no background threads, and simple
+    /// to copy around
+    File,
+}
+
+/// Per-repo commit statistics cache
+pub(crate) struct Cache {
+    /// None when disabled, or when a read-only cache does not exist yet
+    db: Option<Arc<dyn CommitCache>>,
+    read_only: bool,
+    /// Bytes that may still be written during this run; None = unlimited
+    write_budget: Option<AtomicU64>,
+    /// zstd level of new values; 0 stores them uncompressed
+    compression_level: i32,
+    /// Paths seen during this run, with their run ids and their ids in the path table
+    paths: Arc<PathTable>,
+    /// Whether a corrupt entry was reported during this run
+    reported_corruption: AtomicBool,
+}
+
+impl Cache {
+    /// Opens (or creates) the cache store unique to this repo, in OS-appropriate cache dir,
+    /// or uses `options.cache_store`. With `max_cache_bytes`, new entries stop being written
+    /// once the store reaches that size.
+    pub(crate) fn open(repo: &Repository, options: &AnalyzeOptions) -> Result<Self> {
+        let mode = options.cache_mode;
+        let _span = debug_span!("cache_open", ?mode, backend = ?options.cache_backend).entered();
+        let db = match (mode, &options.cache_store) {
+            (CacheMode::Disabled, _) => None,
+            (CacheMode::ReadOnly, Some(store)) => {
+                Some(store.clone()).filter(|store| is_current_version(&**store))
+            }
+            (CacheMode::ReadWrite, Some(store)) => {
+                discard_if_outdated(&**store)?;
+                Some(store.clone())
+            }
+            (CacheMode::ReadOnly, None) => {
+                let path = store_path(repo, options)?;
+                // A cache from another version is as good as none; never clear it here
+                match options.cache_backend {
+                    _ if !path.exists() => None,
+                    CacheBackend::Sled => match SledStore::open_snapshot(&path) {
+                        Ok(Some(db)) => Some(Arc::new(db) as Arc<dyn CommitCache>)
+                            .filter(|db| is_current_version(&**db)),
+                        Ok(None) => {
+                            debug!(
+                                path = %path.display(),
+                                "running without the cache: it is locked"
+                            );
+                            None
+                        }
+                        Err(err) => {
+                            warn!(
+                                path = %path.display(),
+                                "running without the unreadable cache: {err:#}"
+                            );
+                            None
+                        }
+                    },
+                    CacheBackend::File => match FileStore::open_read_only(&path) {
+                        Ok(db) => Some(Arc::new(db) as Arc<dyn CommitCache>)
+                            .filter(|db| is_current_version(&**db)),
+                        Err(err) => {
+                            warn!(
+                                path = %path.display(),
+                                "running without the unreadable cache: {err:#}"
+                            );
+                            None
+                        }
+                    },
+                }
+            }
+            (CacheMode::ReadWrite, None) => {
+                let path = store_path(repo, options)?;
+                open_or_rebuild(&path, options.cache_backend)
+            }
+        };
+
+        let write_budget = options.max_cache_bytes.map(|max| {
+            let used = db.as_ref().map_or(0, |db| db.size());
+            AtomicU64::new(max.saturating_sub(used))
+        });
+        debug!(
+            open = db.is_some(),
+            bytes = db.as_ref().map_or(0, |db| db.size()),
+            "cache ready"
+        );
+        Ok(Self {
+            db,
+            read_only: mode != CacheMode::ReadWrite,
+            write_budget,
+            compression_level: options.cache_compression_level,
+            paths: Arc::default(),
+            reported_corruption: AtomicBool::new(false),
+        })
+    }
+
+    /// The value stored under `key`, decompressed; None when missing or unreadable
+    pub(crate) fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let value = self.db.as_ref()?.get(key.as_bytes()).ok().flatten();
+        trace!(key, hit = value.is_some(), "cache get");
+        let value = value?;
+        match value.strip_prefix(COMPRESSED_HEADER) {
+            Some([ZSTD_FORMAT, frame @ ..]) => match zstd::decode_all(frame) {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    self.discard(key);
+                    None
+                }
+            },
+            // Written by a later version with another format
+            Some(_) => None,
+            None => Some(value.to_vec()),
+        }
+    }
+
+    /// Store `value`, compressed if it is large enough, unless the cache is read-only or
+    /// doing so would exceed the write budget; returns whether it was stored
+    pub(crate) fn insert(&self, key: &str, value: &[u8]) -> bool {
+        let Some(db) = self.writable_db() else {
+            return false;
+        };
+        let compressed;
+        let value = match self.compress(value) {
+            Some(bytes) => {
+                compressed = bytes;
+                &compressed[..]
+            }
+            None => value,
+        };
+        if !self.reserve(key.len() + value.len()) {
+            trace!(key, "cache write budget used up");
+            return false;
+        }
+        trace!(key, bytes = value.len(), "cache insert");
+        // A failed write only costs a recomputation next time
+        match db.insert(key.as_bytes(), value) {
+            Ok(()) => true,
+            Err(err) => {
+                debug!(key, "cache write failed: {err:#}");
+                false
+            }
+        }
+    }
+
+    /// Drop the entry under `key`, unless the cache is read-only
+    pub(crate) fn remove(&self, key: &str) {
+        if let Some(db) = self.writable_db() {
+            let _ = db.remove(key.as_bytes());
+        }
+    }
+
+    /// Drop the entry under `key`, which turned out to be corrupt, so it is recomputed and
+    /// stored afresh. Warns once per run.
+    pub(crate) fn discard(&self, key: &str) {
+        if !self.reported_corruption.swap(true, Ordering::Relaxed) {
+            warn!("ignoring corrupt cache entries; they are recomputed");
+        }
+        if let Some(db) = self.writable_db() {
+            let _ = db.remove(key.as_bytes());
+        }
+    }
+
+    /// Run id of `path`, and the copy of it shared by every change of this run that touches it
+    pub(crate) fn share_path(&self, path: &Path) -> (RunPathId, Arc<Path>) {
+        self.paths.share(path)
+    }
+
+    /// The paths of this run, which resolve run ids
+    pub(crate) fn path_table(&self) -> &Arc<PathTable> {
+        &self.paths
+    }
+
+    /// Id of `path` in the path table, which cached statistics refer to paths by; adds it
+    /// when it is new. None when it is not in the table and cannot be added.
+    pub(crate) fn path_id(&self, path: &Path) -> Option<PathId> {
+        if let Some(id) = self.paths.id(path) {
+            return Some(id);
+        }
+        let db = self.db.as_ref()?;
+        let id_key = [PATH_ID_KEY_PREFIX, path_bytes(path)].concat();
+        if let Some(id) = db.get(&id_key).ok().flatten().and_then(|id| decode_id(&id)) {
+            self.paths.record(path, id);
+            return Some(id);
+        }
+
+        let db = self.writable_db()?;
+        if !self.reserve(2 * (id_key.len() + 8)) {
+            return None;
+        }
+        let count = db.increment(PATH_COUNT_KEY.as_bytes()).ok()?;
+        let id = PathId::try_from(count.checked_sub(1)?).ok()?;
+        db.insert(
+            format!("{PATH_KEY_PREFIX}{id}").as_bytes(),
+            path_bytes(path),
+        )
+        .ok()?;
+        // Another process may have added the path meanwhile; its id wins
+        let id = match db.insert_if_absent(&id_key, &id.to_le_bytes()).ok()? {
+            None => id,
+            Some(current) => decode_id(&current)?,
+        };
+        self.paths.record(path, id);
+        Some(id)
+    }
+
+    /// Run id and shared copy of the path with id `id` in the path table
+    pub(crate) fn path(&self, id: PathId) -> Option<(RunPathId, Arc<Path>)> {
+        if let Some(path) = self.paths.path(id) {
+            return Some(path);
+        }
+        let bytes = self
+            .db
+            .as_ref()?
+            .get(format!("{PATH_KEY_PREFIX}{id}").as_bytes())
+            .ok()??;
+        Some(self.paths.record(&path_from_bytes(&bytes).ok()?, id))
+    }
+
+    fn writable_db(&self) -> Option<&Arc<dyn CommitCache>> {
+        self.db.as_ref().filter(|_| !self.read_only)
+    }
+
+    /// Take `bytes` from the write budget; false when it is used up
+    fn reserve(&self, bytes: usize) -> bool {
+        let Some(budget) = &self.write_budget else {
+            return true;
+        };
+        budget
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                left.checked_sub(bytes as u64)
+            })
+            .is_ok()
+    }
+
+    /// `value` with the compressed header, or None to store it as is. Values that happen to
+    /// start with the header are always compressed so they are not mistaken for one.
+    fn compress(&self, value: &[u8]) -> Option<Vec<u8>> {
+        let looks_compressed = value.starts_with(COMPRESSED_HEADER);
+        if !looks_compressed && (self.compression_level == 0 || value.len() < MIN_COMPRESSED_LEN) {
+            return None;
+        }
+        let frame = zstd::encode_all(value, self.compression_level).ok()?;
+        if !looks_compressed && frame.len() + COMPRESSED_HEADER.len() + 1 >= value.len() {
+            return None;
+        }
+        let mut compressed = Vec::with_capacity(COMPRESSED_HEADER.len() + 1 + frame.len());
+        compressed.extend_from_slice(COMPRESSED_HEADER);
+        compressed.push(ZSTD_FORMAT);
+        compressed.extend_from_slice(&frame);
+        Some(compressed)
+    }
+}
+
+/// Open (or create) a cache store, giving a concurrent frecenfile process holding its lock a
+/// moment to finish. None when it stays locked.
+fn open_unless_locked(path: &Path, backend: CacheBackend) -> Result<Option<Arc<dyn CommitCache>>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create cache directory {}", parent.display()))?;
+    }
+    Ok(match backend {
+        CacheBackend::Sled => SledStore::open(path)?.map(|store| Arc::new(store) as _),
+        CacheBackend::File => FileStore::open(path)?.map(|store| Arc::new(store) as _),
+    })
+}
+
+/// Open (or create) the cache store at `path` and clear it if it was written by another
+/// version. A store that cannot be opened or read, e.g. after a crash or a full disk
+/// corrupted it, is deleted and created afresh. While another process holds its lock, the
+/// run caches in memory instead, so commits it meets twice are still computed once. None
+/// when the store remains unusable: the run then proceeds uncached.
+fn open_or_rebuild(path: &Path, backend: CacheBackend) -> Option<Arc<dyn CommitCache>> {
+    let open = || -> Result<Arc<dyn CommitCache>> {
+        let db = match open_unless_locked(path, backend)? {
+            Some(db) => db,
+            None => {
+                debug!(path = %path.display(), "caching in memory: the cache is locked");
+                Arc::new(MemoryStore::default())
+            }
+        };
+        discard_if_outdated(&*db)?;
+        Ok(db)
+    };
+    let err = match open() {
+        Ok(db) => return Some(db),
+        Err(err) => err,
+    };
+    if path.exists() {
+        warn!(path = %path.display(), "rebuilding the corrupt cache: {err:#}");
+        if let Err(err) = remove_store(path) {
+            warn!(
+                path = %path.display(),
+                "running without the cache: failed to remove it: {err}"
+            );
+            return None;
+        }
+    }
+    match open() {
+        Ok(db) => Some(db),
+        Err(err) => {
+            warn!(path = %path.display(), "running without the cache: {err:#}");
+            None
+        }
+    }
+}
+
+/// The configured cache store for gc, export and import: `options.cache_store`, or the one
+/// at the cache location, which must not be in use. None when it does not exist and
+/// `create` is false.
+fn open_exclusive(
+    repo: &Repository,
+    options: &AnalyzeOptions,
+    create: bool,
+) -> Result<Option<Arc<dyn CommitCache>>> {
+    if let Some(store) = &options.cache_store {
+        return Ok(Some(store.clone()));
+    }
+    let path = store_path(repo, options)?;
+    if !create && !path.exists() {
+        return Ok(None);
+    }
+    let store = open_unless_locked(&path, options.cache_backend)
+        .with_context(|| format!("failed to open cache {}", path.display()))?
+        .ok_or_else(in_use)?;
+    Ok(Some(store))
+}
+
+fn in_use() -> anyhow::Error {
+    anyhow!("the cache is in use by another frecenfile process")
+}
+
+/// Outcome of [`gc_cache`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheGcStats {
+    /// Entries dropped because their commit is no longer reachable from any ref
+    pub removed: u64,
+    pub kept: u64,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+/// Remove cache entries of commits no longer reachable from any ref (after rebases,
+/// force-pushes or branch deletions), then compact the store by rewriting it. An injected
+/// `options.cache_store` has the entries removed in place instead.
+pub fn gc_cache(repo_path: &Path, options: &AnalyzeOptions) -> Result<CacheGcStats> {
+    let repo = open_repo(repo_path)?;
+    let Some(db) = open_exclusive(&repo, options, false)? else {
+        return Ok(CacheGcStats::default());
+    };
+    let reachable = revwalk::collect_reachable_commits(&repo)?;
+    let mut stats = CacheGcStats {
+        bytes_before: db.size(),
+        ..CacheGcStats::default()
+    };
+
+    let rewrite = match &options.cache_store {
+        Some(_) => None,
+        None => {
+            let path = store_path(&repo, options)?;
+            let mut compacted_path = path.clone().into_os_string();
+            compacted_path.push(".gc");
+            let compacted_path = PathBuf::from(compacted_path);
+            let _ = remove_store(&compacted_path);
+            let compacted = open_unless_locked(&compacted_path, options.cache_backend)
+                .with_context(|| format!("failed to create {}", compacted_path.display()))?
+                .ok_or_else(in_use)?;
+            compacted.insert(VERSION_KEY.as_bytes(), &CACHE_VERSION.to_le_bytes())?;
+            Some((path, compacted_path, compacted))
+        }
+    };
+    let current = is_current_version(&*db);
+    let mut removed = Vec::new();
+    db.for_each(&mut |key, value| {
+        if key == VERSION_KEY.as_bytes() {
+            return Ok(());
+        }
+        let keep =
+            is_path_table_key(key) || entry_commit(key).is_some_and(|oid| reachable.contains(&oid));
+        if current && keep {
+            if let Some((_, _, compacted)) = &rewrite {
+                compacted.insert(key, value)?;
+            }
+            stats.kept += 1;
+        } else {
+            removed.push(key.to_vec());
+            stats.removed += 1;
+        }
+        Ok(())
+    })?;
+
+    match rewrite {
+        Some((path, compacted_path, compacted)) => {
+            compacted.flush()?;
+            stats.bytes_after = compacted.size();
+            drop(compacted);
+            drop(db);
+            remove_store(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+            fs::rename(&compacted_path, &path)
+                .with_context(|| format!("failed to replace {}", path.display()))?;
+        }
+        None => {
+            if current {
+                for key in removed {
+                    db.remove(&key)?;
+                }
+            } else {
+                discard_if_outdated(&*db)?;
+            }
+            db.flush()?;
+            stats.bytes_after = db.size();
+        }
+    }
+    Ok(stats)
+}
+
+/// Delete a sled directory or a log file
+fn remove_store(path: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// First bytes of a cache archive written by [`export_cache`]
+const ARCHIVE_MAGIC: &[u8; 8] = b"FRECACHE";
+
+/// Write every entry of the repository's cache to `archive`, e.g. to restore a warm cache
+/// in CI with [`import_cache`]. Returns the number of entries written; 0 when there is no
+/// cache (or only one from another version).
+///
+/// The archive is the magic bytes and the cache version, followed by length-prefixed keys
+/// and values.
+pub fn export_cache(repo_path: &Path, options: &AnalyzeOptions, archive: &Path) -> Result<u64> {
+    let repo = open_repo(repo_path)?;
+    let db = open_exclusive(&repo, options, false)?.filter(|db| is_current_version(&**db));
+
+    let file = fs::File::create(archive)
+        .with_context(|| format!("failed to create {}", archive.display()))?;
+    let mut writer = io::BufWriter::new(file);
+    writer.write_all(ARCHIVE_MAGIC)?;
+    writer.write_all(&CACHE_VERSION.to_le_bytes())?;
+    let mut written = 0;
+    if let Some(db) = &db {
+        db.for_each(&mut |key, value| {
+            if key == VERSION_KEY.as_bytes() {
+                return Ok(());
+            }
+            for bytes in [key, value] {
+                writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+                writer.write_all(bytes)?;
+            }
+            written += 1;
+            Ok(())
+        })?;
+    }
+    writer
+        .flush()
+        .with_context(|| format!("failed to write {}", archive.display()))?;
+    Ok(written)
+}
+
+/// Replace the repository's cache with the entries of an archive written by
+/// [`export_cache`], creating it if needed. Returns the number of entries imported.
+pub fn import_cache(repo_path: &Path, options: &AnalyzeOptions, archive: &Path) -> Result<u64> {
+    let file =
+        fs::File::open(archive).with_context(|| format!("failed to open {}", archive.display()))?;
+    let mut reader = io::BufReader::new(file);
+    let mut header = [0; ARCHIVE_MAGIC.len() + 4];
+    reader
+        .read_exact(&mut header)
+        .with_context(|| format!("{} is not a frecenfile cache archive", archive.display()))?;
+    if &header[..ARCHIVE_MAGIC.len()] != ARCHIVE_MAGIC {
+        bail!("{} is not a frecenfile cache archive", archive.display());
+    }
+    let version = u32::from_le_bytes(header[ARCHIVE_MAGIC.len()..].try_into()?);
+    if version != CACHE_VERSION {
+        bail!(
+            "{} was written by another frecenfile version (cache version {version}, expected \
+             {CACHE_VERSION})",
+            archive.display()
+        );
+    }
+
+    let repo = open_repo(repo_path)?;
+    let db = open_exclusive(&repo, options, true)?.ok_or_else(in_use)?;
+    // Cached statistics refer to paths by their id in the path table they were written with
+    db.clear().context("failed to clear the cache")?;
+    db.insert(VERSION_KEY.as_bytes(), &CACHE_VERSION.to_le_bytes())
+        .context("failed to write cache version")?;
+
+    let mut imported = 0;
+    while let Some(key) = read_chunk(&mut reader, archive)? {
+        let value = read_chunk(&mut reader, archive)?
+            .ok_or_else(|| anyhow!("{} is truncated", archive.display()))?;
+        db.insert(&key, &value)?;
+        imported += 1;
+    }
+    db.flush()?;
+    Ok(imported)
+}
+
+/// One length-prefixed chunk of an archive; None at the end of the file
+fn read_chunk(reader: &mut impl Read, archive: &Path) -> Result<Option<Vec<u8>>> {
+    let mut len = [0; 8];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+    let mut chunk = vec![0; u64::from_le_bytes(len) as usize];
+    reader
+        .read_exact(&mut chunk)
+        .with_context(|| format!("{} is truncated", archive.display()))?;
+    Ok(Some(chunk))
+}
+
+/// Commit a cache key belongs to: statistics are keyed by the commit id (or
+/// `pathspec:<tag>:<commit id>` for restricted diffs), patch-ids by `patch-id:<commit id>`
+fn entry_commit(key: &[u8]) -> Option<Oid> {
+    let key = std::str::from_utf8(key).ok()?;
+    Oid::from_str(key.rsplit(':').next()?).ok()
+}
+
+/// Whether `key` belongs to the path table, which gc keeps whole: entries of reachable
+/// commits refer to it
+fn is_path_table_key(key: &[u8]) -> bool {
+    key.starts_with(PATH_KEY_PREFIX.as_bytes())
+        || key.starts_with(PATH_ID_KEY_PREFIX)
+        || key == PATH_COUNT_KEY.as_bytes()
+}
+
+fn decode_id(bytes: &[u8]) -> Option<PathId> {
+    Some(PathId::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn is_current_version(db: &dyn CommitCache) -> bool {
+    db.get(VERSION_KEY.as_bytes()).ok().flatten().as_deref()
+        == Some(&CACHE_VERSION.to_le_bytes()[..])
+}
+
+/// Clear a store written by another cache version (or before versioning existed)
+fn discard_if_outdated(db: &dyn CommitCache) -> Result<()> {
+    if is_current_version(db) {
+        return Ok(());
+    }
+    db.clear().context("failed to clear outdated cache")?;
+    db.insert(VERSION_KEY.as_bytes(), &CACHE_VERSION.to_le_bytes())
+        .context("failed to write cache version")?;
+    let _ = db.flush();
+    Ok(())
+}
+
+/// Where the store of `options.cache_backend` lives at `options.cache_location`
+fn store_path(repo: &Repository, options: &AnalyzeOptions) -> Result<PathBuf> {
+    match options.cache_backend {
+        CacheBackend::Sled => cache_db_path(repo, &options.cache_location),
+        CacheBackend::File => {
+            cache_file_path(repo, &options.cache_location, "cache.log", "cache.log")
+        }
+    }
+}
+
+/// `<common git dir>/frecenfile/cache.sled`, or `<cache dir>/<hash of the canonical common git
+/// dir>.sled`. Linked worktrees share the common git dir, and so one cache
+fn cache_db_path(repo: &Repository, location: &CacheLocation) -> Result<PathBuf> {
+    cache_file_path(repo, location, "cache.sled", "sled")
+}
+
+/// The editor access log, next to the cache DB
+pub(crate) fn access_log_path(repo: &Repository, location: &CacheLocation) -> Result<PathBuf> {
+    cache_file_path(repo, location, "access.log", "access.log")
+}
+
+/// `<common git dir>/frecenfile/<name>`, or `<cache dir>/<hash>.<extension>`
+fn cache_file_path(
+    repo: &Repository,
+    location: &CacheLocation,
+    name: &str,
+    extension: &str,
+) -> Result<PathBuf> {
+    let common_dir = repo.commondir();
+    let cache_dir = match location {
+        CacheLocation::GitDir => return Ok(common_dir.join("frecenfile").join(name)),
+        CacheLocation::Dir(dir) => dir.clone(),
+        CacheLocation::UserCacheDir => ProjectDirs::from("com", "kantord", "frecenfile")
+            .ok_or_else(|| anyhow!("unable to determine the user cache directory"))?
+            .cache_dir()
+            .to_path_buf(),
+    };
+
+    let absolute_path = common_dir
+        .canonicalize()
+        .with_context(|| format!("failed to canonicalize git dir {}", common_dir.display()))?;
+    let mut hasher = Sha256::new();
+    hasher.update(path_bytes(&absolute_path));
+    let path_hash = hex::encode(&hasher.finalize()[0..16]);
+
+    Ok(cache_dir.join(format!("{}.{}", path_hash, extension)))
+}
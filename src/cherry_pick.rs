@@ -0,0 +1,68 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use git2::{Oid, Repository};
+use rayon::prelude::*;
+
+use crate::cache::Cache;
+use crate::pipeline::with_worker_repo;
+use crate::statics::StaticsMode;
+
+const PATCH_ID_PREFIX: &str = "patch-id:";
+
+/// Drop commits whose patch-id matches an older commit in `oids` (newest first), so a
+/// change cherry-picked onto several branches only counts once, at its original date
+pub(crate) fn dedup_cherry_picks(
+    oids: Vec<Oid>,
+    mode: StaticsMode,
+    cache: &Cache,
+) -> Result<Vec<Oid>> {
+    let patch_ids: Vec<Option<Oid>> = oids
+        .par_iter()
+        .map(|oid| with_worker_repo(mode, |repo| cached_patch_id(repo, *oid, cache)))
+        .collect::<Result<_>>()?;
+
+    let mut seen = HashSet::new();
+    let mut keep = vec![true; oids.len()];
+    for (idx, patch_id) in patch_ids.iter().enumerate().rev() {
+        if let Some(id) = patch_id {
+            keep[idx] = seen.insert(*id);
+        }
+    }
+
+    Ok(oids
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(oid, keep)| keep.then_some(oid))
+        .collect())
+}
+
+fn cached_patch_id(repo: &Repository, oid: Oid, cache: &Cache) -> Option<Oid> {
+    let key = format!("{PATCH_ID_PREFIX}{oid}");
+    if let Some(bytes) = cache.get(&key) {
+        match Oid::from_bytes(&bytes) {
+            Ok(id) => return Some(id).filter(|id| !id.is_zero()),
+            Err(_) => cache.discard(&key),
+        }
+    }
+
+    let patch_id = compute_patch_id(repo, oid).ok().flatten();
+    let stored = patch_id.unwrap_or_else(Oid::zero);
+    cache.insert(&key, stored.as_bytes());
+    patch_id
+}
+
+/// Patch-id of a commit's diff against its first parent; None for merges and empty commits
+fn compute_patch_id(repo: &Repository, oid: Oid) -> Result<Option<Oid>, git2::Error> {
+    let commit = repo.find_commit(oid)?;
+    if commit.parent_count() > 1 {
+        return Ok(None);
+    }
+    let tree = commit.tree()?;
+    let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+    if diff.deltas().len() == 0 {
+        return Ok(None);
+    }
+    diff.patchid(None).map(Some)
+}
@@ -0,0 +1,1370 @@
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, bail};
+use chrono::{DateTime, NaiveDate, NaiveTime};
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use frecenfile::{
+    AnalyzeOptions, AuthorBoost, CacheBackend, CacheLocation, CacheMode, ComplexityMetric,
+    DEFAULT_FIX_PATTERN, DefaultScorer, HistoryInterval, ScoreDisplay, ScoreMode, SizePenalty,
+    Topology, even_tier_boundaries, path_from_bytes, user_email,
+};
+use regex::Regex;
+
+use crate::template::Template;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "frecenfile",
+    version,
+    about = "Compute frecency scores for files in a Git repository",
+    args_conflicts_with_subcommands = true,
+    disable_version_flag = true
+)]
+pub struct Cli {
+    /// Print version; combine with `--format json` for the output format version too
+    #[arg(short = 'V', long = "version")]
+    pub version: bool,
+
+    /// Keep running and print the scores again whenever new commits arrive; with
+    /// `--format jsonl`, each update starts with a new `metadata` record
+    #[arg(long = "watch")]
+    pub watch: bool,
+
+    /// Seconds between checks for new commits in `--watch` mode
+    #[arg(long = "watch-interval", value_name = "SECONDS", default_value_t = 2)]
+    pub watch_interval: u64,
+
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Diagnostics written to stderr, for debugging slow or surprising results
+#[derive(Args, Debug)]
+pub struct LogArgs {
+    /// Log what the analysis does: -v for the walk, its waves and the cache, -vv for every
+    /// commit and cache access
+    #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// Format of the log lines
+    #[arg(
+        long = "log-format",
+        value_enum,
+        default_value_t = LogFormat::Human,
+        global = true
+    )]
+    pub log_format: LogFormat,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// One readable line per event
+    Human,
+    /// One JSON object per event, with the fields of the enclosing spans
+    Json,
+}
+
+impl Cli {
+    /// Parse the command line; run as `git frecenfile`, help and errors use that name
+    pub fn parse_invoked() -> Self {
+        let mut command = Cli::command();
+        if invoked_as_git_subcommand() {
+            command = command.bin_name("git frecenfile");
+        }
+        Cli::from_arg_matches(&command.get_matches()).unwrap_or_else(|err| err.exit())
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Fail when the current history makes too many new files hot compared to a snapshot
+    Gate(GateArgs),
+    /// Report files whose frecency is rising or falling between two time windows
+    Trend(TrendArgs),
+    /// Print a time series of one file's frecency and activity, e.g. for charts
+    History(HistoryArgs),
+    /// Compare the scores of two revisions, e.g. a feature branch against main
+    Diff(DiffArgs),
+    /// Report files that are both hot and large: classic maintenance hotspots
+    Hotspot(HotspotArgs),
+    /// List files that have not changed for a long time, oldest first: candidates for deletion
+    Stale(StaleArgs),
+    /// Rank files by frecency times the share of their commits that were bug fixes
+    Risk(RiskArgs),
+    /// Show the recency-weighted top contributors of files
+    Owners(OwnersArgs),
+    /// Rank contributors by their recency-weighted activity, optionally within some paths
+    Authors(AuthorsArgs),
+    /// Flag files or directories where a single author holds most of the recent changes
+    BusFactor(BusFactorArgs),
+    /// Maintain the on-disk commit statistics cache
+    Cache(CacheArgs),
+    /// Fill the cache with the statistics of the selected commits without printing scores,
+    /// e.g. from cron jobs or post-clone hooks
+    Warm(WarmArgs),
+    /// Manage git hooks that keep the cache warm after commits and merges
+    Hook(HookArgs),
+    /// Store the scores as a git note on HEAD, to push and share them without recomputing
+    Export(ExportArgs),
+    /// Write a shareable Markdown or HTML report of top files, directories, hotspots and trends
+    Report(ReportArgs),
+    /// Keep the scores warm and answer JSON-RPC, HTTP or MCP requests, e.g. for editor
+    /// extensions, dashboards and AI assistants
+    Serve(ServeArgs),
+    /// Browse the files hottest first with a filter and preview; Enter prints the selection
+    Tui(TuiArgs),
+    /// Log that a file was opened in an editor, for `--blend-access`
+    RecordAccess(RecordAccessArgs),
+    /// Print the hottest file matching a substring or glob, e.g. `vim $(frecenfile query handler)`
+    Query(QueryArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    /// Speak newline-delimited JSON-RPC 2.0 on stdin and stdout
+    #[arg(long = "stdio", conflicts_with_all = ["http", "mcp"])]
+    pub stdio: bool,
+
+    /// Act as a Model Context Protocol server on stdin and stdout, offering the tools
+    /// top_files, related_files and file_history_summary to AI coding assistants
+    #[arg(long = "mcp", conflicts_with = "http")]
+    pub mcp: bool,
+
+    /// Serve JSON over HTTP on this address, e.g. `127.0.0.1:7878`
+    #[arg(long = "http", value_name = "ADDR")]
+    pub http: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct TuiArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct RecordAccessArgs {
+    /// The opened file; its repository is used unless --repo is given
+    #[arg(value_name = "PATH")]
+    pub path: PathBuf,
+
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct QueryArgs {
+    /// Substring of the path (case-insensitive unless it has uppercase letters), or a glob when it
+    /// contains `*`, `?`, `[` or `{`
+    #[arg(value_name = "SUBSTRING-OR-GLOB")]
+    pub pattern: String,
+
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ReportArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    /// Report format
+    #[arg(long = "format", value_enum, default_value_t = ReportFormat::Md)]
+    pub format: ReportFormat,
+
+    /// Number of entries in each section
+    #[arg(short = 't', long = "top", value_name = "N", default_value_t = 10)]
+    pub top: usize,
+
+    /// Path components used to group files into directories
+    #[arg(long = "depth", value_name = "N", default_value_t = 1)]
+    pub depth: usize,
+
+    /// Length of each compared trend window, in days
+    #[arg(long = "window-days", value_name = "DAYS", default_value_t = 30)]
+    pub window_days: u32,
+
+    /// Write the report to FILE instead of stdout
+    #[arg(short = 'o', long = "output", value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    /// Notes ref to write to, e.g. `refs/notes/frecenfile`
+    #[arg(long = "notes", value_name = "REF")]
+    pub notes: String,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// Markdown, for wikis and PR descriptions
+    Md,
+    /// A standalone HTML page
+    Html,
+}
+
+#[derive(Args, Debug)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub action: CacheAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheAction {
+    /// Drop entries of commits no longer reachable from any ref and compact the cache
+    Gc(CacheGcArgs),
+    /// Write the cache to a portable archive, e.g. to save it as a CI artifact
+    Export(CacheTransferArgs),
+    /// Replace the cache with an archive written by `cache export`
+    Import(CacheTransferArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct CacheTransferArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    /// Archive file
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct WarmArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    /// Only warm commits made at or after this time (same formats as --as-of)
+    #[arg(long = "since", value_name = "TIME", value_parser = parse_timestamp)]
+    pub since: Option<i64>,
+}
+
+#[derive(Args, Debug)]
+pub struct HookArgs {
+    #[command(subcommand)]
+    pub action: HookAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HookAction {
+    /// Add a post-commit and post-merge hook that runs `frecenfile warm` in the background;
+    /// arguments after `--` are passed to it, e.g. `-- --skip-binary`
+    Install(HookInstallArgs),
+    /// Remove the hooks added by `hook install`, keeping any other hook content
+    Uninstall(HookUninstallArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct HookInstallArgs {
+    /// Path to the Git repository
+    #[arg(short = 'D', long = "repo", value_name = "REPO", default_value = ".")]
+    pub repo: PathBuf,
+
+    /// Arguments for the `frecenfile warm` run in the hook
+    #[arg(last = true, value_name = "ARGS")]
+    pub args: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct HookUninstallArgs {
+    /// Path to the Git repository
+    #[arg(short = 'D', long = "repo", value_name = "REPO", default_value = ".")]
+    pub repo: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct CacheGcArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    /// Output format
+    #[arg(long = "format", value_enum, default_value_t = OutputFormat::Human)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct BusFactorArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    /// Flag paths where one author holds more than this percentage of the weighted changes
+    #[arg(long = "threshold", value_name = "PERCENT", default_value_t = 80.0)]
+    pub threshold: f64,
+
+    /// Report directories truncated to this many path components instead of files
+    #[arg(long = "depth", value_name = "N")]
+    pub depth: Option<usize>,
+
+    /// Number of entries to show; 0 shows all
+    #[arg(short = 'l', long = "limit", value_name = "N", default_value_t = 20)]
+    pub limit: usize,
+
+    /// Output format
+    #[arg(long = "format", value_enum, default_value_t = OutputFormat::Human)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct AuthorsArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    /// Only count changes to these files or directories
+    #[arg(value_name = "PATH")]
+    pub paths: Vec<PathBuf>,
+
+    /// Number of authors to show; 0 shows all
+    #[arg(short = 'l', long = "limit", value_name = "N", default_value_t = 20)]
+    pub limit: usize,
+
+    /// Output format
+    #[arg(long = "format", value_enum, default_value_t = OutputFormat::Human)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct OwnersArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    /// Files to report on; omit to report on every file
+    #[arg(value_name = "PATH")]
+    pub files: Vec<PathBuf>,
+
+    /// Number of contributors to show per file
+    #[arg(short = 't', long = "top", value_name = "N", default_value_t = 3)]
+    pub top: usize,
+
+    /// Output format
+    #[arg(long = "format", value_enum, default_value_t = OutputFormat::Human)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct HotspotArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    /// Complexity proxy measured at HEAD
+    #[arg(long = "metric", value_enum, default_value_t = MetricArg::Lines)]
+    pub metric: MetricArg,
+
+    /// Number of hotspots to show; 0 shows all
+    #[arg(short = 'l', long = "limit", value_name = "N", default_value_t = 20)]
+    pub limit: usize,
+
+    /// Output format
+    #[arg(long = "format", value_enum, default_value_t = OutputFormat::Human)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct StaleArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    /// Only files whose last change is at least this many days old
+    #[arg(long = "older-than", value_name = "DAYS", default_value_t = 365)]
+    pub older_than: u64,
+
+    /// Size measure used by `--min-size`
+    #[arg(long = "metric", value_enum, default_value_t = MetricArg::Lines)]
+    pub metric: MetricArg,
+
+    /// Only files at least this large at HEAD, in lines or bytes per `--metric`
+    #[arg(long = "min-size", value_name = "N", default_value_t = 0)]
+    pub min_size: u64,
+
+    /// Number of files to show; 0 shows all
+    #[arg(short = 'l', long = "limit", value_name = "N", default_value_t = 0)]
+    pub limit: usize,
+
+    /// Output format
+    #[arg(long = "format", value_enum, default_value_t = OutputFormat::Human)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct RiskArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    /// Commits whose message matches this regex count as fixes
+    #[arg(long = "fix-pattern", value_name = "REGEX", default_value = DEFAULT_FIX_PATTERN)]
+    pub fix_pattern: Regex,
+
+    /// Number of files to show; 0 shows all
+    #[arg(short = 'l', long = "limit", value_name = "N", default_value_t = 20)]
+    pub limit: usize,
+
+    /// Output format
+    #[arg(long = "format", value_enum, default_value_t = OutputFormat::Human)]
+    pub format: OutputFormat,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum MetricArg {
+    /// Line count
+    Lines,
+    /// Size in bytes
+    Bytes,
+}
+
+impl From<MetricArg> for ComplexityMetric {
+    fn from(arg: MetricArg) -> Self {
+        match arg {
+            MetricArg::Lines => ComplexityMetric::Lines,
+            MetricArg::Bytes => ComplexityMetric::Bytes,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SizePenaltyArg {
+    /// No penalty (skips all blob access)
+    None,
+    /// 1 / (1 + sqrt(KiB))
+    Sqrt,
+    /// 1 / (1 + ln(1 + KiB)), gentler on large files
+    Log,
+    /// 1 / (1 + KiB), harsher on large files
+    Linear,
+}
+
+impl From<SizePenaltyArg> for SizePenalty {
+    fn from(arg: SizePenaltyArg) -> Self {
+        match arg {
+            SizePenaltyArg::None => SizePenalty::None,
+            SizePenaltyArg::Sqrt => SizePenalty::Sqrt,
+            SizePenaltyArg::Log => SizePenalty::Log,
+            SizePenaltyArg::Linear => SizePenalty::Linear,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ModeArg {
+    /// Recency-weighted change frequency
+    Frecency,
+    /// Most recently changed files first
+    Recency,
+    /// Most frequently changed files first
+    Frequency,
+}
+
+impl From<ModeArg> for ScoreMode {
+    fn from(arg: ModeArg) -> Self {
+        match arg {
+            ModeArg::Frecency => ScoreMode::Frecency,
+            ModeArg::Recency => ScoreMode::Recency,
+            ModeArg::Frequency => ScoreMode::Frequency,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TopologyArg {
+    /// Follow only first parents (the mainline)
+    FirstParent,
+    /// Every reachable commit, including those on merged side branches
+    Full,
+}
+
+impl From<TopologyArg> for Topology {
+    fn from(arg: TopologyArg) -> Self {
+        match arg {
+            TopologyArg::FirstParent => Topology::FirstParent,
+            TopologyArg::Full => Topology::Full,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct TrendArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    /// Length of each compared window, in days
+    #[arg(long = "window-days", value_name = "DAYS", default_value_t = 30)]
+    pub window_days: u32,
+
+    /// Number of rising and of falling files to show
+    #[arg(short = 'l', long = "limit", value_name = "N", default_value_t = 20)]
+    pub limit: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct HistoryArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    /// File to chart
+    #[arg(value_name = "PATH")]
+    pub path: PathBuf,
+
+    /// Length of each bucket of time
+    #[arg(long = "interval", value_enum, default_value_t = IntervalArg::Week)]
+    pub interval: IntervalArg,
+
+    /// Output format
+    #[arg(long = "format", value_enum, default_value_t = HistoryFormat::Human)]
+    pub format: HistoryFormat,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CacheBackendArg {
+    /// A sled database directory
+    Sled,
+    /// A single append-only log file
+    File,
+}
+
+impl From<CacheBackendArg> for CacheBackend {
+    fn from(arg: CacheBackendArg) -> Self {
+        match arg {
+            CacheBackendArg::Sled => CacheBackend::Sled,
+            CacheBackendArg::File => CacheBackend::File,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum IntervalArg {
+    Day,
+    /// Weeks starting on Monday
+    Week,
+    Month,
+}
+
+impl From<IntervalArg> for HistoryInterval {
+    fn from(arg: IntervalArg) -> Self {
+        match arg {
+            IntervalArg::Day => HistoryInterval::Day,
+            IntervalArg::Week => HistoryInterval::Week,
+            IntervalArg::Month => HistoryInterval::Month,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum HistoryFormat {
+    /// Aligned date, score and activity columns
+    Human,
+    /// `start,end,score,activity,commits` with dates as YYYY-MM-DD
+    Csv,
+    /// A JSON array of objects with Unix timestamps
+    Json,
+    /// One JSON object per line
+    Jsonl,
+}
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    /// Base revision
+    #[arg(value_name = "REF1")]
+    pub base: String,
+
+    /// Revision compared against the base
+    #[arg(value_name = "REF2")]
+    pub head: String,
+
+    /// Number of gaining and of losing files to show; 0 shows all
+    #[arg(short = 'l', long = "limit", value_name = "N", default_value_t = 20)]
+    pub limit: usize,
+
+    /// Output format
+    #[arg(long = "format", value_enum, default_value_t = OutputFormat::Human)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct GateArgs {
+    #[command(flatten)]
+    pub analysis: AnalysisArgs,
+
+    /// Baseline scores, as written by `frecenfile --format json`
+    #[arg(long = "snapshot", value_name = "FILE")]
+    pub snapshot: PathBuf,
+
+    /// Maximum number of files allowed to newly enter the hot set
+    #[arg(long = "max-new-hot", value_name = "N", default_value_t = 0)]
+    pub max_new_hot: usize,
+
+    /// Size of the hot set, as a percentage of all scored files
+    #[arg(long = "top-percent", value_name = "PERCENT", default_value_t = 10.0)]
+    pub top_percent: f64,
+}
+
+/// Options shared by every command that scores the history
+#[derive(Args, Debug)]
+pub struct AnalysisArgs {
+    /// Path to the Git repository (defaults to current directory, or `GIT_DIR` when set);
+    /// repeat to rank the files of several repositories together
+    #[arg(short = 'D', long = "repo", value_name = "REPO", default_value = ".")]
+    pub repos: Vec<PathBuf>,
+
+    /// Only analyze files under this directory and print paths relative to it
+    #[arg(long = "prefix", value_name = "DIR")]
+    pub prefix: Option<PathBuf>,
+
+    /// Relative paths to include; omit to include all files.
+    #[arg(short, long = "paths", value_name = "PATH", num_args = 1..)]
+    pub paths: Vec<PathBuf>,
+
+    /// Read paths to include from FILE (`-` for stdin), one per line or NUL-separated;
+    /// combined with `--paths`
+    #[arg(long = "paths-from", value_name = "FILE")]
+    pub paths_from: Option<PathBuf>,
+
+    /// Match --paths and --prefix case-insensitively and merge files whose names only
+    /// differ in case, e.g. after a `Readme.md` → `README.md` rename
+    #[arg(long = "ignore-case")]
+    pub ignore_case: bool,
+
+    /// Maximum number of commits to inspect (newest first). \
+    /// Use 0 for “no limit”.
+    #[arg(
+        short = 'n',
+        long = "max-commits",
+        value_name = "N",
+        default_value_t = 3000
+    )]
+    pub max_commits: usize,
+
+    /// Write a commit-graph (`git commit-graph write --reachable`) first if the repository
+    /// has none, which speeds up walking long histories
+    #[arg(long = "write-commit-graph")]
+    pub write_commit_graph: bool,
+
+    /// Also count commits on every ref: all branches, remote-tracking branches and tags
+    #[arg(long = "all")]
+    pub all: bool,
+
+    /// Also count commits on every local branch
+    #[arg(long = "branches")]
+    pub branches: bool,
+
+    /// Also count commits on every tag
+    #[arg(long = "tags")]
+    pub tags: bool,
+
+    /// Only count commits since HEAD forked from BASE (e.g. `main`): what this branch touches
+    #[arg(long = "since-merge-base", value_name = "BASE")]
+    pub since_merge_base: Option<String>,
+
+    /// Also rank the files of initialized submodules, prefixed with the submodule path
+    #[arg(long = "recurse-submodules")]
+    pub recurse_submodules: bool,
+
+    /// Walk only first parents, or also count commits on merged side branches
+    #[arg(long = "topology", value_enum, default_value_t = TopologyArg::FirstParent)]
+    pub topology: TopologyArg,
+
+    /// Skip this revision when scoring (repeatable)
+    #[arg(long = "ignore-rev", value_name = "REV")]
+    pub ignore_revs: Vec<String>,
+
+    /// Skip revisions listed in this file (repeatable), like `git blame --ignore-revs-file`
+    #[arg(long = "ignore-revs-file", value_name = "FILE")]
+    pub ignore_revs_files: Vec<PathBuf>,
+
+    /// Do not read the repository's .git-blame-ignore-revs file
+    #[arg(long = "no-blame-ignore-revs")]
+    pub no_blame_ignore_revs: bool,
+
+    /// Only count commits whose author (`Name <email>`) matches this regex (repeatable)
+    #[arg(long = "author", value_name = "PATTERN")]
+    pub authors: Vec<Regex>,
+
+    /// Only count your own commits, by the `user.email` of the git config
+    #[arg(long = "mine")]
+    pub mine: bool,
+
+    /// Multiply the weight of commits by matching authors, e.g. `me@example.com=3` (repeatable)
+    #[arg(long = "boost-author", value_name = "PATTERN=FACTOR")]
+    pub author_boosts: Vec<AuthorBoost>,
+
+    /// Skip commits whose author (`Name <email>`) matches this regex, e.g. `\[bot\]` (repeatable)
+    #[arg(long = "exclude-author-regex", value_name = "REGEX")]
+    pub exclude_authors: Vec<Regex>,
+
+    /// Skip commits whose message matches this regex, e.g. `^chore` (repeatable)
+    #[arg(long = "exclude-message-regex", value_name = "REGEX")]
+    pub exclude_messages: Vec<Regex>,
+
+    /// Count cherry-picked copies of a change once (matched by patch-id)
+    #[arg(long = "dedup-cherry-picks")]
+    pub dedup_cherry_picks: bool,
+
+    /// Skip the penalty for large files, and with it all blob access (much faster on cold runs)
+    #[arg(long = "no-size-penalty")]
+    pub no_size_penalty: bool,
+
+    /// Leave out files whose blob is larger than this (e.g. 1M), such as data fixtures and snapshots
+    #[arg(long = "max-file-size", value_name = "BYTES", value_parser = parse_byte_size)]
+    pub max_file_size: Option<u64>,
+
+    /// How quickly the weight of changes falls off with file size
+    #[arg(long = "size-penalty", value_enum, default_value_t = SizePenaltyArg::Sqrt)]
+    pub size_penalty: SizePenaltyArg,
+
+    /// Measure sizes in units of this many KiB for the size penalty
+    #[arg(long = "size-penalty-scale", value_name = "KIB", default_value_t = 1.0)]
+    pub size_penalty_scale: f64,
+
+    /// Look up blob sizes of huge commits on a single worker instead of spreading them out
+    #[arg(long = "no-split-large-commits")]
+    pub no_split_large_commits: bool,
+
+    /// Most that one commit can add to one file's score (overrides `max_commit_contribution`
+    /// in `.frecenfile.toml`)
+    #[arg(long = "max-commit-contribution", value_name = "SCORE")]
+    pub max_commit_contribution: Option<f64>,
+
+    /// Ignore the settings in the repository's `.frecenfile.toml`
+    #[arg(long = "no-config")]
+    pub no_config: bool,
+
+    /// Mix in the editor opens logged by `frecenfile record-access`, each counting like this many
+    /// commits at the time of the open
+    #[arg(long = "blend-access", value_name = "WEIGHT")]
+    pub blend_access: Option<f64>,
+
+    /// Boost files with uncommitted changes (modified, staged or untracked), as if committed now
+    #[arg(long = "include-worktree")]
+    pub include_worktree: bool,
+
+    /// How many commits of today an uncommitted change counts as with `--include-worktree`
+    #[arg(long = "worktree-weight", value_name = "WEIGHT", default_value_t = 1.0)]
+    pub worktree_weight: f64,
+
+    /// Multiply the weight of commits whose message references an issue or PR (`#1234`,
+    /// `JIRA-567`)
+    #[arg(long = "issue-weight", value_name = "WEIGHT")]
+    pub issue_weight: Option<f64>,
+
+    /// List the issues referenced by each file's commits (in the `issues` field of JSON output)
+    #[arg(long = "issue-refs")]
+    pub issue_refs: bool,
+
+    /// Regex for issue references, instead of `#1234` and `ABC-123`
+    #[arg(long = "issue-pattern", value_name = "REGEX")]
+    pub issue_pattern: Option<Regex>,
+
+    /// Boost files switched by recent checkouts, rebases and resets in the HEAD reflog (local to
+    /// this machine)
+    #[arg(long = "use-reflog")]
+    pub use_reflog: bool,
+
+    /// How many commits one reflog entry counts as with `--use-reflog`, spread over its files
+    #[arg(long = "reflog-weight", value_name = "WEIGHT", default_value_t = 1.0)]
+    pub reflog_weight: f64,
+
+    /// Also count generated files (`linguist-generated` or `-diff` in `.gitattributes`,
+    /// lockfiles, `*.min.js`, `*.pb.go`, `dist/`, ...)
+    #[arg(long = "no-default-excludes")]
+    pub no_default_excludes: bool,
+
+    /// Leave binary files such as images and other assets out of the results (slower on a cold
+    /// cache)
+    #[arg(long = "skip-binary")]
+    pub skip_binary: bool,
+
+    /// Don't count changes to a file that only touch whitespace (slower on a cold cache)
+    #[arg(long = "ignore-whitespace")]
+    pub ignore_whitespace: bool,
+
+    /// Don't count changes to a file of fewer than N lines, e.g. formatter touch-ups
+    #[arg(long = "min-changed-lines", value_name = "N")]
+    pub min_changed_lines: Option<u64>,
+
+    /// Rank by frecency, or only by how recently or how often files changed
+    #[arg(long = "mode", value_enum, default_value_t = ModeArg::Frecency)]
+    pub mode: ModeArg,
+
+    /// Also report each score's frequency (commit count) and recency (days since the last
+    /// change) components
+    #[arg(long = "breakdown")]
+    pub breakdown: bool,
+
+    /// Compute per-file line churn (slower on a cold cache)
+    #[arg(long = "churn")]
+    pub churn: bool,
+
+    /// Number of worker threads (defaults to one per core)
+    #[arg(short = 'j', long = "threads", value_name = "N")]
+    pub threads: Option<usize>,
+
+    /// Commits each worker processes per wave; adapts to the cost per commit by default
+    #[arg(long = "chunk-size", value_name = "N")]
+    pub chunk_size: Option<usize>,
+
+    /// Maximum number of repository handles (and worker threads) open at once
+    #[arg(long = "max-open-repos", value_name = "N")]
+    pub max_open_repos: Option<usize>,
+
+    /// Store the cache under this directory instead of the user cache directory
+    #[arg(long = "cache-dir", value_name = "DIR", env = "FRECENFILE_CACHE_DIR")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Store the cache inside the repository's git directory (`.git/frecenfile/`);
+    /// takes precedence over `--cache-dir`
+    #[arg(long = "cache-in-git-dir")]
+    pub cache_in_git_dir: bool,
+
+    /// Compute everything fresh without reading or writing the on-disk cache
+    #[arg(long = "no-cache", conflicts_with = "cache_readonly")]
+    pub no_cache: bool,
+
+    /// Use the on-disk cache if it exists, but never create or modify it. A sled cache is
+    /// copied first, since sled cannot open a database read-only
+    #[arg(long = "cache-readonly")]
+    pub cache_readonly: bool,
+
+    /// Storage format of the on-disk cache
+    #[arg(long = "cache-backend", value_enum, default_value_t = CacheBackendArg::Sled)]
+    pub cache_backend: CacheBackendArg,
+
+    /// Stop adding to the on-disk cache once it reaches this size (e.g. 512M, 2G)
+    #[arg(long = "max-cache-bytes", value_name = "BYTES", value_parser = parse_byte_size)]
+    pub max_cache_bytes: Option<u64>,
+
+    /// zstd level for new cache entries (1-22); 0 stores them uncompressed
+    #[arg(
+        long = "cache-compression",
+        value_name = "LEVEL",
+        default_value_t = 3,
+        value_parser = clap::value_parser!(i32).range(0..=22)
+    )]
+    pub cache_compression: i32,
+
+    /// Abort cleanly when the score table would need more memory than this (e.g. 4G)
+    #[arg(long = "max-memory", value_name = "BYTES", value_parser = parse_byte_size)]
+    pub max_memory: Option<u64>,
+
+    /// Past --max-memory, move the scores aggregated so far to temporary files and carry on
+    /// instead of aborting
+    #[arg(long = "spill-to-disk", requires = "max_memory")]
+    pub spill_to_disk: bool,
+
+    /// Measure commit ages from this time instead of now, for reproducible output:
+    /// Unix seconds, `YYYY-MM-DD` (midnight UTC) or RFC 3339
+    #[arg(long = "as-of", value_name = "TIME", value_parser = parse_timestamp)]
+    pub as_of: Option<i64>,
+
+    /// Score as if run at this time: only count commits up to it and measure ages from it,
+    /// e.g. to backtest rankings. Same formats as --as-of
+    #[arg(
+        long = "at",
+        value_name = "TIME",
+        value_parser = parse_timestamp,
+        conflicts_with_all = ["as_of", "include_worktree", "blend_access", "use_reflog"]
+    )]
+    pub at: Option<i64>,
+}
+
+impl AnalysisArgs {
+    /// The repository of commands that analyze a single one
+    pub fn repo(&self) -> anyhow::Result<&Path> {
+        match self.repos.as_slice() {
+            [repo] => Ok(repo),
+            _ => bail!("this command analyzes a single repository; pass --repo only once"),
+        }
+    }
+
+    /// The user's directory relative to the work tree root, when paths on the command line are
+    /// relative to it: under `git frecenfile`, and in git aliases, which run at the top level
+    /// with `GIT_PREFIX` set
+    fn cwd_prefix(&self) -> anyhow::Result<Option<PathBuf>> {
+        if self.repos != [PathBuf::from(".")] {
+            return Ok(None);
+        }
+        if let Some(dir) = alias_dir() {
+            return Ok(Some(dir));
+        }
+        if !invoked_as_git_subcommand() {
+            return Ok(None);
+        }
+        let repo = git2::Repository::discover(".")?;
+        let Some(workdir) = repo.workdir() else {
+            return Ok(None);
+        };
+        let cwd = env::current_dir()?.canonicalize()?;
+        Ok(cwd
+            .strip_prefix(workdir.canonicalize()?)
+            .ok()
+            .map(Path::to_path_buf))
+    }
+
+    /// `--author` patterns, plus with `--mine` the user's email in each repository
+    fn authors(&self) -> anyhow::Result<Vec<Regex>> {
+        let mut authors = self.authors.clone();
+        if self.mine {
+            for repo in &self.repos {
+                let email = user_email(repo)?;
+                authors.push(Regex::new(&format!("(?i)<{}>$", regex::escape(&email)))?);
+            }
+        }
+        Ok(authors)
+    }
+
+    pub fn to_options(&self) -> anyhow::Result<AnalyzeOptions> {
+        let cwd_prefix = self.cwd_prefix()?;
+        let from_cwd = |path: &Path| match &cwd_prefix {
+            Some(dir) => normalize(&dir.join(path)),
+            None => path.to_path_buf(),
+        };
+        // Paths are relative to the prefix when one is given
+        let prefix = self
+            .prefix
+            .as_deref()
+            .map(from_cwd)
+            .filter(|prefix| !prefix.as_os_str().is_empty());
+        let from_cwd = |path: &PathBuf| match self.prefix {
+            Some(_) => path.clone(),
+            None => from_cwd(path),
+        };
+
+        let filter = match &self.paths_from {
+            Some(source) => {
+                let source = match alias_dir() {
+                    Some(dir) if source != Path::new("-") => dir.join(source),
+                    _ => source.clone(),
+                };
+                let mut paths = read_path_list(&source)?;
+                paths.extend(self.paths.iter().cloned());
+                Some(paths.iter().map(from_cwd).collect())
+            }
+            None if self.paths.is_empty() => None,
+            None => Some(self.paths.iter().map(from_cwd).collect()),
+        };
+
+        // When max_commits == 0 we process the entire commit history
+        let max_commits_opt = if self.max_commits == 0 {
+            None
+        } else {
+            Some(self.max_commits)
+        };
+
+        Ok(AnalyzeOptions {
+            paths: filter,
+            max_commits: max_commits_opt,
+            write_commit_graph: self.write_commit_graph,
+            prefix,
+            max_commit_contribution: self.max_commit_contribution,
+            ignore_revs: self.ignore_revs.clone(),
+            ignore_revs_files: self.ignore_revs_files.clone(),
+            use_blame_ignore_revs: !self.no_blame_ignore_revs,
+            authors: self.authors()?,
+            author_boosts: self.author_boosts.clone(),
+            exclude_authors: self.exclude_authors.clone(),
+            exclude_messages: self.exclude_messages.clone(),
+            dedup_cherry_picks: self.dedup_cherry_picks,
+            churn: self.churn,
+            breakdown: self.breakdown,
+            mode: self.mode.into(),
+            default_excludes: !self.no_default_excludes,
+            skip_binary: self.skip_binary,
+            ignore_whitespace: self.ignore_whitespace,
+            ignore_case: self.ignore_case,
+            min_changed_lines: self.min_changed_lines,
+            threads: self.threads,
+            max_open_repos: self.max_open_repos,
+            commits_per_chunk: self.chunk_size,
+            cache_mode: if self.no_cache {
+                CacheMode::Disabled
+            } else if self.cache_readonly {
+                CacheMode::ReadOnly
+            } else {
+                CacheMode::ReadWrite
+            },
+            cache_location: if self.cache_in_git_dir {
+                CacheLocation::GitDir
+            } else if let Some(dir) = &self.cache_dir {
+                CacheLocation::Dir(dir.clone())
+            } else {
+                CacheLocation::UserCacheDir
+            },
+            cache_backend: self.cache_backend.into(),
+            max_cache_bytes: self.max_cache_bytes,
+            cache_compression_level: self.cache_compression,
+            max_memory_bytes: self.max_memory,
+            spill_to_disk: self.spill_to_disk,
+            file_sizes: !self.no_size_penalty && self.size_penalty != SizePenaltyArg::None,
+            max_file_size: self.max_file_size,
+            scorer: Arc::new(DefaultScorer {
+                size_penalty: self.size_penalty.into(),
+                size_scale_kib: self.size_penalty_scale,
+            }),
+            split_large_commits: !self.no_split_large_commits,
+            until: self.at,
+            as_of: self.at.or(self.as_of),
+            ref_globs: if self.all {
+                vec!["refs/*".to_owned()]
+            } else {
+                [(self.branches, "refs/heads"), (self.tags, "refs/tags")]
+                    .into_iter()
+                    .filter(|&(enabled, _)| enabled)
+                    .map(|(_, glob)| glob.to_owned())
+                    .collect()
+            },
+            since_merge_base: self.since_merge_base.clone(),
+            topology: self.topology.into(),
+            recurse_submodules: self.recurse_submodules,
+            use_config_file: !self.no_config,
+            access_weight: self.blend_access,
+            worktree_weight: self.include_worktree.then_some(self.worktree_weight),
+            reflog_weight: self.use_reflog.then_some(self.reflog_weight),
+            issue_ref_weight: self.issue_weight,
+            issue_refs: self.issue_refs,
+            issue_pattern: self.issue_pattern.clone(),
+            ..AnalyzeOptions::default()
+        })
+    }
+}
+
+impl OutputArgs {
+    /// Descending percentile boundaries of the requested tiers, if any
+    pub fn tier_boundaries(&self) -> anyhow::Result<Option<Vec<f64>>> {
+        if let Some(count) = self.tiers {
+            return Ok(Some(even_tier_boundaries(count as usize)));
+        }
+        if self.tier_boundaries.is_empty() {
+            return Ok(None);
+        }
+        if let Some(bad) = self
+            .tier_boundaries
+            .iter()
+            .find(|p| !(0.0..=100.0).contains(*p))
+        {
+            bail!("tier boundary {bad} is not a percentile between 0 and 100");
+        }
+        let mut boundaries = self.tier_boundaries.clone();
+        boundaries.sort_by(|a, b| b.total_cmp(a));
+        Ok(Some(boundaries))
+    }
+}
+
+/// Whether this binary runs as `git-frecenfile`, i.e. as `git frecenfile`
+pub fn invoked_as_git_subcommand() -> bool {
+    env::args_os()
+        .next()
+        .is_some_and(|arg0| Path::new(&arg0).file_stem() == Some(OsStr::new("git-frecenfile")))
+}
+
+/// `GIT_PREFIX`: git aliases run at the top of the work tree and pass the directory they were
+/// started from in it
+fn alias_dir() -> Option<PathBuf> {
+    env::var_os("GIT_PREFIX")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+/// `path` without `.` and with `..` resolved lexically
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Paths listed in `source` (stdin for `-`): NUL-separated if the input contains a NUL,
+/// otherwise one per line. Empty entries are skipped.
+fn read_path_list(source: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut bytes = Vec::new();
+    if source == Path::new("-") {
+        io::stdin().lock().read_to_end(&mut bytes)?;
+    } else {
+        bytes = fs::read(source).with_context(|| format!("reading {}", source.display()))?;
+    }
+
+    let separator = if bytes.contains(&0) { b'\0' } else { b'\n' };
+    bytes
+        .split(|&b| b == separator)
+        .map(|entry| entry.strip_suffix(b"\r").unwrap_or(entry))
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            path_from_bytes(entry)
+                .with_context(|| format!("path list {} is not UTF-8", source.display()))
+        })
+        .collect()
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned score and path columns
+    Human,
+    /// A JSON array of objects with per-file statistics
+    Json,
+    /// One JSON object per line; the main report starts with a `metadata` record
+    Jsonl,
+    /// Scores nested by directory as `{name, value, children}` JSON, e.g. for d3 treemaps
+    Tree,
+    /// Folded stacks (`dir;file weight`) for flamegraph tools such as inferno
+    Folded,
+    /// `path:1:1: score ...` lines for Vim's quickfix list (`:cgetexpr`)
+    Quickfix,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ScoreDisplayArg {
+    /// Decayed frecency values
+    Raw,
+    /// Percentile among all scored files (100 = hottest)
+    Percentile,
+    /// Rank, 1 = hottest
+    Rank,
+    /// Rating centered on 1500; +400 means ten times hotter
+    Elo,
+}
+
+impl From<ScoreDisplayArg> for ScoreDisplay {
+    fn from(arg: ScoreDisplayArg) -> Self {
+        match arg {
+            ScoreDisplayArg::Raw => ScoreDisplay::Raw,
+            ScoreDisplayArg::Percentile => ScoreDisplay::Percentile,
+            ScoreDisplayArg::Rank => ScoreDisplay::Rank,
+            ScoreDisplayArg::Elo => ScoreDisplay::Elo,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Column {
+    /// Position in the ranking, 1 = hottest; ties share a rank
+    Rank,
+    /// Score as selected by `--score-display`
+    Score,
+    /// Percentage of files scoring at or below this one
+    Percentile,
+    /// Tier label from --tiers or --tier-boundaries (default: hot/warm/cold)
+    Tier,
+    /// Number of counted commits
+    Commits,
+    /// Unix time of the newest counted change
+    LastModified,
+    Path,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color when printing to a terminal and `NO_COLOR` is not set
+    Auto,
+    Always,
+    Never,
+}
+
+/// Options controlling how the ranking is printed
+#[derive(Args, Debug)]
+pub struct OutputArgs {
+    /// Sort ascending (lowest score first)
+    #[arg(
+        short = 'a',
+        long = "ascending",
+        help = "Sort ascending (lowest score first)"
+    )]
+    pub ascending: bool,
+
+    /// Sort descending (highest score first)
+    #[arg(
+        short = 'd',
+        long = "descending",
+        help = "Sort descending (highest score first)"
+    )]
+    pub descending: bool,
+
+    /// Print only file paths, without scores
+    #[arg(
+        short = 'P',
+        long = "path-only",
+        help = "Print only paths, omit scores"
+    )]
+    pub path_only: bool,
+
+    /// Print paths with the platform's separator (`\` on Windows) instead of git's `/`
+    #[arg(long = "native-separators")]
+    pub native_separators: bool,
+
+    /// Print only paths, each as its raw bytes followed by a NUL, e.g. for `xargs -0`
+    #[arg(short = '0', long = "print0", conflicts_with = "path_only")]
+    pub print0: bool,
+
+    /// Output format
+    #[arg(long = "format", value_enum, default_value_t = OutputFormat::Human)]
+    pub format: OutputFormat,
+
+    /// How to present scores in human output
+    #[arg(long = "score-display", value_enum, default_value_t = ScoreDisplayArg::Raw)]
+    pub score_display: ScoreDisplayArg,
+
+    /// Print a tier label instead of the score: N equally sized tiers by percentile, named
+    /// hot/warm/cold for 3 (hot/cold for 2, hot/warm/cool/cold for 4, numbers otherwise)
+    #[arg(
+        long = "tiers",
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(2..),
+        conflicts_with = "tier_boundaries"
+    )]
+    pub tiers: Option<u32>,
+
+    /// Like --tiers, with the percentiles where each tier starts, e.g. `90,50` for the top
+    /// 10% hot, the next 40% warm and the rest cold
+    #[arg(
+        long = "tier-boundaries",
+        value_name = "PERCENTILES",
+        value_delimiter = ','
+    )]
+    pub tier_boundaries: Vec<f64>,
+
+    /// Columns of human output, in order, e.g. `rank,score,percentile,path`
+    #[arg(
+        long = "columns",
+        value_enum,
+        value_delimiter = ',',
+        value_name = "COLUMNS",
+        conflicts_with_all = ["path_only", "print0", "format_string"]
+    )]
+    pub columns: Vec<Column>,
+
+    /// Color scores from cold to hot and draw a bar after each one in human output
+    #[arg(long = "color", value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    /// Format scores with the separators of this locale (e.g. `de_DE`); `auto` reads
+    /// LC_ALL/LC_NUMERIC/LANG. Defaults to a locale-independent format.
+    #[arg(long = "locale", value_name = "LOCALE")]
+    pub locale: Option<String>,
+
+    /// Print each file with a template, e.g. "{rank}\t{score:.2}\t{path}"; placeholders:
+    /// rank, score, path, last_modified, commits, churn
+    #[arg(long = "format-string", value_name = "TEMPLATE")]
+    pub format_string: Option<Template>,
+
+    /// Also write the scores to tables in this SQLite database
+    #[arg(long = "export-sqlite", value_name = "FILE")]
+    pub export_sqlite: Option<PathBuf>,
+
+    /// Also write the scores (and contributions) as Parquet files into this directory
+    #[arg(long = "export-parquet", value_name = "DIR")]
+    pub export_parquet: Option<PathBuf>,
+
+    /// Include per-commit contributions to every file in exports (can be large)
+    #[arg(long = "export-contributions")]
+    pub export_contributions: bool,
+
+    /// Print the summed score of each group instead of individual files
+    #[arg(long = "group-by", value_enum, value_name = "GROUP")]
+    pub group_by: Option<GroupBy>,
+
+    /// Only files owned by this owner in CODEOWNERS, e.g. `@team/payments` (repeatable)
+    #[arg(long = "owner", value_name = "OWNER")]
+    pub owners: Vec<String>,
+
+    /// Print commits walked, cache hits, blobs loaded, time per phase and peak memory to stderr
+    #[arg(long = "stats")]
+    pub stats: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    /// Owners from the CODEOWNERS file; files without one are grouped as `(unowned)`
+    Owner,
+    /// Packages: the closest directory with a Cargo.toml, package.json or go.mod
+    Package,
+    /// Programming languages, guessed from file extensions; unknown ones are grouped as `(other)`
+    Language,
+}
+
+/// Parse a point in time: Unix seconds, `YYYY-MM-DD` (midnight UTC) or RFC 3339
+fn parse_timestamp(s: &str) -> Result<i64, String> {
+    let s = s.trim();
+    if let Ok(secs) = s.parse::<i64>() {
+        return Ok(secs);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date.and_time(NaiveTime::MIN).and_utc().timestamp());
+    }
+    DateTime::parse_from_rfc3339(s)
+        .map(|time| time.timestamp())
+        .map_err(|_| format!("invalid time {s:?}; expected Unix seconds, YYYY-MM-DD or RFC 3339"))
+}
+
+/// Parse a byte count with an optional binary suffix: `1024`, `64K`, `512M`, `2G`
+fn parse_byte_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (digits, suffix) = s.split_at(split);
+    let value: u64 = digits.parse().map_err(|_| format!("invalid size {s:?}"))?;
+    let multiplier: u64 = match suffix.to_ascii_uppercase().trim_end_matches(['B', 'I']) {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(format!("unknown size suffix in {s:?}")),
+    };
+    value
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("size {s:?} is too large"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_sizes_take_binary_suffixes() {
+        assert_eq!(parse_byte_size("1024"), Ok(1024));
+        assert_eq!(parse_byte_size("64K"), Ok(64 << 10));
+        assert_eq!(parse_byte_size("512mb"), Ok(512 << 20));
+        assert_eq!(parse_byte_size(" 2GiB "), Ok(2 << 30));
+        assert!(parse_byte_size("12X").is_err());
+        assert!(parse_byte_size("K").is_err());
+        assert!(parse_byte_size("99999999999T").is_err());
+    }
+
+    #[test]
+    fn timestamps_take_seconds_dates_and_rfc3339() {
+        assert_eq!(parse_timestamp("1700000000"), Ok(1_700_000_000));
+        assert_eq!(parse_timestamp("2023-11-14"), Ok(1_699_920_000));
+        assert_eq!(
+            parse_timestamp("2023-11-14T22:13:20+00:00"),
+            Ok(1_700_000_000)
+        );
+        assert_eq!(
+            parse_timestamp("2023-11-15T00:13:20+02:00"),
+            Ok(1_700_000_000)
+        );
+        assert!(parse_timestamp("yesterday").is_err());
+    }
+}
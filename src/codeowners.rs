@@ -0,0 +1,157 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobMatcher};
+
+use crate::{open_repo, read_bare_head_file};
+
+/// Where GitHub looks for the file, in order
+const LOCATIONS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// Owners assigned to paths by a `CODEOWNERS` file; the last matching rule wins
+#[derive(Debug, Clone, Default)]
+pub struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    /// Matches the path itself
+    path: Option<GlobMatcher>,
+    /// Matches everything below a matching directory; None for `dir/*`, which only covers the
+    /// files directly in `dir`
+    contents: Option<GlobMatcher>,
+    owners: Vec<String>,
+}
+
+impl CodeOwners {
+    /// The `CODEOWNERS` file of the repository (`.github/`, the root or `docs/`, read from HEAD
+    /// in bare repositories); None when there is none
+    pub fn for_repo(repo_path: &Path) -> Result<Option<Self>> {
+        let repo = open_repo(repo_path)?;
+        for location in LOCATIONS {
+            let contents = match repo.workdir() {
+                Some(workdir) => {
+                    let file = workdir.join(location);
+                    if !file.is_file() {
+                        continue;
+                    }
+                    fs::read_to_string(&file)
+                        .with_context(|| format!("failed to read {}", file.display()))?
+                }
+                None => match read_bare_head_file(&repo, location) {
+                    Some(contents) => contents,
+                    None => continue,
+                },
+            };
+            return Self::parse(&contents).map(Some);
+        }
+        Ok(None)
+    }
+
+    /// Parse `CODEOWNERS` syntax: `pattern @owner...` lines, `#` comments. Patterns follow
+    /// `.gitignore` rules: without a `/` they match in any directory, and a directory
+    /// pattern covers everything below it.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for line in contents.lines() {
+            let line = line.split_once(" #").map_or(line, |(rule, _)| rule).trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let Some(pattern) = words.next() else {
+                continue;
+            };
+            let owners = words.map(str::to_owned).collect();
+            rules.push(Rule::new(pattern, owners)?);
+        }
+        Ok(Self { rules })
+    }
+
+    /// Owners of `path` (relative to the repository root); empty when unowned
+    pub fn owners(&self, path: &Path) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(path))
+            .map_or(&[], |rule| &rule.owners)
+    }
+}
+
+impl Rule {
+    fn new(pattern: &str, owners: Vec<String>) -> Result<Self> {
+        let directory_only = pattern.ends_with('/');
+        let trimmed = pattern.trim_end_matches('/');
+        let anchored = trimmed.contains('/');
+        let trimmed = trimmed.trim_start_matches('/');
+        let glob = match (anchored, trimmed) {
+            (_, "" | "*" | "**") => "**".to_owned(),
+            (true, trimmed) => trimmed.to_owned(),
+            (false, trimmed) => format!("**/{trimmed}"),
+        };
+        let compile = |glob: &str| -> Result<GlobMatcher> {
+            Ok(GlobBuilder::new(glob)
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("invalid CODEOWNERS pattern {pattern:?}"))?
+                .compile_matcher())
+        };
+        Ok(Self {
+            path: (!directory_only).then(|| compile(&glob)).transpose()?,
+            contents: (!glob.ends_with("/*"))
+                .then(|| compile(&format!("{glob}/**")))
+                .transpose()?,
+            owners,
+        })
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        [&self.path, &self.contents]
+            .into_iter()
+            .flatten()
+            .any(|glob| glob.is_match(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owners<'a>(codeowners: &'a CodeOwners, path: &str) -> Vec<&'a str> {
+        codeowners
+            .owners(Path::new(path))
+            .iter()
+            .map(String::as_str)
+            .collect()
+    }
+
+    #[test]
+    fn last_matching_rule_wins() {
+        let codeowners = CodeOwners::parse(
+            "# comment\n\
+             * @everyone\n\
+             *.rs @rust # trailing comment\n\
+             /docs/ @docs\n\
+             src/bin/* @cli\n\
+             build @build\n",
+        )
+        .unwrap();
+        assert_eq!(owners(&codeowners, "README.md"), ["@everyone"]);
+        assert_eq!(owners(&codeowners, "src/deep/lib.rs"), ["@rust"]);
+        assert_eq!(owners(&codeowners, "docs/guide/intro.md"), ["@docs"]);
+        assert_eq!(owners(&codeowners, "src/docs/intro.md"), ["@everyone"]);
+        assert_eq!(owners(&codeowners, "src/bin/main.rs"), ["@cli"]);
+        assert_eq!(owners(&codeowners, "src/bin/tool/main.rs"), ["@rust"]);
+        assert_eq!(owners(&codeowners, "tools/build/run.sh"), ["@build"]);
+    }
+
+    #[test]
+    fn unowned_paths_have_no_owners() {
+        let codeowners = CodeOwners::parse("/src/ @core\n").unwrap();
+        assert!(owners(&codeowners, "README.md").is_empty());
+        assert!(owners(&codeowners, "src").is_empty());
+        assert_eq!(owners(&codeowners, "src/lib.rs"), ["@core"]);
+    }
+}
@@ -0,0 +1,37 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+use crate::cli::ColorChoice;
+
+/// Width of the bar printed after each score
+const BAR_WIDTH: usize = 12;
+/// 256-color palette entries from cold (blue) to hot (red)
+const GRADIENT: [u8; 8] = [27, 33, 39, 45, 220, 214, 208, 196];
+const RESET: &str = "\x1b[0m";
+
+/// Whether to color human output: `auto` colors a terminal unless `NO_COLOR` is set
+pub fn enabled(choice: ColorChoice) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+    }
+}
+
+/// `text` colored by `heat`, from 0 (coldest) to 1 (hottest)
+pub fn paint(text: &str, heat: f64) -> String {
+    let step = (heat.clamp(0.0, 1.0) * (GRADIENT.len() - 1) as f64).round() as usize;
+    format!("\x1b[38;5;{}m{text}{RESET}", GRADIENT[step])
+}
+
+/// A bar of eighth blocks, `heat` of `BAR_WIDTH` cells long and padded to that width
+pub fn bar(heat: f64) -> String {
+    const EIGHTHS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+    let eighths = (heat.clamp(0.0, 1.0) * (BAR_WIDTH * 8) as f64).round() as usize;
+    let mut bar = "█".repeat(eighths / 8);
+    match eighths % 8 {
+        0 => {}
+        partial => bar.push(EIGHTHS[partial - 1]),
+    }
+    format!("{bar:<BAR_WIDTH$}")
+}
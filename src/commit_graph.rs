@@ -0,0 +1,443 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+use git2::{Oid, Repository};
+
+/// Walks of at least this many commits suggest writing a commit-graph when there is none
+pub(crate) const HINT_MIN_COMMITS: usize = 50_000;
+
+const SIGNATURE: &[u8; 4] = b"CGPH";
+const OID_FANOUT: u32 = u32::from_be_bytes(*b"OIDF");
+const OID_LOOKUP: u32 = u32::from_be_bytes(*b"OIDL");
+const COMMIT_DATA: u32 = u32::from_be_bytes(*b"CDAT");
+const EXTRA_EDGES: u32 = u32::from_be_bytes(*b"EDGE");
+/// Parent position of a missing parent
+const PARENT_NONE: u32 = 0x7000_0000;
+/// Set on the second parent position when it indexes the extra edges of an octopus merge,
+/// and on the last of those edges
+const EDGE_FLAG: u32 = 0x8000_0000;
+
+/// What the commit-graph knows about a commit without inflating it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GraphCommit {
+    /// Committer time, as `Commit::time`
+    pub(crate) time: i64,
+    pub(crate) parents: Vec<Oid>,
+}
+
+impl GraphCommit {
+    pub(crate) fn is_merge(&self) -> bool {
+        self.parents.len() > 1
+    }
+}
+
+/// git's commit-graph (`git commit-graph write`): a single file or a split chain
+pub(crate) struct CommitGraph {
+    /// Base layer first; parent positions count through the layers in this order
+    layers: Vec<Layer>,
+}
+
+struct Layer {
+    data: Vec<u8>,
+    hash_len: usize,
+    count: usize,
+    /// Position of the layer's first commit in the whole chain
+    base: usize,
+    fanout: usize,
+    oids: usize,
+    commits: usize,
+    extra_edges: Option<usize>,
+}
+
+impl CommitGraph {
+    /// The repository's commit-graph; None when there is none or it cannot be read
+    pub(crate) fn open(repo: &Repository) -> Option<Self> {
+        Self::read(&objects_info_dir(repo))
+    }
+
+    fn read(info: &Path) -> Option<Self> {
+        let files = if info.join("commit-graph").is_file() {
+            vec![info.join("commit-graph")]
+        } else {
+            let chain = fs::read_to_string(info.join("commit-graphs/commit-graph-chain")).ok()?;
+            chain
+                .lines()
+                .map(|hash| info.join(format!("commit-graphs/graph-{}.graph", hash.trim())))
+                .collect()
+        };
+        let mut layers = Vec::with_capacity(files.len());
+        let mut base = 0;
+        for file in &files {
+            let layer = Layer::read(file, base)?;
+            base += layer.count;
+            layers.push(layer);
+        }
+        (!layers.is_empty()).then_some(Self { layers })
+    }
+
+    /// None for commits written after the graph
+    pub(crate) fn commit(&self, oid: Oid) -> Option<GraphCommit> {
+        let (layer, position) = self
+            .layers
+            .iter()
+            .find_map(|layer| Some((layer, layer.position(oid.as_bytes())?)))?;
+        let entry = layer.commits + position * (layer.hash_len + 16) + layer.hash_len;
+        let first = read_u32(&layer.data, entry)?;
+        let second = read_u32(&layer.data, entry + 4)?;
+        let time_high = read_u32(&layer.data, entry + 8)? & 0b11;
+        let time_low = read_u32(&layer.data, entry + 12)?;
+
+        let mut parents = Vec::with_capacity(2);
+        if first != PARENT_NONE {
+            parents.push(self.oid(first as usize)?);
+        }
+        if second & EDGE_FLAG != 0 {
+            let mut at = layer.extra_edges? + (second & !EDGE_FLAG) as usize * 4;
+            loop {
+                let edge = read_u32(&layer.data, at)?;
+                parents.push(self.oid((edge & !EDGE_FLAG) as usize)?);
+                if edge & EDGE_FLAG != 0 {
+                    break;
+                }
+                at += 4;
+            }
+        } else if second != PARENT_NONE {
+            parents.push(self.oid(second as usize)?);
+        }
+        Some(GraphCommit {
+            time: (i64::from(time_high) << 32) | i64::from(time_low),
+            parents,
+        })
+    }
+
+    /// The commit at `position` of the whole chain
+    fn oid(&self, position: usize) -> Option<Oid> {
+        let layer = self
+            .layers
+            .iter()
+            .find(|layer| (layer.base..layer.base + layer.count).contains(&position))?;
+        let at = layer.oids + (position - layer.base) * layer.hash_len;
+        Oid::from_bytes(&layer.data[at..at + layer.hash_len]).ok()
+    }
+
+    /// Commits reachable from `start`, newest first like `Sort::TIME`, following only first
+    /// parents with `first_parent`. Commits written after the graph are read from `repo`.
+    pub(crate) fn walk<'a>(
+        &'a self,
+        repo: &'a Repository,
+        start: Oid,
+        first_parent: bool,
+    ) -> Result<GraphWalk<'a>, git2::Error> {
+        let mut walk = GraphWalk {
+            graph: self,
+            repo,
+            first_parent,
+            queue: BinaryHeap::new(),
+            seen: HashSet::new(),
+            pushed: 0,
+        };
+        walk.push(start)?;
+        Ok(walk)
+    }
+}
+
+/// Iterator of [`CommitGraph::walk`]
+pub(crate) struct GraphWalk<'a> {
+    graph: &'a CommitGraph,
+    repo: &'a Repository,
+    first_parent: bool,
+    /// Newest commit time first, then the earliest pushed
+    queue: BinaryHeap<(i64, Reverse<u64>, Oid, Vec<Oid>)>,
+    seen: HashSet<Oid>,
+    pushed: u64,
+}
+
+impl GraphWalk<'_> {
+    fn push(&mut self, oid: Oid) -> Result<(), git2::Error> {
+        if !self.seen.insert(oid) {
+            return Ok(());
+        }
+        let commit = match self.graph.commit(oid) {
+            Some(commit) => commit,
+            None => {
+                let commit = self.repo.find_commit(oid)?;
+                GraphCommit {
+                    time: commit.time().seconds(),
+                    parents: commit.parent_ids().collect(),
+                }
+            }
+        };
+        self.queue
+            .push((commit.time, Reverse(self.pushed), oid, commit.parents));
+        self.pushed += 1;
+        Ok(())
+    }
+}
+
+impl Iterator for GraphWalk<'_> {
+    type Item = Result<Oid, git2::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (_, _, oid, parents) = self.queue.pop()?;
+        let followed = if self.first_parent { 1 } else { parents.len() };
+        for parent in parents.into_iter().take(followed) {
+            if let Err(err) = self.push(parent) {
+                return Some(Err(err));
+            }
+        }
+        Some(Ok(oid))
+    }
+}
+
+impl Layer {
+    fn read(path: &Path, base: usize) -> Option<Self> {
+        let data = fs::read(path).ok()?;
+        if data.len() < 8 || &data[..4] != SIGNATURE || data[4] != 1 {
+            return None;
+        }
+        let hash_len = match data[5] {
+            1 => 20,
+            2 => 32,
+            _ => return None,
+        };
+        let chunk_count = usize::from(data[6]);
+        let (mut fanout, mut oids, mut commits, mut extra_edges) = (None, None, None, None);
+        for chunk in 0..chunk_count {
+            let entry = data.get(8 + chunk * 12..8 + chunk * 12 + 12)?;
+            let id = u32::from_be_bytes(entry[..4].try_into().ok()?);
+            let offset = usize::try_from(u64::from_be_bytes(entry[4..].try_into().ok()?)).ok()?;
+            match id {
+                OID_FANOUT => fanout = Some(offset),
+                OID_LOOKUP => oids = Some(offset),
+                COMMIT_DATA => commits = Some(offset),
+                EXTRA_EDGES => extra_edges = Some(offset),
+                _ => {}
+            }
+        }
+        let fanout = fanout?;
+        let count = read_u32(&data, fanout + 255 * 4)? as usize;
+        let layer = Self {
+            hash_len,
+            count,
+            base,
+            fanout,
+            oids: oids?,
+            commits: commits?,
+            extra_edges,
+            data,
+        };
+        // Reject truncated files up front so lookups can index freely
+        let end = layer.commits + count * (hash_len + 16);
+        (layer.data.len() >= end && layer.data.len() >= layer.oids + count * hash_len)
+            .then_some(layer)
+    }
+
+    /// Position of `oid` within this layer
+    fn position(&self, oid: &[u8]) -> Option<usize> {
+        if oid.len() != self.hash_len {
+            return None;
+        }
+        let first = usize::from(oid[0]);
+        let start = match first {
+            0 => 0,
+            _ => read_u32(&self.data, self.fanout + (first - 1) * 4)? as usize,
+        };
+        let end = (read_u32(&self.data, self.fanout + first * 4)? as usize).min(self.count);
+        let mut range = start..end;
+        loop {
+            if range.is_empty() {
+                return None;
+            }
+            let middle = range.start + range.len() / 2;
+            let at = self.oids + middle * self.hash_len;
+            match self.data[at..at + self.hash_len].cmp(oid) {
+                std::cmp::Ordering::Less => range.start = middle + 1,
+                std::cmp::Ordering::Greater => range.end = middle,
+                std::cmp::Ordering::Equal => return Some(middle),
+            }
+        }
+    }
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn objects_info_dir(repo: &Repository) -> PathBuf {
+    repo.commondir().join("objects").join("info")
+}
+
+/// Whether the repository has a commit-graph, as a single file or a split chain
+pub(crate) fn has_commit_graph(repo: &Repository) -> bool {
+    let info = objects_info_dir(repo);
+    info.join("commit-graph").is_file() || info.join("commit-graphs/commit-graph-chain").is_file()
+}
+
+/// Write a single-file commit-graph of every reachable commit with `git commit-graph write`
+pub(crate) fn write_commit_graph(repo: &Repository) -> Result<()> {
+    git_commit_graph_write(repo, &[])
+}
+
+fn git_commit_graph_write(repo: &Repository, extra_args: &[&str]) -> Result<()> {
+    let status = Command::new("git")
+        .arg("--git-dir")
+        .arg(repo.commondir())
+        .args(["commit-graph", "write", "--reachable"])
+        .args(extra_args)
+        .status()
+        .context("failed to run git commit-graph write")?;
+    if !status.success() {
+        bail!("git commit-graph write failed ({status})");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::{Signature, Sort, Time};
+
+    use super::*;
+    use crate::test_support::TestRepo;
+
+    /// A repository whose commits are made one minute apart, with any parents
+    struct History {
+        repo: TestRepo,
+        time: i64,
+    }
+
+    impl History {
+        fn new() -> Self {
+            Self {
+                repo: TestRepo::new(),
+                time: 1_700_000_000,
+            }
+        }
+
+        /// A commit with `parents` and a distinct, increasing commit time
+        fn commit(&mut self, parents: &[Oid]) -> Oid {
+            self.time += 60;
+            let signature =
+                Signature::new("Test", "test@example.com", &Time::new(self.time, 0)).unwrap();
+            let tree = self.repo.treebuilder(None).unwrap().write().unwrap();
+            let tree = self.repo.find_tree(tree).unwrap();
+            let parents: Vec<_> = parents
+                .iter()
+                .map(|oid| self.repo.find_commit(*oid).unwrap())
+                .collect();
+            let parents: Vec<_> = parents.iter().collect();
+            let oid = (*self.repo)
+                .commit(None, &signature, &signature, "change", &tree, &parents)
+                .unwrap();
+            self.repo
+                .reference("refs/heads/main", oid, true, "test")
+                .unwrap();
+            self.repo.set_head("refs/heads/main").unwrap();
+            oid
+        }
+
+        /// A history with a merge and an octopus merge (whose extra parents are stored in the
+        /// graph's EDGE chunk)
+        fn branchy_history(&mut self) -> Oid {
+            let root = self.commit(&[]);
+            let a = self.commit(&[root]);
+            let b = self.commit(&[root]);
+            let c = self.commit(&[root]);
+            let merge = self.commit(&[a, b]);
+            let d = self.commit(&[b]);
+            self.commit(&[merge, c, d])
+        }
+
+        fn libgit2_walk(&self, start: Oid, first_parent: bool) -> Vec<Oid> {
+            let mut revwalk = self.repo.revwalk().unwrap();
+            revwalk.push(start).unwrap();
+            revwalk.set_sorting(Sort::TIME).unwrap();
+            if first_parent {
+                revwalk.simplify_first_parent().unwrap();
+            }
+            revwalk.map(Result::unwrap).collect()
+        }
+
+        /// Every commit reachable from `start` is in `graph`, as in the repository, and
+        /// walks match libgit2's
+        fn assert_matches_repo(&self, graph: &CommitGraph, start: Oid) {
+            for oid in self.libgit2_walk(start, false) {
+                let commit = self.repo.find_commit(oid).unwrap();
+                assert_eq!(
+                    graph.commit(oid),
+                    Some(GraphCommit {
+                        time: commit.time().seconds(),
+                        parents: commit.parent_ids().collect(),
+                    })
+                );
+            }
+            self.assert_walks_match(graph, start);
+        }
+
+        fn assert_walks_match(&self, graph: &CommitGraph, start: Oid) {
+            for first_parent in [false, true] {
+                let walked: Vec<Oid> = graph
+                    .walk(&self.repo, start, first_parent)
+                    .unwrap()
+                    .map(Result::unwrap)
+                    .collect();
+                assert_eq!(walked, self.libgit2_walk(start, first_parent));
+            }
+        }
+    }
+
+    #[test]
+    fn reads_a_single_file_graph() {
+        let mut test = History::new();
+        let head = test.branchy_history();
+        write_commit_graph(&test.repo).unwrap();
+
+        let graph = CommitGraph::open(&test.repo).unwrap();
+        assert_eq!(graph.layers.len(), 1);
+        assert!(graph.commit(head).unwrap().is_merge());
+        test.assert_matches_repo(&graph, head);
+    }
+
+    #[test]
+    fn reads_a_split_chain() {
+        let mut test = History::new();
+        let base = test.branchy_history();
+        git_commit_graph_write(&test.repo, &["--split=no-merge"]).unwrap();
+        let side = test.commit(&[base]);
+        let head = test.commit(&[base, side]);
+        git_commit_graph_write(&test.repo, &["--split=no-merge"]).unwrap();
+
+        let graph = CommitGraph::open(&test.repo).unwrap();
+        assert_eq!(graph.layers.len(), 2);
+        assert!(has_commit_graph(&test.repo));
+        test.assert_matches_repo(&graph, head);
+    }
+
+    #[test]
+    fn walks_commits_newer_than_the_graph() {
+        let mut test = History::new();
+        let base = test.branchy_history();
+        write_commit_graph(&test.repo).unwrap();
+        let side = test.commit(&[base]);
+        let head = test.commit(&[side, base]);
+
+        let graph = CommitGraph::open(&test.repo).unwrap();
+        assert_eq!(graph.commit(head), None);
+        test.assert_walks_match(&graph, head);
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let mut test = History::new();
+        test.branchy_history();
+        write_commit_graph(&test.repo).unwrap();
+        let file = objects_info_dir(&test.repo).join("commit-graph");
+        let data = fs::read(&file).unwrap();
+        fs::write(&file, &data[..data.len() / 2]).unwrap();
+
+        assert!(CommitGraph::open(&test.repo).is_none());
+    }
+}
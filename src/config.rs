@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use git2::Repository;
+use globset::{GlobBuilder, GlobMatcher};
+use serde::Deserialize;
+
+use crate::{AnalyzeOptions, read_bare_head_file};
+
+/// Per-repository configuration file, read from the root of the work tree
+pub const CONFIG_FILE: &str = ".frecenfile.toml";
+
+/// Manual adjustment for files matching a glob, applied after aggregation as
+/// `score * multiplier + bonus`
+#[derive(Debug, Clone)]
+pub struct ScoreAdjustment {
+    matcher: GlobMatcher,
+    /// Globs without a `/` match the file name in any directory, like `.gitignore`
+    match_file_name: bool,
+    pub multiplier: f64,
+    /// Added after the multiplier, on the same scale as scores
+    pub bonus: f64,
+}
+
+impl ScoreAdjustment {
+    pub fn new(glob: &str, multiplier: f64, bonus: f64) -> Result<Self> {
+        let matcher = GlobBuilder::new(glob)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("invalid glob {glob:?}"))?
+            .compile_matcher();
+        Ok(Self {
+            matcher,
+            match_file_name: !glob.contains('/'),
+            multiplier,
+            bonus,
+        })
+    }
+
+    /// The glob this adjustment was created from
+    pub fn glob(&self) -> &str {
+        self.matcher.glob().glob()
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        if self.match_file_name {
+            path.file_name()
+                .is_some_and(|name| self.matcher.is_match(name))
+        } else {
+            self.matcher.is_match(path)
+        }
+    }
+}
+
+/// Multiplier for every contribution to files matching a glob, applied during aggregation
+/// (unlike [`ScoreAdjustment`], which applies to the final score)
+#[derive(Debug, Clone)]
+pub struct PathWeight(ScoreAdjustment);
+
+impl PathWeight {
+    pub fn new(glob: &str, multiplier: f64) -> Result<Self> {
+        Ok(Self(ScoreAdjustment::new(glob, multiplier, 0.0)?))
+    }
+
+    pub fn multiplier(&self) -> f64 {
+        self.0.multiplier
+    }
+
+    pub fn glob(&self) -> &str {
+        self.0.glob()
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        self.0.matches(path)
+    }
+}
+
+/// Product of the multipliers of every weight matching `path`; 1 when none does
+pub(crate) fn path_weight(weights: &[PathWeight], path: &Path) -> f64 {
+    weights
+        .iter()
+        .filter(|weight| weight.matches(path))
+        .map(PathWeight::multiplier)
+        .product()
+}
+
+/// Apply every matching adjustment to `score`, in order
+pub(crate) fn adjust_score(adjustments: &[ScoreAdjustment], path: &Path, score: f64) -> f64 {
+    adjustments
+        .iter()
+        .filter(|adjustment| adjustment.matches(path))
+        .fold(score, |score, adjustment| {
+            score * adjustment.multiplier + adjustment.bonus
+        })
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    boost: Vec<BoostEntry>,
+    max_commit_contribution: Option<f64>,
+    #[serde(default)]
+    weights: BTreeMap<String, f64>,
+    #[serde(default)]
+    commit_types: BTreeMap<String, f64>,
+}
+
+/// Everything a repository's config file sets
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RepoConfig {
+    pub(crate) adjustments: Vec<ScoreAdjustment>,
+    pub(crate) max_commit_contribution: Option<f64>,
+    pub(crate) path_weights: Vec<PathWeight>,
+    pub(crate) commit_type_weights: BTreeMap<String, f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BoostEntry {
+    glob: String,
+    #[serde(default = "default_multiplier")]
+    multiplier: f64,
+    #[serde(default)]
+    bonus: f64,
+}
+
+fn default_multiplier() -> f64 {
+    1.0
+}
+
+/// Parse the `[[boost]]` tables of a config file:
+///
+/// ```toml
+/// max_commit_contribution = 0.5
+///
+/// [weights]
+/// "*.rs" = 1.0
+/// "*.md" = 0.5
+///
+/// [commit_types]
+/// chore = 0.2
+/// docs = 0.5
+///
+/// [[boost]]
+/// glob = "README.md"
+/// bonus = 0.5
+///
+/// [[boost]]
+/// glob = "*.snap"
+/// multiplier = 0.1
+/// ```
+pub fn read_config_file(path: &Path) -> Result<Vec<ScoreAdjustment>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("failed to read config file {}", path.display()))?;
+    Ok(parse_config(&contents, &path.display().to_string())?.adjustments)
+}
+
+/// Parse the contents of a config file; `origin` names it in errors
+fn parse_config(contents: &str, origin: &str) -> Result<RepoConfig> {
+    let config: ConfigFile =
+        toml::from_str(contents).with_context(|| format!("invalid config file {origin}"))?;
+    Ok(RepoConfig {
+        adjustments: config
+            .boost
+            .iter()
+            .map(|entry| ScoreAdjustment::new(&entry.glob, entry.multiplier, entry.bonus))
+            .collect::<Result<_>>()?,
+        max_commit_contribution: config.max_commit_contribution,
+        path_weights: config
+            .weights
+            .iter()
+            .map(|(glob, multiplier)| PathWeight::new(glob, *multiplier))
+            .collect::<Result<_>>()?,
+        commit_type_weights: config
+            .commit_types
+            .into_iter()
+            .map(|(kind, weight)| (kind.to_ascii_lowercase(), weight))
+            .collect(),
+    })
+}
+
+/// The repository's `.frecenfile.toml` (read from HEAD in bare repositories), if enabled
+pub(crate) fn repo_config(repo: &Repository, options: &AnalyzeOptions) -> Result<RepoConfig> {
+    match config_file_contents(repo, options)? {
+        Some((contents, source)) => parse_config(&contents, &source),
+        None => Ok(RepoConfig::default()),
+    }
+}
+
+/// Contents of the repo's config file, if enabled and present, and where they were read from
+pub(crate) fn config_file_contents(
+    repo: &Repository,
+    options: &AnalyzeOptions,
+) -> Result<Option<(String, String)>> {
+    if !options.use_config_file {
+        return Ok(None);
+    }
+    if let Some(file) = default_config_file(repo) {
+        let contents = fs::read_to_string(&file)
+            .with_context(|| format!("failed to read config file {}", file.display()))?;
+        Ok(Some((contents, file.display().to_string())))
+    } else {
+        Ok(read_bare_head_file(repo, CONFIG_FILE)
+            .map(|contents| (contents, format!("HEAD:{CONFIG_FILE}"))))
+    }
+}
+
+/// `.frecenfile.toml` at the root of the work tree, if present
+fn default_config_file(repo: &Repository) -> Option<PathBuf> {
+    repo.workdir()
+        .map(|workdir| workdir.join(CONFIG_FILE))
+        .filter(|p| p.is_file())
+}
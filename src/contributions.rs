@@ -0,0 +1,66 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::AnalyzeOptions;
+use crate::pipeline::{self, Accumulator, WeightedCommit};
+
+/// What one commit added to one file's score
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Contribution {
+    pub commit: String,
+    /// Commit time, Unix seconds
+    pub time: i64,
+    /// `Name <email>`, after applying `.mailmap`
+    pub author: String,
+    #[serde(serialize_with = "crate::paths::serialize")]
+    pub path: PathBuf,
+    /// Decayed, weighted amount added to the file's score
+    pub weight: f64,
+}
+
+#[derive(Default)]
+struct ContributionList(Vec<Contribution>);
+
+impl Accumulator for ContributionList {
+    const NEEDS_AUTHOR: bool = true;
+
+    fn add(&mut self, commit: &WeightedCommit) {
+        for change in commit.changes {
+            self.0.push(Contribution {
+                commit: commit.id.to_string(),
+                time: commit.time,
+                author: commit.author.unwrap_or_default().to_owned(),
+                path: change.path.to_path_buf(),
+                weight: commit.file_score(change),
+            });
+        }
+    }
+
+    fn merge(mut self, mut other: Self) -> Self {
+        self.0.append(&mut other.0);
+        self
+    }
+
+    fn estimated_memory(&self) -> u64 {
+        let paths: usize = self.0.iter().map(|c| c.path.as_os_str().len()).sum();
+        (self.0.capacity() * std::mem::size_of::<Contribution>() + paths) as u64
+    }
+}
+
+/// Every (commit, file) pair behind the scores, newest commit first; summing `weight` per
+/// path gives the unadjusted file scores
+pub fn analyze_contributions(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+) -> Result<Vec<Contribution>> {
+    let ContributionList(mut contributions) = pipeline::run(repo_path, options)?;
+    contributions.sort_by(|a, b| {
+        b.time
+            .cmp(&a.time)
+            .then_with(|| a.commit.cmp(&b.commit))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    Ok(contributions)
+}
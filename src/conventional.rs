@@ -0,0 +1,31 @@
+use std::collections::BTreeMap;
+
+use git2::Commit;
+
+/// Type of a conventional commit summary, e.g. `feat` for `feat(parser)!: ...`, lowercased
+pub(crate) fn commit_type(summary: &str) -> Option<String> {
+    let (head, _) = summary.split_once(':')?;
+    let head = head.strip_suffix('!').unwrap_or(head);
+    let kind = match head.split_once('(') {
+        Some((kind, scope)) if scope.ends_with(')') => kind,
+        Some(_) => return None,
+        None => head,
+    };
+    let valid = !kind.is_empty()
+        && kind
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then(|| kind.to_ascii_lowercase())
+}
+
+/// Weight of `commit` per its conventional commit type; 1 for other types and for summaries
+/// that don't follow the convention
+pub(crate) fn commit_type_weight(commit: &Commit, weights: &BTreeMap<String, f64>) -> f64 {
+    if weights.is_empty() {
+        return 1.0;
+    }
+    let summary = String::from_utf8_lossy(commit.summary_bytes().unwrap_or_default());
+    commit_type(&summary)
+        .and_then(|kind| weights.get(&kind).copied())
+        .unwrap_or(1.0)
+}
@@ -0,0 +1,9 @@
+use chrono::DateTime;
+
+/// `YYYY-MM-DD` (UTC) of a Unix timestamp for human-readable output; the timestamp itself
+/// when it is out of range
+pub fn date(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|time| time.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
@@ -0,0 +1,80 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::Utc;
+use rustc_hash::FxHashMap as HashMap;
+use serde::Serialize;
+
+use crate::{AnalyzeOptions, analyze_repo_with_options};
+
+/// Score of one file at two revisions
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScoreDelta {
+    #[serde(serialize_with = "crate::paths::serialize")]
+    pub path: PathBuf,
+    /// Score over the history of the base revision
+    pub base: f64,
+    /// Score over the history of the compared revision
+    pub head: f64,
+}
+
+impl ScoreDelta {
+    /// Absolute score difference; positive when the compared revision concentrates more work on the file
+    pub fn change(&self) -> f64 {
+        self.head - self.base
+    }
+}
+
+/// Score the histories of `base` and `head`, both as of `options.as_of` (or now) so shared
+/// commits cancel out, and return every changed file sorted from largest gain to largest loss
+pub fn diff_revs(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+    base: &str,
+    head: &str,
+) -> Result<Vec<ScoreDelta>> {
+    let now = options.as_of.unwrap_or_else(|| Utc::now().timestamp());
+    let score = |rev: &str| {
+        analyze_repo_with_options(
+            repo_path,
+            &AnalyzeOptions {
+                rev: Some(rev.to_owned()),
+                as_of: Some(now),
+                ..options.clone()
+            },
+        )
+    };
+
+    let mut deltas: HashMap<PathBuf, ScoreDelta> = HashMap::default();
+    for file in score(base)? {
+        deltas.insert(
+            file.path.clone(),
+            ScoreDelta {
+                path: file.path,
+                base: file.score,
+                head: 0.0,
+            },
+        );
+    }
+    for file in score(head)? {
+        deltas
+            .entry(file.path.clone())
+            .or_insert_with(|| ScoreDelta {
+                path: file.path,
+                base: 0.0,
+                head: 0.0,
+            })
+            .head = file.score;
+    }
+
+    let mut deltas: Vec<ScoreDelta> = deltas
+        .into_values()
+        .filter(|delta| delta.change() != 0.0)
+        .collect();
+    deltas.sort_by(|a, b| {
+        b.change()
+            .total_cmp(&a.change())
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    Ok(deltas)
+}
@@ -0,0 +1,39 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::FileScore;
+
+/// Paths ranked within the top `percent` of `scores` (at least one when non-empty)
+pub fn hot_files(scores: &[FileScore], percent: f64) -> HashSet<PathBuf> {
+    let mut ranked: Vec<&FileScore> = scores.iter().collect();
+    ranked.sort_by(|a, b| a.cmp_hottest_first(b));
+
+    let count = ((ranked.len() as f64) * percent / 100.0).ceil() as usize;
+    let count = count.clamp(
+        usize::from(percent > 0.0 && !ranked.is_empty()),
+        ranked.len(),
+    );
+    ranked
+        .into_iter()
+        .take(count)
+        .map(|f| f.path.clone())
+        .collect()
+}
+
+/// Files that are hot in `current` but were not hot in `baseline`, hottest first
+pub fn new_hot_files(
+    baseline: &[FileScore],
+    current: &[FileScore],
+    percent: f64,
+) -> Vec<FileScore> {
+    let was_hot = hot_files(baseline, percent);
+    let is_hot = hot_files(current, percent);
+
+    let mut new_hot: Vec<FileScore> = current
+        .iter()
+        .filter(|f| is_hot.contains(&f.path) && !was_hot.contains(&f.path))
+        .cloned()
+        .collect();
+    new_hot.sort_by(FileScore::cmp_hottest_first);
+    new_hot
+}
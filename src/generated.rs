@@ -0,0 +1,57 @@
+use std::path::Path;
+use std::sync::LazyLock;
+
+use git2::{AttrCheckFlags, AttrValue, Repository};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+/// Files that are almost always generated: lockfiles, minified bundles, protobuf output and
+/// build directories
+const DEFAULT_EXCLUDES: &[&str] = &[
+    "**/Cargo.lock",
+    "**/package-lock.json",
+    "**/npm-shrinkwrap.json",
+    "**/yarn.lock",
+    "**/pnpm-lock.yaml",
+    "**/bun.lockb",
+    "**/composer.lock",
+    "**/Gemfile.lock",
+    "**/poetry.lock",
+    "**/Pipfile.lock",
+    "**/uv.lock",
+    "**/go.sum",
+    "**/flake.lock",
+    "**/*.min.js",
+    "**/*.min.css",
+    "**/*.js.map",
+    "**/*.pb.go",
+    "**/*_pb2.py",
+    "**/*_pb2_grpc.py",
+    "**/*.pb.cc",
+    "**/*.pb.h",
+    "**/dist/**",
+];
+
+static DEFAULT_EXCLUDE_SET: LazyLock<GlobSet> = LazyLock::new(|| {
+    let mut builder = GlobSetBuilder::new();
+    for glob in DEFAULT_EXCLUDES {
+        builder.add(Glob::new(glob).expect("valid default exclude glob"));
+    }
+    builder.build().expect("valid default exclude globs")
+});
+
+/// Whether `path` is a generated file: marked `linguist-generated` or `-diff` in
+/// `.gitattributes`, or matching one of the built-in patterns
+pub(crate) fn is_generated(repo: &Repository, path: &Path) -> bool {
+    if DEFAULT_EXCLUDE_SET.is_match(path) {
+        return true;
+    }
+    let attr = |name| {
+        repo.get_attr(path, name, AttrCheckFlags::default())
+            .ok()
+            .flatten()
+    };
+    matches!(
+        AttrValue::from_string(attr("linguist-generated")),
+        AttrValue::True | AttrValue::String("true")
+    ) || matches!(AttrValue::from_string(attr("diff")), AttrValue::False)
+}
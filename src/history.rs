@@ -0,0 +1,164 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, NaiveTime, Utc};
+use git2::Oid;
+use serde::Serialize;
+
+use crate::AnalyzeOptions;
+use crate::pipeline::{self, Accumulator, WeightedCommit};
+use crate::scorer::CommitMeta;
+
+/// Length of the buckets of a score history; buckets start at midnight UTC, on Mondays for
+/// weeks and on the first of the month for months
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HistoryInterval {
+    Day,
+    Week,
+    Month,
+}
+
+impl HistoryInterval {
+    /// Start of the bucket containing `time`
+    fn floor(self, time: i64) -> Option<i64> {
+        let date = DateTime::from_timestamp(time, 0)?.date_naive();
+        let start = match self {
+            HistoryInterval::Day => date,
+            HistoryInterval::Week => {
+                date - Days::new(u64::from(date.weekday().num_days_from_monday()))
+            }
+            HistoryInterval::Month => NaiveDate::from_ymd_opt(date.year(), date.month(), 1)?,
+        };
+        Some(start.and_time(NaiveTime::MIN).and_utc().timestamp())
+    }
+
+    /// Start of the bucket after the one starting at `start`
+    fn next(self, start: i64) -> Option<i64> {
+        let date = DateTime::from_timestamp(start, 0)?.date_naive();
+        let next = match self {
+            HistoryInterval::Day => date.checked_add_days(Days::new(1))?,
+            HistoryInterval::Week => date.checked_add_days(Days::new(7))?,
+            HistoryInterval::Month => date.checked_add_months(Months::new(1))?,
+        };
+        Some(next.and_time(NaiveTime::MIN).and_utc().timestamp())
+    }
+}
+
+/// A file's frecency at the end of one bucket of time and its activity within it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryPoint {
+    /// Unix timestamp where the bucket starts
+    pub start: i64,
+    /// Unix timestamp where the bucket ends (exclusive)
+    pub end: i64,
+    /// Frecency as of `end` (the analysis time for the current bucket), counting every
+    /// earlier commit
+    pub score: f64,
+    /// Sum of the commits' weights within the bucket, without recency decay
+    pub activity: f64,
+    /// Commits within the bucket
+    pub commits: u32,
+}
+
+/// One counted commit to the file, with its weights before recency decay
+struct Change {
+    id: Oid,
+    time: i64,
+    author: Option<String>,
+    commit_factor: f64,
+    files_changed: usize,
+    file_factor: f64,
+}
+
+#[derive(Default)]
+struct Changes {
+    changes: Vec<Change>,
+    max_contribution: Option<f64>,
+}
+
+impl Accumulator for Changes {
+    fn add(&mut self, commit: &WeightedCommit) {
+        self.max_contribution = commit.max_contribution;
+        for change in commit.changes {
+            self.changes.push(Change {
+                id: commit.id,
+                time: commit.time,
+                author: commit.author.map(str::to_owned),
+                commit_factor: commit.commit_factor,
+                files_changed: commit.changes.len(),
+                file_factor: commit.file_factor(change),
+            });
+        }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        self.changes.extend(other.changes);
+        self.max_contribution = self.max_contribution.or(other.max_contribution);
+        self
+    }
+}
+
+/// Frecency of `path` at the end of every `interval` from its first counted commit (or
+/// `options.since`) to `options.as_of` (or now), oldest first
+pub fn analyze_history(
+    repo_path: &Path,
+    path: &Path,
+    options: &AnalyzeOptions,
+    interval: HistoryInterval,
+) -> Result<Vec<HistoryPoint>> {
+    let now = options.as_of.unwrap_or_else(|| Utc::now().timestamp());
+    let options = AnalyzeOptions {
+        paths: Some(HashSet::from([PathBuf::from(path)])),
+        ..options.clone()
+    };
+    let Changes {
+        mut changes,
+        max_contribution,
+    } = pipeline::run(repo_path, &options)?;
+    changes.sort_by_key(|change| change.time);
+
+    let Some(first) = options
+        .since
+        .or_else(|| changes.first().map(|change| change.time))
+    else {
+        return Ok(Vec::new());
+    };
+    let invalid_time = || format!("time {first} is out of range");
+    let mut start = interval.floor(first).with_context(invalid_time)?;
+    let mut points = Vec::new();
+    while start <= now {
+        let end = interval.next(start).with_context(invalid_time)?;
+        let as_of = end.min(now);
+        let bucket = changes
+            .iter()
+            .filter(|change| change.time >= start && change.time < end);
+        let score = changes
+            .iter()
+            .take_while(|change| change.time < end)
+            .map(|change| {
+                let commit_weight = options.scorer.commit_weight(&CommitMeta {
+                    id: change.id,
+                    time: change.time,
+                    age_days: ((as_of - change.time) / 86_400).max(0) as f64,
+                    author: change.author.as_deref(),
+                    files_changed: change.files_changed,
+                });
+                let score = change.commit_factor * commit_weight * change.file_factor;
+                max_contribution.map_or(score, |cap| score.min(cap))
+            })
+            .sum();
+        points.push(HistoryPoint {
+            start,
+            end,
+            score,
+            activity: bucket
+                .clone()
+                .map(|change| change.commit_factor * change.file_factor)
+                .sum(),
+            commits: bucket.count() as u32,
+        });
+        start = end;
+    }
+    Ok(points)
+}
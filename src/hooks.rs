@@ -0,0 +1,173 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use git2::Repository;
+
+use crate::open_repo;
+
+/// Hooks that run after the history gains commits
+const HOOKS: [&str; 2] = ["post-commit", "post-merge"];
+/// Lines around the part of a hook that frecenfile owns
+const BEGIN: &str = "# >>> frecenfile: warm the commit statistics cache >>>";
+const END: &str = "# <<< frecenfile <<<";
+
+/// Make the post-commit and post-merge hooks of the repository run the shell `command` in
+/// the background, so commits don't wait for it. The command goes right after the `#!` line,
+/// where hooks that end in `exit` still reach it; other content of existing hooks is kept,
+/// and an earlier installation is replaced. Returns the hook files that changed.
+pub fn install_hooks(repo_path: &Path, command: &str) -> Result<Vec<PathBuf>> {
+    let dir = hooks_dir(&open_repo(repo_path)?)?;
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("failed to create hooks directory {}", dir.display()))?;
+
+    let mut changed = Vec::new();
+    for hook in HOOKS {
+        let path = dir.join(hook);
+        let existing = read_hook(&path)?;
+        let contents = without_block(&existing);
+        let (shebang, rest) = match contents.split_once('\n') {
+            Some((first, rest)) if first.starts_with("#!") => (first, rest),
+            _ => ("#!/bin/sh", contents.as_str()),
+        };
+        if !is_shell(shebang) {
+            bail!(
+                "hook {} is not a shell script ({shebang}); add `{command} &` to it by hand",
+                path.display()
+            );
+        }
+        let contents = format!("{shebang}\n{BEGIN}\n({command}) >/dev/null 2>&1 &\n{END}\n{rest}");
+
+        if contents != existing {
+            write_hook(&path, &contents)?;
+            changed.push(path);
+        }
+    }
+    Ok(changed)
+}
+
+/// Remove what [`install_hooks`] added, deleting hooks that have nothing else left. Returns
+/// the hook files that changed.
+pub fn uninstall_hooks(repo_path: &Path) -> Result<Vec<PathBuf>> {
+    let dir = hooks_dir(&open_repo(repo_path)?)?;
+
+    let mut changed = Vec::new();
+    for hook in HOOKS {
+        let path = dir.join(hook);
+        let existing = read_hook(&path)?;
+        if !existing.lines().any(|line| line == BEGIN) {
+            continue;
+        }
+        let contents = without_block(&existing);
+        if matches!(contents.trim(), "" | "#!/bin/sh") {
+            fs::remove_file(&path)
+                .with_context(|| format!("failed to remove hook {}", path.display()))?;
+        } else {
+            write_hook(&path, &contents)?;
+        }
+        changed.push(path);
+    }
+    Ok(changed)
+}
+
+/// `core.hooksPath` if set (relative to the work tree), otherwise the `hooks` directory that
+/// all worktrees share
+fn hooks_dir(repo: &Repository) -> Result<PathBuf> {
+    match repo.config()?.get_path("core.hooksPath") {
+        Ok(path) if path.is_relative() => Ok(repo.workdir().unwrap_or(repo.path()).join(path)),
+        Ok(path) => Ok(path),
+        Err(_) => Ok(repo.commondir().join("hooks")),
+    }
+}
+
+/// Contents of a hook; empty if there is none
+fn read_hook(path: &Path) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(contents),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(err) => Err(err).with_context(|| format!("failed to read hook {}", path.display())),
+    }
+}
+
+fn write_hook(path: &Path, contents: &str) -> Result<()> {
+    fs::write(path, contents)
+        .with_context(|| format!("failed to write hook {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut permissions = fs::metadata(path)?.permissions();
+        permissions.set_mode(permissions.mode() | 0o111);
+        fs::set_permissions(path, permissions)?;
+    }
+    Ok(())
+}
+
+/// Whether a `#!` line runs a POSIX-style shell, directly or through `env`
+fn is_shell(shebang: &str) -> bool {
+    let mut words = shebang.trim_start_matches("#!").split_whitespace();
+    let mut program = words.next().unwrap_or_default();
+    if program.ends_with("/env") {
+        program = words
+            .find(|word| !word.starts_with('-'))
+            .unwrap_or_default();
+    }
+    let name = program.rsplit('/').next().unwrap_or_default();
+    matches!(name, "sh" | "bash" | "dash" | "ash" | "ksh" | "zsh")
+}
+
+/// `contents` without the lines from `BEGIN` through `END`
+fn without_block(contents: &str) -> String {
+    let mut kept = String::new();
+    let mut inside = false;
+    for line in contents.lines() {
+        match line {
+            BEGIN => inside = true,
+            END if inside => inside = false,
+            _ if !inside => {
+                kept.push_str(line);
+                kept.push('\n');
+            }
+            _ => {}
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestRepo;
+
+    #[test]
+    fn hooks_run_the_command_before_their_own_content() {
+        let repo = TestRepo::new();
+        let hook = repo.path().join(".git/hooks/post-commit");
+        fs::create_dir_all(hook.parent().unwrap()).unwrap();
+        fs::write(&hook, "#!/usr/bin/env bash\necho done\nexit 0\n").unwrap();
+
+        install_hooks(repo.path(), "'frecenfile' 'warm'").unwrap();
+        assert_eq!(
+            fs::read_to_string(&hook).unwrap(),
+            format!(
+                "#!/usr/bin/env bash\n{BEGIN}\n('frecenfile' 'warm') >/dev/null 2>&1 &\n{END}\n\
+                 echo done\nexit 0\n"
+            )
+        );
+        // Installing again changes nothing, and uninstalling restores the hook
+        assert!(
+            install_hooks(repo.path(), "'frecenfile' 'warm'")
+                .unwrap()
+                .is_empty()
+        );
+        uninstall_hooks(repo.path()).unwrap();
+        assert_eq!(
+            fs::read_to_string(&hook).unwrap(),
+            "#!/usr/bin/env bash\necho done\nexit 0\n"
+        );
+        assert!(!repo.path().join(".git/hooks/post-merge").exists());
+
+        fs::write(&hook, "#!/usr/bin/env python3\nprint()\n").unwrap();
+        assert!(install_hooks(repo.path(), "'frecenfile' 'warm'").is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use git2::ObjectType;
+use serde::Serialize;
+
+use crate::{AnalyzeOptions, analyze_repo_with_options, open_repo, percentiles, revwalk};
+
+/// Complexity proxy read from the file's blob at the analyzed revision
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ComplexityMetric {
+    /// Number of lines
+    #[default]
+    Lines,
+    /// Size in bytes
+    Bytes,
+}
+
+/// A file ranked by combined activity and size
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Hotspot {
+    #[serde(serialize_with = "crate::paths::serialize")]
+    pub path: PathBuf,
+    /// Frecency score of the file
+    pub score: f64,
+    /// Line count at the analyzed revision
+    pub lines: u64,
+    /// Size in bytes at the analyzed revision
+    pub bytes: u64,
+    /// Product of the score percentile and the complexity percentile, 0–1
+    pub hotspot: f64,
+}
+
+/// Rank files that are both frequently/recently changed and large at the analyzed revision
+/// (`options.rev`, or HEAD). Files that no longer exist there are left out. Sorted hottest
+/// first.
+pub fn analyze_hotspots(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+    metric: ComplexityMetric,
+) -> Result<Vec<Hotspot>> {
+    let scores = analyze_repo_with_options(repo_path, options)?;
+    let repo = open_repo(repo_path)?;
+    let tree = repo
+        .find_commit(revwalk::start_commit(&repo, options.rev.as_deref())?)?
+        .tree()?;
+    let root = options.prefix.as_deref().unwrap_or(Path::new(""));
+
+    let mut hotspots: Vec<Hotspot> = scores
+        .into_iter()
+        .filter_map(|file| {
+            let entry = tree.get_path(&root.join(&file.path)).ok()?;
+            if entry.kind() != Some(ObjectType::Blob) {
+                return None;
+            }
+            let blob = repo.find_blob(entry.id()).ok()?;
+            let content = blob.content();
+            Some(Hotspot {
+                path: file.path,
+                score: file.score,
+                lines: line_count(content),
+                bytes: content.len() as u64,
+                hotspot: 0.0,
+            })
+        })
+        .collect();
+
+    let activity = percentiles(&hotspots.iter().map(|h| h.score).collect::<Vec<_>>());
+    let complexity = percentiles(
+        &hotspots
+            .iter()
+            .map(|h| match metric {
+                ComplexityMetric::Lines => h.lines as f64,
+                ComplexityMetric::Bytes => h.bytes as f64,
+            })
+            .collect::<Vec<_>>(),
+    );
+    for ((hotspot, a), c) in hotspots.iter_mut().zip(activity).zip(complexity) {
+        hotspot.hotspot = (a / 100.0) * (c / 100.0);
+    }
+
+    hotspots.sort_by(|a, b| {
+        b.hotspot
+            .total_cmp(&a.hotspot)
+            .then_with(|| b.score.total_cmp(&a.score))
+    });
+    Ok(hotspots)
+}
+
+pub(crate) fn line_count(content: &[u8]) -> u64 {
+    let newlines = content.iter().filter(|&&b| b == b'\n').count() as u64;
+    match content.last() {
+        Some(b'\n') | None => newlines,
+        Some(_) => newlines + 1,
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashSet;
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
+use tracing::warn;
+
+/// Default file name used by `git blame --ignore-revs-file` conventions
+pub const BLAME_IGNORE_REVS_FILE: &str = ".git-blame-ignore-revs";
+
+/// Parse an ignore-revs file: one revision per line, `#` starts a comment
+pub fn read_ignore_revs_file(repo: &Repository, path: &Path) -> Result<HashSet<Oid>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("failed to read ignore-revs file {}", path.display()))?;
+    parse_ignore_revs(repo, &contents)
+}
+
+/// Parse the contents of an ignore-revs file
+pub(crate) fn parse_ignore_revs(repo: &Repository, contents: &str) -> Result<HashSet<Oid>> {
+    listed_revs(contents)
+        .map(|rev| resolve_rev(repo, rev))
+        .collect()
+}
+
+/// Parse the contents of an ignore-revs file found on its own, skipping (with a warning)
+/// revisions the repository lacks, e.g. in a shallow clone or after a history rewrite
+pub(crate) fn parse_ignore_revs_lenient(
+    repo: &Repository,
+    contents: &str,
+    source: &str,
+) -> HashSet<Oid> {
+    listed_revs(contents)
+        .filter_map(|rev| match resolve_rev(repo, rev) {
+            Ok(oid) => Some(oid),
+            Err(err) => {
+                warn!("{source}: skipping {rev}: {err:#}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// The revisions of an ignore-revs file: one per line, `#` starts a comment
+fn listed_revs(contents: &str) -> impl Iterator<Item = &str> {
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|rev| !rev.is_empty())
+}
+
+/// Resolve a (possibly abbreviated) revision to the commit it points to
+pub fn resolve_rev(repo: &Repository, rev: &str) -> Result<Oid> {
+    let object = repo
+        .revparse_single(rev)
+        .with_context(|| format!("unknown revision {rev}"))?;
+    let commit = object
+        .peel_to_commit()
+        .with_context(|| format!("revision {rev} is not a commit"))?;
+    Ok(commit.id())
+}
+
+/// Locate the repository's blame ignore file: `blame.ignoreRevsFile` from git config,
+/// falling back to `.git-blame-ignore-revs` at the root of the work tree
+pub fn default_ignore_revs_file(repo: &Repository) -> Option<std::path::PathBuf> {
+    let workdir = repo.workdir()?;
+    let configured = repo
+        .config()
+        .ok()
+        .and_then(|cfg| cfg.get_path("blame.ignoreRevsFile").ok())
+        .map(|p| if p.is_absolute() { p } else { workdir.join(p) });
+
+    configured
+        .or_else(|| Some(workdir.join(BLAME_IGNORE_REVS_FILE)))
+        .filter(|p| p.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listed_revs_skip_comments_and_blank_lines() {
+        let contents = "# Formatting\n\nabc123 # rustfmt\n  def456\n#ghi789\n";
+        assert_eq!(
+            listed_revs(contents).collect::<Vec<_>>(),
+            ["abc123", "def456"]
+        );
+    }
+}
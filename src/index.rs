@@ -0,0 +1,809 @@
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, RandomState};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use std::{fs, mem, process};
+
+use anyhow::Result;
+use chrono::Utc;
+use git2::{Oid, Repository};
+use regex::Regex;
+use rustc_hash::{FxHashMap as HashMap, FxHashSet as HashSet};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::cache::{Cache, CacheMode};
+use crate::intern::PathTable;
+use crate::paths::{path_bytes, path_from_bytes};
+use crate::pipeline::{self, Accumulator, FileStats, ScoreMap, Scores, WeightedCommit};
+use crate::revwalk::Topology;
+use crate::scorer::{CommitMeta, ScoreMode};
+use crate::stats::Phase;
+use crate::{AnalyzeOptions, config, open_repo, revwalk};
+
+/// Version of the stored index: bump it whenever `ScoreIndex` or the way it is built changes
+const INDEX_VERSION: u32 = 5;
+
+/// Cache keys of indexes start with this, followed by a hash of the options. The changes of
+/// each file are stored under that key followed by `:<token>:<position in the paths>`.
+const INDEX_KEY_PREFIX: &str = "score-index:";
+
+/// Every counted change of a history with its weights before recency decay, so scores for
+/// any analysis time and time window are re-derived without walking the commits again.
+/// Per-file sums cannot be stored instead: a change decays by its age in whole days at the
+/// analysis time (`1 / (age_days + 1)^2` by default, or any [`Scorer`](crate::Scorer)'s own
+/// curve), and a sum of such terms can neither be moved to another analysis time nor cut
+/// to a time window. Re-deriving the scores is one pass over the changes, without git.
+///
+/// The index is stored as this header, holding the commits and paths, and one entry per file
+/// with its changes. When the walked refs move, only the commits that joined or left the
+/// selection are walked or dropped, and only the entries of the files they touched are
+/// rewritten.
+#[derive(Default, Serialize, Deserialize)]
+pub(crate) struct ScoreIndex {
+    version: u32,
+    /// Hash of the refs the walk starts from or stops at; while they stay put, the index is
+    /// up to date
+    refs: String,
+    /// Hash of the ignored revisions, mailmaps and attributes the index was built with
+    inputs: String,
+    /// Commits selected for scoring, including those that ended up not counting
+    selected: usize,
+    max_contribution: Option<f64>,
+    /// None for commits that left the selection after they were indexed
+    commits: Vec<Option<IndexedCommit>>,
+    /// Ids of the selected commits that did not count, e.g. merges
+    skipped: Vec<Vec<u8>>,
+    /// Distinct commit authors, which commits refer to by position
+    authors: Vec<String>,
+    /// Positions in `authors`, while the index is built
+    #[serde(skip)]
+    author_ids: HashMap<String, u32>,
+    paths: Vec<PathBuf>,
+    /// Token of the stored entry holding the changes of each path in `paths`; None once no
+    /// change of it is left
+    entries: Vec<Option<u64>>,
+    /// Keyed by position in `paths`, or by run id while the index is built; stored in the
+    /// entries, not in the header
+    #[serde(skip)]
+    files: HashMap<u32, IndexedFile>,
+    /// Positions of the files whose changes differ from their stored entries
+    #[serde(skip)]
+    dirty: HashSet<u32>,
+    /// Resolves the run ids while the index is built
+    #[serde(skip)]
+    run_paths: Option<Arc<PathTable>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexedCommit {
+    id: Vec<u8>,
+    time: i64,
+    /// Index into `ScoreIndex::authors`
+    author: Option<u32>,
+    commit_factor: f64,
+    files_changed: usize,
+    /// Files the commit changed, like the keys of `ScoreIndex::files`
+    files: Vec<u32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct IndexedFile {
+    changes: Vec<IndexedChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexedChange {
+    /// Index into `ScoreIndex::commits`
+    commit: u32,
+    factor: f64,
+    churn: u64,
+}
+
+impl Accumulator for ScoreIndex {
+    fn add(&mut self, commit: &WeightedCommit) {
+        let index = self.commits.len() as u32;
+        let author = commit.author.map(|author| self.author_id(author));
+        let mut files: Vec<u32> = commit.changes.iter().map(|change| change.id).collect();
+        files.sort_unstable();
+        files.dedup();
+        self.commits.push(Some(IndexedCommit {
+            id: commit.id.as_bytes().to_vec(),
+            time: commit.time,
+            author,
+            commit_factor: commit.commit_factor,
+            files_changed: commit.changes.len(),
+            files,
+        }));
+        self.max_contribution = commit.max_contribution;
+        if self.run_paths.is_none() {
+            self.run_paths = Some(commit.paths.clone());
+        }
+        for change in commit.changes {
+            let file = self.files.entry(change.id).or_default();
+            file.changes.push(IndexedChange {
+                commit: index,
+                factor: commit.file_factor(change),
+                churn: change.churn.unwrap_or(0),
+            });
+        }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        let offset = self.commits.len() as u32;
+        let authors: Vec<u32> = other
+            .authors
+            .iter()
+            .map(|author| self.author_id(author))
+            .collect();
+        self.commits.extend(other.commits.into_iter().map(|commit| {
+            commit.map(|commit| IndexedCommit {
+                author: commit.author.map(|author| authors[author as usize]),
+                ..commit
+            })
+        }));
+        self.max_contribution = self.max_contribution.or(other.max_contribution);
+        self.run_paths = self.run_paths.or(other.run_paths);
+        for (run_id, other) in other.files {
+            let file = self.files.entry(run_id).or_default();
+            file.changes
+                .extend(other.changes.into_iter().map(|change| IndexedChange {
+                    commit: change.commit + offset,
+                    ..change
+                }));
+        }
+        self
+    }
+
+    fn estimated_memory(&self) -> u64 {
+        const PER_FILE_OVERHEAD: usize = mem::size_of::<(u32, IndexedFile)>() + 16;
+        const PER_COMMIT: usize = mem::size_of::<Option<IndexedCommit>>() + 20;
+        let changes: usize = self.files.values().map(|file| file.changes.len()).sum();
+        (self.files.len() * PER_FILE_OVERHEAD
+            + changes * (mem::size_of::<IndexedChange>() + mem::size_of::<u32>())
+            + self.commits.len() * PER_COMMIT) as u64
+    }
+}
+
+impl ScoreIndex {
+    fn author_id(&mut self, author: &str) -> u32 {
+        if let Some(&id) = self.author_ids.get(author) {
+            return id;
+        }
+        let id = self.authors.len() as u32;
+        self.authors.push(author.to_owned());
+        self.author_ids.insert(author.to_owned(), id);
+        id
+    }
+
+    /// Ids of the indexed commits and of those that did not count
+    fn selected_ids(&self) -> HashSet<&[u8]> {
+        self.commits
+            .iter()
+            .flatten()
+            .map(|commit| commit.id.as_slice())
+            .chain(self.skipped.iter().map(Vec::as_slice))
+            .collect()
+    }
+
+    /// Number of commits that left the selection but are still in `commits`
+    fn dropped(&self) -> usize {
+        self.commits
+            .iter()
+            .filter(|commit| commit.is_none())
+            .count()
+    }
+
+    /// Drop the commits in `dropped`, then add the commits of `added`, an index of a walk
+    /// keyed by run ids, whose selected commits were `selected`. Marks the files whose
+    /// changes differ from their stored entries.
+    fn update(&mut self, dropped: &HashSet<Vec<u8>>, added: ScoreIndex, selected: &[Oid]) {
+        for (index, slot) in self.commits.iter_mut().enumerate() {
+            let Some(commit) = slot.take_if(|commit| dropped.contains(&commit.id)) else {
+                continue;
+            };
+            for position in commit.files {
+                if let Some(file) = self.files.get_mut(&position) {
+                    file.changes.retain(|change| change.commit != index as u32);
+                    if file.changes.is_empty() {
+                        self.files.remove(&position);
+                    }
+                }
+                self.dirty.insert(position);
+            }
+        }
+        self.skipped.retain(|id| !dropped.contains(id));
+
+        let counted: HashSet<&[u8]> = added
+            .commits
+            .iter()
+            .flatten()
+            .map(|commit| commit.id.as_slice())
+            .collect();
+        self.skipped.extend(
+            selected
+                .iter()
+                .filter(|oid| !counted.contains(oid.as_bytes()))
+                .map(|oid| oid.as_bytes().to_vec()),
+        );
+        self.author_ids = (0..)
+            .zip(&self.authors)
+            .map(|(id, author)| (author.clone(), id))
+            .collect();
+        let authors: Vec<u32> = added
+            .authors
+            .iter()
+            .map(|author| self.author_id(author))
+            .collect();
+        // Files by run id, as `added` keys them, and then by position in `paths`
+        let mut positions: HashMap<PathBuf, u32> = (0..)
+            .zip(&self.paths)
+            .map(|(position, path)| (path.clone(), position))
+            .collect();
+        let mut run_positions: HashMap<u32, u32> = HashMap::default();
+        let mut position_of = |run_id: u32| -> Option<u32> {
+            if let Some(&position) = run_positions.get(&run_id) {
+                return Some(position);
+            }
+            let path = added.run_paths.as_ref()?.resolve(run_id)?.to_path_buf();
+            let position = *positions.entry(path.clone()).or_insert_with(|| {
+                self.paths.push(path);
+                self.entries.push(None);
+                self.paths.len() as u32 - 1
+            });
+            run_positions.insert(run_id, position);
+            Some(position)
+        };
+
+        let offset = self.commits.len() as u32;
+        for commit in added.commits.into_iter().flatten() {
+            let files = commit
+                .files
+                .iter()
+                .filter_map(|&id| position_of(id))
+                .collect();
+            self.commits.push(Some(IndexedCommit {
+                author: commit.author.map(|author| authors[author as usize]),
+                files,
+                ..commit
+            }));
+        }
+        for (run_id, added) in added.files {
+            let Some(position) = position_of(run_id) else {
+                continue;
+            };
+            let file = self.files.entry(position).or_default();
+            file.changes
+                .extend(added.changes.into_iter().map(|change| IndexedChange {
+                    commit: change.commit + offset,
+                    ..change
+                }));
+            self.dirty.insert(position);
+        }
+        self.max_contribution = added.max_contribution.or(self.max_contribution);
+    }
+
+    /// Read the entries of the files from the cache; false when one of them is missing or
+    /// unreadable
+    fn load_files(&mut self, cache: &Cache, key: &str) -> bool {
+        for (position, token) in (0..).zip(&self.entries) {
+            let Some(token) = token else {
+                continue;
+            };
+            let Some(file) = cache
+                .get(&entry_key(key, *token, position))
+                .and_then(|bytes| bincode::deserialize(&bytes).ok())
+            else {
+                return false;
+            };
+            self.files.insert(position, file);
+        }
+        true
+    }
+
+    /// Store the header under `key` along with entries for the files that changed, then drop
+    /// the entries they replace. Nothing changes in the cache when one of the writes fails.
+    fn store(&mut self, cache: &Cache, key: &str) -> Result<()> {
+        let token = new_token();
+        let mut written: Vec<String> = Vec::new();
+        let mut replaced: Vec<String> = Vec::new();
+        let mut entries = self.entries.clone();
+        for &position in &self.dirty {
+            if let Some(old) = entries[position as usize].take() {
+                replaced.push(entry_key(key, old, position));
+            }
+            if let Some(file) = self.files.get(&position) {
+                let entry = entry_key(key, token, position);
+                if !cache.insert(&entry, &bincode::serialize(file)?) {
+                    written.iter().for_each(|entry| cache.remove(entry));
+                    return Ok(());
+                }
+                written.push(entry);
+                entries[position as usize] = Some(token);
+            }
+        }
+        let previous = mem::replace(&mut self.entries, entries);
+        if !cache.insert(key, &bincode::serialize(self)?) {
+            self.entries = previous;
+            written.iter().for_each(|entry| cache.remove(entry));
+            return Ok(());
+        }
+        replaced.iter().for_each(|entry| cache.remove(entry));
+        self.dirty.clear();
+        Ok(())
+    }
+
+    /// Drop the stored entries of the files, once a new index replaced this one
+    fn remove_entries(&self, cache: &Cache, key: &str) {
+        for (position, token) in (0..).zip(&self.entries) {
+            if let Some(token) = token {
+                cache.remove(&entry_key(key, *token, position));
+            }
+        }
+    }
+
+    /// Per-file stats of the commits in the time window of `options`, with the recency decay
+    /// of its scorer as of `now`, and the number of selected commits in the window
+    fn scores(&self, options: &AnalyzeOptions, now: i64) -> (ScoreMap, usize) {
+        // None for commits outside the window
+        let weights: Vec<Option<f64>> = self
+            .commits
+            .iter()
+            .map(|commit| {
+                let commit = commit.as_ref()?;
+                options.in_time_window(commit.time).then(|| {
+                    commit.commit_factor
+                        * options.scorer.commit_weight(&CommitMeta {
+                            id: Oid::from_bytes(&commit.id).unwrap_or_else(|_| Oid::zero()),
+                            time: commit.time,
+                            age_days: ((now - commit.time) / 86_400).max(0) as f64,
+                            author: commit
+                                .author
+                                .map(|author| self.authors[author as usize].as_str()),
+                            files_changed: commit.files_changed,
+                        })
+                })
+            })
+            .collect();
+        let mut paths = Vec::with_capacity(self.files.len());
+        let scores = self
+            .files
+            .iter()
+            .filter_map(|(&position, file)| {
+                let mut stats = FileStats::default();
+                for change in &file.changes {
+                    let commit = change.commit as usize;
+                    let (Some(weight), Some(indexed)) = (weights[commit], &self.commits[commit])
+                    else {
+                        continue;
+                    };
+                    let score = weight * change.factor;
+                    stats.score += self.max_contribution.map_or(score, |cap| score.min(cap));
+                    stats.commits += 1;
+                    stats.last_modified = stats.last_modified.max(indexed.time);
+                    stats.churn += change.churn;
+                }
+                (stats.commits > 0).then(|| {
+                    paths.push(self.paths[position as usize].as_path());
+                    (paths.len() as u32 - 1, stats)
+                })
+            })
+            .collect();
+        // The paths are distinct, so their positions become their run ids
+        let paths = PathTable::from_paths(paths);
+        let scores = ScoreMap::new(Arc::new(paths), scores);
+        // Commits that did not count are only known by number, for the whole history
+        let selected = if options.since.is_none() && options.until.is_none() {
+            self.selected
+        } else {
+            weights.iter().filter(|weight| weight.is_some()).count()
+        };
+        (scores, selected)
+    }
+}
+
+/// Cache key of the entry of the file at `position`, written with `token`
+fn entry_key(key: &str, token: u64, position: u32) -> String {
+    format!("{key}:{token:016x}:{position}")
+}
+
+/// Tag of the entries written by one update of an index, so that an interrupted or
+/// concurrent update never overwrites the entries a stored header refers to
+fn new_token() -> u64 {
+    RandomState::new().hash_one((SystemTime::now(), process::id()))
+}
+
+/// Per-file stats of the history selected by `options`, and the number of selected commits.
+/// They are derived from the index stored in the cache when it was built with the same
+/// options, for any time window: when the walked refs moved since, the commits that joined
+/// the selection are walked and those that left it are dropped. Otherwise the history is
+/// walked, and walks of the whole history with a writable cache store the index for next
+/// time; with [`AnalyzeOptions::index_windowed_runs`], so do walks with a time window, which
+/// then walk the whole history.
+pub(crate) fn indexed_scores(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+) -> Result<(Scores, usize)> {
+    let now = options.as_of.unwrap_or_else(|| Utc::now().timestamp());
+    let repo = open_repo(repo_path)?;
+    let cache = Cache::open(&repo, options)?;
+    // The index holds every counted change, more than the memory limit may allow. Which
+    // commits a limit selects depends on the window, so those walks are not indexed either.
+    let windowed = options.since.is_some() || options.until.is_some();
+    let options_hash = if options.cache_mode == CacheMode::Disabled
+        || options.max_memory_bytes.is_some()
+        || (windowed && options.max_commits.is_some())
+    {
+        None
+    } else {
+        options_hash(&repo, options)?
+    };
+    let Some(options_hash) = options_hash else {
+        return walk(repo_path, options, &cache);
+    };
+
+    let key = format!("{INDEX_KEY_PREFIX}{options_hash}");
+    let refs = refs_hash(&repo, options)?;
+    let inputs = inputs_hash(&repo, options)?;
+    // The index covers the whole history, whatever the window of this run
+    let whole_history = AnalyzeOptions {
+        since: None,
+        until: None,
+        ..options.clone()
+    };
+    let mut stale = None;
+    let stored = cache
+        .get(&key)
+        .and_then(|bytes| {
+            let index = bincode::deserialize::<ScoreIndex>(&bytes);
+            if index.is_err() {
+                cache.discard(&key);
+            }
+            index.ok()
+        })
+        .filter(|index| index.version == INDEX_VERSION);
+    if let Some(mut index) = stored {
+        if index.inputs != inputs {
+            stale = Some(index);
+        } else if !index.load_files(&cache, &key) {
+            cache.discard(&key);
+        } else if index.refs == refs {
+            debug!(commits = index.selected, "reusing the cached score index");
+            options.stats.index_hit();
+            let (scores, selected) = options
+                .stats
+                .time(Phase::Reduce, || index.scores(options, now));
+            return Ok((Scores::InMemory(scores), selected));
+        } else if !options.dedup_cherry_picks {
+            // Which copy of a cherry-pick counts depends on the whole selection
+            let selection = pipeline::select_commits(&repo, &whole_history)?;
+            let indexed = index.selected_ids();
+            let added: Vec<Oid> = selection
+                .iter()
+                .filter(|oid| !indexed.contains(oid.as_bytes()))
+                .copied()
+                .collect();
+            let kept: HashSet<&[u8]> = selection.iter().map(Oid::as_bytes).collect();
+            let dropped: HashSet<Vec<u8>> = indexed
+                .into_iter()
+                .filter(|id| !kept.contains(id))
+                .map(<[u8]>::to_vec)
+                .collect();
+            // Dropped commits keep their place until the index is rebuilt, so a selection
+            // that mostly changed is walked afresh
+            if index.dropped() + dropped.len() <= selection.len() {
+                debug!(
+                    added = added.len(),
+                    dropped = dropped.len(),
+                    "updating the cached score index"
+                );
+                let walked = if added.is_empty() {
+                    ScoreIndex::default()
+                } else {
+                    pipeline::run_commits_with_cache(
+                        repo_path,
+                        &whole_history,
+                        &cache,
+                        added.clone(),
+                        ScoreIndex::default,
+                        |_, _| {},
+                    )?
+                };
+                index.update(&dropped, walked, &added);
+                index.refs = refs;
+                index.selected = selection.len();
+                if options.cache_mode == CacheMode::ReadWrite
+                    && !options.cancellation.is_cancelled()
+                {
+                    index.store(&cache, &key)?;
+                }
+                options.stats.index_hit();
+                let (scores, selected) = options
+                    .stats
+                    .time(Phase::Reduce, || index.scores(options, now));
+                return Ok((Scores::InMemory(scores), selected));
+            }
+            stale = Some(index);
+        } else {
+            stale = Some(index);
+        }
+    }
+    // A windowed walk sees only part of the history, and a read-only cache cannot keep
+    // the index
+    if (windowed && !options.index_windowed_runs) || options.cache_mode == CacheMode::ReadOnly {
+        debug!("no cached score index for these options; walking the history");
+        return walk(repo_path, options, &cache);
+    }
+
+    debug!("no cached score index for these options; walking the history to build it");
+    let selection = pipeline::select_commits(&repo, &whole_history)?;
+    let mut selected = 0;
+    let walked: ScoreIndex = pipeline::run_commits_with_cache(
+        repo_path,
+        &whole_history,
+        &cache,
+        selection.clone(),
+        ScoreIndex::default,
+        |_, progress| selected = progress.total,
+    )?;
+    let mut index = ScoreIndex {
+        version: INDEX_VERSION,
+        refs,
+        inputs,
+        ..ScoreIndex::default()
+    };
+    index.update(&HashSet::default(), walked, &selection);
+    index.selected = selected;
+    // An index of a cancelled walk would pass for the whole history
+    if !options.cancellation.is_cancelled() {
+        index.store(&cache, &key)?;
+        if let Some(stale) = stale {
+            stale.remove_entries(&cache, &key);
+        }
+    }
+    let (scores, selected) = options
+        .stats
+        .time(Phase::Reduce, || index.scores(options, now));
+    Ok((Scores::InMemory(scores), selected))
+}
+
+/// Per-file stats of a plain walk, and the number of selected commits
+fn walk(repo_path: &Path, options: &AnalyzeOptions, cache: &Cache) -> Result<(Scores, usize)> {
+    let mut selected = 0;
+    let scores = pipeline::run_scores(repo_path, options, cache, |_, progress| {
+        selected = progress.total
+    })?;
+    Ok((scores, selected))
+}
+
+/// The options that decide which changes count and how they are weighted, apart from the
+/// analysis time and the time window. Settings applied after aggregation, or that only affect
+/// how the work is done, are left out.
+#[derive(Serialize)]
+struct IndexKey<'a> {
+    paths: Vec<&'a [u8]>,
+    rev: Option<&'a str>,
+    ref_globs: &'a [String],
+    since_merge_base: Option<&'a str>,
+    recurse_submodules: bool,
+    first_parent: bool,
+    prefix: Option<&'a [u8]>,
+    ignore_case: bool,
+    path_weights: Vec<(&'a str, f64)>,
+    commit_type_weights: &'a BTreeMap<String, f64>,
+    issue_ref_weight: Option<f64>,
+    issue_pattern: Option<&'a str>,
+    max_commit_contribution: Option<f64>,
+    max_commits: Option<u64>,
+    ignore_revs: &'a [String],
+    ignore_revs_files: Vec<&'a [u8]>,
+    use_blame_ignore_revs: bool,
+    authors: Vec<&'a str>,
+    author_boosts: Vec<(&'a str, f64)>,
+    exclude_authors: Vec<&'a str>,
+    exclude_messages: Vec<&'a str>,
+    dedup_cherry_picks: bool,
+    churn: bool,
+    mode: &'static str,
+    default_excludes: bool,
+    skip_binary: bool,
+    ignore_whitespace: bool,
+    min_changed_lines: Option<u64>,
+    file_sizes: bool,
+    max_file_size: Option<u64>,
+    config_file: Option<String>,
+    scorer: String,
+}
+
+/// Hash of the [`IndexKey`] of `options`; None when the scorer has no
+/// [`Scorer::cache_key`](crate::Scorer::cache_key), so its indexes cannot be told apart
+fn options_hash(repo: &Repository, options: &AnalyzeOptions) -> Result<Option<String>> {
+    let Some(scorer) = options.scorer.cache_key() else {
+        return Ok(None);
+    };
+    let mut paths: Vec<&[u8]> = options
+        .paths
+        .iter()
+        .flatten()
+        .map(|p| path_bytes(p))
+        .collect();
+    paths.sort();
+    let key = IndexKey {
+        paths,
+        rev: options.rev.as_deref(),
+        ref_globs: &options.ref_globs,
+        since_merge_base: options.since_merge_base.as_deref(),
+        recurse_submodules: options.recurse_submodules,
+        first_parent: options.topology == Topology::FirstParent,
+        prefix: options.prefix.as_deref().map(path_bytes),
+        ignore_case: options.ignore_case,
+        path_weights: options
+            .path_weights
+            .iter()
+            .map(|weight| (weight.glob(), weight.multiplier()))
+            .collect(),
+        commit_type_weights: &options.commit_type_weights,
+        issue_ref_weight: options.issue_ref_weight,
+        issue_pattern: options.issue_pattern.as_ref().map(Regex::as_str),
+        max_commit_contribution: options.max_commit_contribution,
+        max_commits: options.max_commits.map(|max| max as u64),
+        ignore_revs: &options.ignore_revs,
+        ignore_revs_files: options
+            .ignore_revs_files
+            .iter()
+            .map(|file| path_bytes(file))
+            .collect(),
+        use_blame_ignore_revs: options.use_blame_ignore_revs,
+        authors: patterns(&options.authors),
+        author_boosts: options
+            .author_boosts
+            .iter()
+            .map(|boost| (boost.pattern.as_str(), boost.factor))
+            .collect(),
+        exclude_authors: patterns(&options.exclude_authors),
+        exclude_messages: patterns(&options.exclude_messages),
+        dedup_cherry_picks: options.dedup_cherry_picks,
+        churn: options.churn,
+        mode: match options.mode {
+            ScoreMode::Frecency => "frecency",
+            ScoreMode::Recency => "recency",
+            ScoreMode::Frequency => "frequency",
+        },
+        default_excludes: options.default_excludes,
+        skip_binary: options.skip_binary,
+        ignore_whitespace: options.ignore_whitespace,
+        min_changed_lines: options.min_changed_lines,
+        file_sizes: options.file_sizes,
+        max_file_size: options.max_file_size,
+        config_file: config::config_file_contents(repo, options)?.map(|(contents, _)| contents),
+        scorer,
+    };
+    let hash = Sha256::digest(bincode::serialize(&key)?);
+    Ok(Some(hex::encode(&hash[0..16])))
+}
+
+fn patterns(regexes: &[Regex]) -> Vec<&str> {
+    regexes.iter().map(Regex::as_str).collect()
+}
+
+/// Hash of the refs the walk starts from or stops at
+fn refs_hash(repo: &Repository, options: &AnalyzeOptions) -> Result<String> {
+    let mut hasher = Sha256::new();
+    let start = revwalk::start_commit(repo, options.rev.as_deref()).ok();
+    if let Some(start) = start {
+        hasher.update(start.as_bytes());
+    }
+    let mut refs = Vec::new();
+    for glob in &options.ref_globs {
+        for reference in repo.references_glob(&revwalk::ref_glob_pattern(glob))? {
+            let reference = reference?;
+            if let Some(target) = reference.target() {
+                refs.push((reference.name_bytes().to_vec(), target));
+            }
+        }
+    }
+    refs.sort();
+    refs.dedup();
+    for (name, target) in refs {
+        hasher.update(name);
+        hasher.update([0]);
+        hasher.update(target.as_bytes());
+    }
+    if let (Some(start), Some(base)) = (start, &options.since_merge_base) {
+        let base = repo.revparse_single(base)?.peel_to_commit()?.id();
+        hasher.update(repo.merge_base(start, base)?.as_bytes());
+    }
+    Ok(hex::encode(&hasher.finalize()[0..16]))
+}
+
+/// Hash of everything else outside the options that selects or attributes commits: the
+/// ignored revisions, the mailmaps and, when generated files are excluded, the attributes
+/// that mark them. Changing them may change any commit, so the index is rebuilt.
+fn inputs_hash(repo: &Repository, options: &AnalyzeOptions) -> Result<String> {
+    let mut hasher = Sha256::new();
+    let mut ignored: Vec<Oid> = revwalk::collect_ignored_revs(repo, options)?
+        .into_iter()
+        .collect();
+    ignored.sort();
+    for oid in ignored {
+        hasher.update(oid.as_bytes());
+    }
+    for mailmap in mailmap_sources(repo) {
+        hasher.update((mailmap.len() as u64).to_le_bytes());
+        hasher.update(mailmap);
+    }
+    if options.default_excludes {
+        for (name, attributes) in attribute_sources(repo) {
+            hasher.update((name.len() as u64).to_le_bytes());
+            hasher.update(name);
+            hasher.update((attributes.len() as u64).to_le_bytes());
+            hasher.update(attributes);
+        }
+    }
+    Ok(hex::encode(&hasher.finalize()[0..16]))
+}
+
+/// Contents of the mailmaps `Repository::mailmap` combines, in its order (an empty entry for
+/// each one missing): `.mailmap` in the work tree, the `mailmap.blob` blob (`HEAD:.mailmap` by
+/// default in bare repositories) and the `mailmap.file` file
+fn mailmap_sources(repo: &Repository) -> [Vec<u8>; 3] {
+    let config = repo.config().ok();
+    let workdir_file = repo
+        .workdir()
+        .and_then(|workdir| fs::read(workdir.join(".mailmap")).ok());
+    let blob = config
+        .as_ref()
+        .and_then(|config| config.get_string("mailmap.blob").ok())
+        .or_else(|| repo.is_bare().then(|| "HEAD:.mailmap".to_owned()))
+        .and_then(|rev| repo.revparse_single(&rev).ok()?.peel_to_blob().ok())
+        .map(|blob| blob.content().to_vec());
+    let file = config
+        .as_ref()
+        .and_then(|config| config.get_path("mailmap.file").ok())
+        .and_then(|path| fs::read(path).ok());
+    [workdir_file, blob, file].map(Option::unwrap_or_default)
+}
+
+/// Names and contents of the attribute files [`is_generated`](crate::generated::is_generated)
+/// reads, in a fixed order: the `.gitattributes` at the root and every tracked one (from the
+/// work tree, or the index where it has none), `info/attributes` and `core.attributesFile`
+/// (`git/attributes` in the XDG config directory by default). Untracked `.gitattributes` below
+/// the root are not looked for, as that would mean walking the whole work tree.
+fn attribute_sources(repo: &Repository) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let mut sources = BTreeMap::new();
+    sources.insert(b".gitattributes".to_vec(), None);
+    if let Ok(index) = repo.index() {
+        for entry in index.iter() {
+            if entry.path == b".gitattributes" || entry.path.ends_with(b"/.gitattributes") {
+                sources.insert(entry.path, Some(entry.id));
+            }
+        }
+    }
+    let mut contents: Vec<(Vec<u8>, Vec<u8>)> = sources
+        .into_iter()
+        .map(|(path, id)| {
+            let workdir_file = repo
+                .workdir()
+                .and_then(|workdir| fs::read(workdir.join(path_from_bytes(&path).ok()?)).ok());
+            let indexed = || Some(repo.find_blob(id?).ok()?.content().to_vec());
+            let attributes = workdir_file.or_else(indexed).unwrap_or_default();
+            (path, attributes)
+        })
+        .collect();
+    let info = fs::read(repo.commondir().join("info").join("attributes")).unwrap_or_default();
+    contents.push((b"info/attributes".to_vec(), info));
+    let global = repo
+        .config()
+        .ok()
+        .and_then(|config| config.get_path("core.attributesFile").ok())
+        .or_else(|| {
+            let xdg = git2::Config::find_xdg().ok()?;
+            Some(xdg.parent()?.join("attributes"))
+        })
+        .and_then(|path| fs::read(path).ok())
+        .unwrap_or_default();
+    contents.push((b"core.attributesFile".to_vec(), global));
+    contents
+}
@@ -0,0 +1,120 @@
+use std::mem;
+use std::path::Path;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use rustc_hash::FxHashMap as HashMap;
+
+/// Id of a path in the cache's path table
+pub(crate) type PathId = u32;
+
+/// Id of a path within one run, numbered in the order the paths were first seen
+pub(crate) type RunPathId = u32;
+
+/// The paths seen during a run: each distinct path is allocated once and shared by every change
+/// that touches it, together with its run id and its id in the cache's path table once that
+/// is known
+#[derive(Default)]
+pub(crate) struct PathTable {
+    tables: RwLock<Tables>,
+}
+
+#[derive(Default)]
+struct Tables {
+    ids: HashMap<Arc<Path>, (RunPathId, Option<PathId>)>,
+    /// Indexed by run id
+    run_paths: Vec<Arc<Path>>,
+    paths: HashMap<PathId, Arc<Path>>,
+    /// Bytes of the distinct paths in `run_paths`
+    path_bytes: usize,
+}
+
+impl Tables {
+    fn share(&mut self, path: &Path) -> (RunPathId, Arc<Path>) {
+        if let Some((shared, &(run_id, _))) = self.ids.get_key_value(path) {
+            return (run_id, shared.clone());
+        }
+        let shared: Arc<Path> = Arc::from(path);
+        let run_id = self.run_paths.len() as RunPathId;
+        self.ids.insert(shared.clone(), (run_id, None));
+        self.run_paths.push(shared.clone());
+        self.path_bytes += path.as_os_str().len();
+        (run_id, shared)
+    }
+}
+
+impl PathTable {
+    /// A table holding `paths`, with their positions as run ids
+    pub(crate) fn from_paths<'a>(paths: impl IntoIterator<Item = &'a Path>) -> Self {
+        let table = Self::default();
+        for path in paths {
+            table.share(path);
+        }
+        table
+    }
+
+    /// Run id and shared copy of `path`
+    pub(crate) fn share(&self, path: &Path) -> (RunPathId, Arc<Path>) {
+        if let Some((shared, &(run_id, _))) = self.read().ids.get_key_value(path) {
+            return (run_id, shared.clone());
+        }
+        self.write().share(path)
+    }
+
+    /// Run id of `path`, if it was seen
+    pub(crate) fn run_id(&self, path: &Path) -> Option<RunPathId> {
+        self.read().ids.get(path).map(|&(run_id, _)| run_id)
+    }
+
+    /// The shared path with run id `run_id`
+    pub(crate) fn resolve(&self, run_id: RunPathId) -> Option<Arc<Path>> {
+        self.read().run_paths.get(run_id as usize).cloned()
+    }
+
+    /// Cache id of `path`, if known
+    pub(crate) fn id(&self, path: &Path) -> Option<PathId> {
+        self.read().ids.get(path).and_then(|&(_, id)| id)
+    }
+
+    /// Run id and shared copy of the path with cache id `id`, if known
+    pub(crate) fn path(&self, id: PathId) -> Option<(RunPathId, Arc<Path>)> {
+        let tables = self.read();
+        let path = tables.paths.get(&id)?;
+        Some((tables.ids.get(path)?.0, path.clone()))
+    }
+
+    /// Remember that `path` has cache id `id`; returns its run id and shared copy
+    pub(crate) fn record(&self, path: &Path, id: PathId) -> (RunPathId, Arc<Path>) {
+        let mut tables = self.write();
+        let (run_id, shared) = tables.share(path);
+        tables.ids.insert(shared.clone(), (run_id, Some(id)));
+        tables.paths.insert(id, shared.clone());
+        (run_id, shared)
+    }
+
+    /// Forget every path, freeing the table; run ids handed out so far no longer resolve.
+    /// Paths with cache ids are looked up in the cache again when they come up.
+    pub(crate) fn clear(&self) {
+        *self.write() = Tables::default();
+    }
+
+    /// Approximate heap usage: the path bytes plus the shared allocation and table entries of
+    /// each path
+    pub(crate) fn estimated_memory(&self) -> u64 {
+        // Arc reference counts, the `ids` entry and the `run_paths` slot
+        const PER_PATH_OVERHEAD: usize = 2 * mem::size_of::<usize>()
+            + mem::size_of::<(Arc<Path>, (RunPathId, Option<PathId>))>()
+            + 16
+            + mem::size_of::<Arc<Path>>();
+        let tables = self.read();
+        (tables.path_bytes + tables.run_paths.len() * PER_PATH_OVERHEAD) as u64
+    }
+
+    // A panicking worker cannot leave the tables half-updated, so poisoning is ignored
+    fn read(&self) -> RwLockReadGuard<'_, Tables> {
+        self.tables.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Tables> {
+        self.tables.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
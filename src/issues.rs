@@ -0,0 +1,85 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use anyhow::Result;
+use git2::Commit;
+use regex::Regex;
+use rustc_hash::FxHashMap as HashMap;
+
+use crate::AnalyzeOptions;
+use crate::pipeline::{self, Accumulator, WeightedCommit};
+
+/// Default `issue_pattern`: GitHub-style `#1234` and Jira-style `PROJ-567` references
+pub const DEFAULT_ISSUE_PATTERN: &str = r"#[0-9]+\b|\b[A-Z][A-Z0-9]+-[0-9]+\b";
+
+static DEFAULT_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(DEFAULT_ISSUE_PATTERN).expect("valid default issue pattern"));
+
+fn issue_pattern(options: &AnalyzeOptions) -> &Regex {
+    options.issue_pattern.as_ref().unwrap_or(&DEFAULT_PATTERN)
+}
+
+/// `issue_ref_weight` for commits whose message references an issue, otherwise 1
+pub(crate) fn issue_ref_weight(commit: &Commit, options: &AnalyzeOptions) -> f64 {
+    let Some(weight) = options.issue_ref_weight else {
+        return 1.0;
+    };
+    let message = String::from_utf8_lossy(commit.message_bytes());
+    if issue_pattern(options).is_match(&message) {
+        weight
+    } else {
+        1.0
+    }
+}
+
+/// Issues referenced by the commits that touched each path
+struct IssueRefs {
+    pattern: Regex,
+    refs: HashMap<PathBuf, BTreeSet<String>>,
+}
+
+impl Accumulator for IssueRefs {
+    const NEEDS_MESSAGE: bool = true;
+
+    fn add(&mut self, commit: &WeightedCommit) {
+        let Some(message) = commit.message else {
+            return;
+        };
+        let issues: Vec<&str> = self
+            .pattern
+            .find_iter(message)
+            .map(|found| found.as_str())
+            .collect();
+        if issues.is_empty() {
+            return;
+        }
+        for change in commit.changes {
+            let refs = self.refs.entry(change.path.to_path_buf()).or_default();
+            refs.extend(issues.iter().map(|&issue| issue.to_owned()));
+        }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        for (path, refs) in other.refs {
+            self.refs.entry(path).or_default().extend(refs);
+        }
+        self
+    }
+}
+
+/// Issue references (per `issue_pattern`) in the messages of the commits counted for each
+/// path, sorted
+pub(crate) fn issue_refs(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+) -> Result<HashMap<PathBuf, Vec<String>>> {
+    let IssueRefs { refs, .. } = pipeline::run_with(repo_path, options, || IssueRefs {
+        pattern: issue_pattern(options).clone(),
+        refs: HashMap::default(),
+    })?;
+    Ok(refs
+        .into_iter()
+        .map(|(path, refs)| (path, refs.into_iter().collect()))
+        .collect())
+}
@@ -0,0 +1,76 @@
+use std::path::Path;
+
+/// Language of a file, guessed from its extension (or the name of files like `Dockerfile`);
+/// None when unknown
+pub fn language(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+    let by_name = match name {
+        "Dockerfile" | "Containerfile" => Some("Dockerfile"),
+        "Makefile" | "GNUmakefile" | "makefile" => Some("Makefile"),
+        "CMakeLists.txt" => Some("CMake"),
+        "Jenkinsfile" | "build.gradle" | "settings.gradle" => Some("Groovy"),
+        "Gemfile" | "Rakefile" => Some("Ruby"),
+        _ => None,
+    };
+    if by_name.is_some() {
+        return by_name;
+    }
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "rs" => "Rust",
+        "ts" | "mts" | "cts" | "tsx" => "TypeScript",
+        "js" | "mjs" | "cjs" | "jsx" => "JavaScript",
+        "py" | "pyi" => "Python",
+        "go" => "Go",
+        "java" => "Java",
+        "kt" | "kts" => "Kotlin",
+        "scala" | "sc" => "Scala",
+        "groovy" | "gradle" => "Groovy",
+        "c" => "C",
+        "h" => "C/C++ Header",
+        "cc" | "cpp" | "cxx" | "c++" | "hh" | "hpp" | "hxx" => "C++",
+        "cs" => "C#",
+        "fs" | "fsi" | "fsx" => "F#",
+        "m" | "mm" => "Objective-C",
+        "swift" => "Swift",
+        "rb" => "Ruby",
+        "php" => "PHP",
+        "pl" | "pm" => "Perl",
+        "lua" => "Lua",
+        "dart" => "Dart",
+        "ex" | "exs" => "Elixir",
+        "erl" | "hrl" => "Erlang",
+        "hs" => "Haskell",
+        "ml" | "mli" => "OCaml",
+        "clj" | "cljs" | "cljc" | "edn" => "Clojure",
+        "zig" => "Zig",
+        "nix" => "Nix",
+        "r" => "R",
+        "jl" => "Julia",
+        "sql" => "SQL",
+        "sh" | "bash" | "zsh" | "fish" => "Shell",
+        "ps1" | "psm1" => "PowerShell",
+        "html" | "htm" => "HTML",
+        "css" => "CSS",
+        "scss" | "sass" => "Sass",
+        "less" => "Less",
+        "vue" => "Vue",
+        "svelte" => "Svelte",
+        "md" | "markdown" => "Markdown",
+        "rst" => "reStructuredText",
+        "adoc" => "AsciiDoc",
+        "tex" => "TeX",
+        "json" | "jsonc" => "JSON",
+        "yaml" | "yml" => "YAML",
+        "toml" => "TOML",
+        "xml" => "XML",
+        "proto" => "Protocol Buffers",
+        "graphql" | "gql" => "GraphQL",
+        "tf" | "tfvars" => "Terraform",
+        "cmake" => "CMake",
+        "mk" => "Makefile",
+        "dockerfile" => "Dockerfile",
+        "vim" => "Vim Script",
+        _ => return None,
+    })
+}
@@ -0,0 +1,89 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use rustc_hash::FxHashMap as HashMap;
+use serde::Serialize;
+
+use crate::AnalyzeOptions;
+use crate::pipeline::{self, Accumulator, WeightedCommit};
+
+/// An author's recency-weighted activity across the selected files
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuthorActivity {
+    /// `Name <email>`, after applying `.mailmap`
+    pub author: String,
+    /// Sum of decayed, size-penalized contributions, on the same scale as file scores
+    pub score: f64,
+    /// Commits touching at least one selected file
+    pub commits: u32,
+    /// Distinct files touched
+    pub files: usize,
+    /// Unix timestamp of the author's latest commit
+    pub last_commit: i64,
+}
+
+#[derive(Default)]
+struct AuthorStats {
+    score: f64,
+    commits: u32,
+    files: HashSet<PathBuf>,
+    last_commit: i64,
+}
+
+#[derive(Default)]
+struct Leaderboard(HashMap<String, AuthorStats>);
+
+impl Accumulator for Leaderboard {
+    const NEEDS_AUTHOR: bool = true;
+
+    fn add(&mut self, commit: &WeightedCommit) {
+        let Some(author) = commit.author else {
+            return;
+        };
+        if commit.changes.is_empty() {
+            return;
+        }
+        let stats = self.0.entry(author.to_owned()).or_default();
+        stats.commits += 1;
+        stats.last_commit = stats.last_commit.max(commit.time);
+        for change in commit.changes {
+            stats.score += commit.file_score(change);
+            stats.files.insert(change.path.to_path_buf());
+        }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        for (author, theirs) in other.0 {
+            let stats = self.0.entry(author).or_default();
+            stats.score += theirs.score;
+            stats.commits += theirs.commits;
+            stats.files.extend(theirs.files);
+            stats.last_commit = stats.last_commit.max(theirs.last_commit);
+        }
+        self
+    }
+}
+
+/// Rank authors by their recency-weighted changes to the files selected by `options`, most
+/// active first
+pub fn analyze_authors(repo_path: &Path, options: &AnalyzeOptions) -> Result<Vec<AuthorActivity>> {
+    let Leaderboard(map) = pipeline::run(repo_path, options)?;
+
+    let mut authors: Vec<AuthorActivity> = map
+        .into_iter()
+        .map(|(author, stats)| AuthorActivity {
+            author,
+            score: stats.score,
+            commits: stats.commits,
+            files: stats.files.len(),
+            last_commit: stats.last_commit,
+        })
+        .collect();
+    authors.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.author.cmp(&b.author))
+    });
+    Ok(authors)
+}
@@ -1,194 +1,667 @@
-use std::collections::HashSet;
-use std::{
-    fs,
-    path::{Path, PathBuf},
-    sync::Arc,
-};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use anyhow::Result;
-use bincode;
 use chrono::Utc;
-use directories::ProjectDirs;
-use git2::{DiffOptions, Oid, Repository, Sort};
-use hex;
-use rayon::prelude::*;
+use git2::Repository;
+use regex::Regex;
 use rustc_hash::FxHashMap as HashMap;
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
-use sled;
 
-/// Calculates size penalty: 1 / (1 + sqrt(size_in_kib))
-fn size_penalty(size_bytes: u64) -> f64 {
-    let kib = (size_bytes as f64) / 1024.0;
-    1.0 / (1.0 + kib.sqrt())
+use crate::pipeline::{ScoreMap, Scores};
+use crate::spill::Runs;
+
+mod access;
+mod aggregate;
+mod authors;
+mod bus_factor;
+mod cache;
+mod cancel;
+mod cherry_pick;
+mod codeowners;
+mod commit_graph;
+mod config;
+mod contributions;
+mod conventional;
+mod diff;
+mod gate;
+mod generated;
+mod history;
+mod hooks;
+mod hotspot;
+mod ignore_revs;
+mod index;
+mod intern;
+mod issues;
+mod language;
+mod leaderboard;
+mod locale;
+mod notes;
+mod owners;
+mod packages;
+mod paths;
+mod pipeline;
+mod query;
+mod ranking;
+mod reflog;
+mod related;
+mod revwalk;
+mod risk;
+mod scorer;
+mod sorted;
+mod spill;
+mod stale;
+mod statics;
+mod stats;
+mod store;
+mod stream;
+mod submodules;
+#[cfg(test)]
+#[path = "../tests/support/mod.rs"]
+mod test_support;
+mod trend;
+mod warm;
+mod workspace;
+mod worktree;
+
+pub use access::record_access;
+pub use aggregate::{
+    DirectoryScore, GroupScore, ScoreTree, directory_scores, folded_stacks, group_scores,
+    score_tree,
+};
+pub use authors::{AuthorBoost, user_email};
+pub use bus_factor::{BusFactorRisk, analyze_bus_factor};
+pub use cache::{
+    CacheBackend, CacheGcStats, CacheLocation, CacheMode, export_cache, gc_cache, import_cache,
+};
+pub use cancel::{CancellationToken, Cancelled};
+pub use codeowners::CodeOwners;
+pub use config::{CONFIG_FILE, PathWeight, ScoreAdjustment, read_config_file};
+pub use contributions::{Contribution, analyze_contributions};
+pub use diff::{ScoreDelta, diff_revs};
+pub use gate::{hot_files, new_hot_files};
+pub use history::{HistoryInterval, HistoryPoint, analyze_history};
+pub use hooks::{install_hooks, uninstall_hooks};
+pub use hotspot::{ComplexityMetric, Hotspot, analyze_hotspots};
+pub use ignore_revs::{BLAME_IGNORE_REVS_FILE, read_ignore_revs_file};
+pub use issues::DEFAULT_ISSUE_PATTERN;
+pub use language::language;
+pub use leaderboard::{AuthorActivity, analyze_authors};
+pub use locale::{NumberFormat, env_locale};
+pub use notes::write_score_notes;
+pub use owners::{Contributor, FileOwnership, analyze_owners};
+pub use packages::Packages;
+pub use paths::{path_bytes, path_from_bytes, quote_path, to_git_path, to_native_path};
+pub use query::{PathQuery, best_match};
+pub use ranking::{ScoreDisplay, elo_ratings, even_tier_boundaries, percentiles, ranks, tiers};
+pub use related::{RelatedFile, related_files};
+pub use revwalk::Topology;
+pub use risk::{DEFAULT_FIX_PATTERN, FileRisk, analyze_risk};
+pub use scorer::{CommitMeta, DefaultScorer, FileMeta, ScoreMode, Scorer, SizePenalty};
+pub use sorted::{SortOrder, SortedScores};
+pub use stale::{StaleFile, analyze_stale};
+pub use stats::{Phase, RunStats, StatsReport};
+pub use store::{CommitCache, EntryVisitor, FileStore, MemoryStore, SledStore};
+pub use stream::{PartialScores, ScoreStream, analyze_repo_iter};
+pub use trend::{TrendEntry, analyze_trend};
+pub use warm::warm_cache;
+pub use workspace::analyze_repos;
+
+/// Version of the machine-readable output (JSON fields and porcelain columns).
+///
+/// Bumped whenever a field is renamed, removed or changes meaning; adding new
+/// fields does not bump it.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Version of the default scoring algorithm; bumped whenever the same history would be
+/// scored differently
+pub const ALGORITHM_VERSION: u32 = 1;
+
+/// Score and per-file statistics for one path
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileScore {
+    #[serde(with = "paths")]
+    pub path: PathBuf,
+    pub score: f64,
+    /// Number of counted commits that touched the file
+    pub commits: u32,
+    /// Commit time (Unix seconds) of the newest counted change
+    pub last_modified: i64,
+    /// Lines added plus removed across counted commits; None unless `AnalyzeOptions::churn` is set
+    #[serde(default)]
+    pub churn: Option<u64>,
+    /// Frequency and recency components of the score; None unless `AnalyzeOptions::breakdown`
+    /// is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breakdown: Option<ScoreBreakdown>,
+    /// Issues referenced by the messages of the counted commits, e.g. `#1234`; None unless
+    /// `AnalyzeOptions::issue_refs` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issues: Option<Vec<String>>,
 }
 
-/// On-disk static data per commit: per-file penalties
-#[derive(Serialize, Deserialize)]
-struct CommitStatics {
-    contribs: Vec<(PathBuf, f64)>,
+/// The components that the frecency score blends, for building other blends downstream
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScoreBreakdown {
+    /// Number of counted commits that touched the file
+    pub frequency: u32,
+    /// Days between the newest counted change and the analysis time (`as_of` or now)
+    pub recency_days: f64,
 }
 
-/// Opens (or creates) a sled cache DB unique to this repo, in OS-appropriate cache dir
-fn open_repo_cache(repo_path: &Path) -> sled::Db {
-    let proj = ProjectDirs::from("com", "kantord", "frecenfile")
-        .expect("unable to get project directories");
-    let cache_base = proj.cache_dir();
-    fs::create_dir_all(cache_base).expect("failed to create cache directory");
+impl FileScore {
+    /// Highest score first, ties broken by path so output is reproducible
+    pub fn cmp_hottest_first(&self, other: &Self) -> Ordering {
+        other
+            .score
+            .total_cmp(&self.score)
+            .then_with(|| self.path.cmp(&other.path))
+    }
+}
 
-    let absolute_path = repo_path
-        .canonicalize()
-        .expect("failed to canonicalize repo path");
-    let mut hasher = Sha256::new();
-    hasher.update(absolute_path.to_string_lossy().as_bytes());
-    let path_hash = hex::encode(&hasher.finalize()[0..16]);
+/// Options controlling which commits and files contribute to the scores
+#[derive(Debug, Clone)]
+pub struct AnalyzeOptions {
+    /// Files to include; None = all
+    pub paths: Option<HashSet<PathBuf>>,
+    /// Revision whose history is scored (anything `git rev-parse` understands); None = HEAD
+    pub rev: Option<String>,
+    /// Also walk the commits of refs matching these globs (e.g. `refs/heads`); the walk visits each
+    /// commit once
+    pub ref_globs: Vec<String>,
+    /// Only count commits since the merge base of the scored revision and this one, e.g. `main`
+    pub since_merge_base: Option<String>,
+    /// Also score the files of initialized submodules, prefixed with the submodule path
+    pub recurse_submodules: bool,
+    /// Whether commits on merged side branches are counted
+    pub topology: Topology,
+    /// Only diff files under this directory (relative to the repository root) and report paths
+    /// relative to it; `paths` are then relative to it too
+    pub prefix: Option<PathBuf>,
+    /// Match `paths` and `prefix` case-insensitively and merge the scores of paths that only
+    /// differ in case (e.g. across a `Readme.md` → `README.md` rename) under the newest spelling
+    pub ignore_case: bool,
+    /// Multipliers for every contribution to matching files, e.g. `*.md` at 0.5; the config
+    /// file's `[weights]` are applied too
+    pub path_weights: Vec<PathWeight>,
+    /// Multipliers for commits by conventional commit type (`feat`, `fix`, `chore`, ...), e.g.
+    /// `chore` at 0.2; the config file's `[commit_types]` are applied too
+    pub commit_type_weights: BTreeMap<String, f64>,
+    /// Multiplier for commits whose message references an issue or PR, which tend to be real
+    /// work rather than housekeeping
+    pub issue_ref_weight: Option<f64>,
+    /// List the issues referenced by each file's commits in [`FileScore::issues`]
+    pub issue_refs: bool,
+    /// What counts as an issue reference; None = [`DEFAULT_ISSUE_PATTERN`]
+    pub issue_pattern: Option<Regex>,
+    /// Most that one commit can add to one file's score, so a single huge recent commit cannot
+    /// dominate the ranking; None = uncapped (or the config file's `max_commit_contribution`)
+    pub max_commit_contribution: Option<f64>,
+    /// Limit to the newest N commits; None = entire history
+    pub max_commits: Option<usize>,
+    /// Write a commit-graph with `git commit-graph write` before walking the history, unless
+    /// the repository has one
+    pub write_commit_graph: bool,
+    /// Revisions to skip during scoring (anything `git rev-parse` understands)
+    pub ignore_revs: Vec<String>,
+    /// Files listing revisions to skip, in `git blame --ignore-revs-file` format
+    pub ignore_revs_files: Vec<PathBuf>,
+    /// Also skip revisions from the repo's `.git-blame-ignore-revs` (or `blame.ignoreRevsFile`)
+    pub use_blame_ignore_revs: bool,
+    /// Only count commits whose author (`Name <email>`) matches one of these; empty = all
+    pub authors: Vec<Regex>,
+    /// Weight multipliers for commits by matching authors
+    pub author_boosts: Vec<AuthorBoost>,
+    /// Skip commits whose author (`Name <email>`) matches one of these, e.g. bots
+    pub exclude_authors: Vec<Regex>,
+    /// Skip commits whose full message matches one of these, e.g. `^chore`
+    pub exclude_messages: Vec<Regex>,
+    /// Count commits with identical patch-ids (cherry-picks) only once, at the oldest copy
+    pub dedup_cherry_picks: bool,
+    /// Compute per-file line churn (requires diffing file contents)
+    pub churn: bool,
+    /// Report the frequency and recency components of each score
+    pub breakdown: bool,
+    /// Rank by frecency, or only by recency or frequency
+    pub mode: ScoreMode,
+    /// Skip generated files: those marked `linguist-generated` or `-diff` in `.gitattributes`,
+    /// lockfiles, minified bundles, protobuf output and `dist/` directories
+    pub default_excludes: bool,
+    /// Skip binary files such as images and other assets (requires reading blob contents)
+    pub skip_binary: bool,
+    /// Skip files whose change in a commit is whitespace-only
+    pub ignore_whitespace: bool,
+    /// Skip files with fewer changed lines than this in a commit (not counting whitespace
+    /// changes with `ignore_whitespace`), e.g. formatter touch-ups
+    pub min_changed_lines: Option<u64>,
+    /// Number of worker threads; None = one per core
+    pub threads: Option<usize>,
+    /// Maximum number of repository handles open at once (one per worker thread)
+    pub max_open_repos: Option<usize>,
+    /// Commits each worker processes per wave; None = adapt to the measured cost per commit
+    pub commits_per_chunk: Option<usize>,
+    /// Whether the on-disk commit statistics cache is read and written
+    pub cache_mode: CacheMode,
+    /// Where the cache DB is stored
+    pub cache_location: CacheLocation,
+    /// Storage format of the cache at `cache_location`
+    pub cache_backend: CacheBackend,
+    /// Use this store instead of the one at `cache_location`, e.g. a [`MemoryStore`] kept
+    /// across the analyses of a long-running process
+    pub cache_store: Option<Arc<dyn CommitCache>>,
+    /// Stop writing new cache entries once the cache DB reaches this many bytes
+    pub max_cache_bytes: Option<u64>,
+    /// zstd level of new cache entries; 0 stores them uncompressed. Either kind is read back.
+    pub cache_compression_level: i32,
+    /// Abort with an error when the score table is estimated to need more memory than this
+    pub max_memory_bytes: Option<u64>,
+    /// Past `max_memory_bytes`, move the scores aggregated so far (keyed by path) to temporary
+    /// files and carry on instead of failing. The spilled scores are reduced one shard at a
+    /// time: [`analyze_repo_sorted`] keeps them on disk until they are read, while functions
+    /// returning every score at once collect them in memory, which the limit does not apply to.
+    /// Scores can differ from an unspilled walk in the last bits, as the partial sums of the
+    /// spills are added up in another grouping.
+    pub spill_to_disk: bool,
+    /// Only count commits made at or after this Unix timestamp
+    pub since: Option<i64>,
+    /// Only count commits made at or before this Unix timestamp
+    pub until: Option<i64>,
+    /// Measure commit ages relative to this Unix timestamp instead of the current time
+    pub as_of: Option<i64>,
+    /// Look up blob sizes for the size penalty; off skips blob access entirely
+    pub file_sizes: bool,
+    /// Drop every contribution of a blob larger than this many bytes (sizes are looked up for
+    /// this even without `file_sizes`)
+    pub max_file_size: Option<u64>,
+    /// Spread blob lookups of commits touching thousands of files across workers
+    pub split_large_commits: bool,
+    /// Manual score adjustments, applied in order after aggregation
+    pub score_adjustments: Vec<ScoreAdjustment>,
+    /// Also apply the `[[boost]]` adjustments from the repo's `.frecenfile.toml`
+    pub use_config_file: bool,
+    /// Blend in the editor accesses logged by [`record_access`], each counting like this many
+    /// commits; only affects the frecency of files with git history
+    pub access_weight: Option<f64>,
+    /// Count every file with uncommitted changes (modified, staged or untracked) like a commit
+    /// of this weight made right now
+    pub worktree_weight: Option<f64>,
+    /// Blend in recent checkouts, rebases and resets from the HEAD reflog: each spreads this
+    /// weight over the files it switched. The reflog is local to the machine.
+    pub reflog_weight: Option<f64>,
+    /// Recency decay and per-file weighting
+    pub scorer: Arc<dyn Scorer>,
+    /// Abort the analysis with a [`Cancelled`] error once cancelled
+    pub cancellation: CancellationToken,
+    /// Collects counters and phase timings of the analysis, e.g. for `--stats`
+    pub stats: RunStats,
+    /// On cancellation, return the scores of the commits processed so far instead of failing
+    pub partial_on_cancel: bool,
+    /// Walk the whole history to build the cached score index even for runs with a time
+    /// window, which otherwise only walk the window, so that later runs (e.g. of `--watch`)
+    /// walk only the commits that joined the history since
+    pub index_windowed_runs: bool,
+}
 
-    let db_path = cache_base.join(format!("{}.sled", path_hash));
-    sled::open(db_path).expect("failed to open sled cache")
+impl Default for AnalyzeOptions {
+    fn default() -> Self {
+        Self {
+            paths: None,
+            max_commits: None,
+            write_commit_graph: false,
+            ignore_revs: Vec::new(),
+            ignore_revs_files: Vec::new(),
+            use_blame_ignore_revs: true,
+            authors: Vec::new(),
+            author_boosts: Vec::new(),
+            exclude_authors: Vec::new(),
+            exclude_messages: Vec::new(),
+            dedup_cherry_picks: false,
+            churn: false,
+            breakdown: false,
+            mode: ScoreMode::Frecency,
+            default_excludes: true,
+            skip_binary: false,
+            ignore_whitespace: false,
+            min_changed_lines: None,
+            threads: None,
+            max_open_repos: None,
+            commits_per_chunk: None,
+            cache_mode: CacheMode::ReadWrite,
+            cache_location: CacheLocation::UserCacheDir,
+            cache_backend: CacheBackend::Sled,
+            cache_store: None,
+            max_cache_bytes: None,
+            cache_compression_level: 3,
+            max_memory_bytes: None,
+            spill_to_disk: false,
+            since: None,
+            until: None,
+            as_of: None,
+            rev: None,
+            prefix: None,
+            ignore_case: false,
+            path_weights: Vec::new(),
+            commit_type_weights: BTreeMap::new(),
+            issue_ref_weight: None,
+            issue_refs: false,
+            issue_pattern: None,
+            max_commit_contribution: None,
+            ref_globs: Vec::new(),
+            since_merge_base: None,
+            topology: Topology::FirstParent,
+            recurse_submodules: false,
+            file_sizes: true,
+            max_file_size: None,
+            split_large_commits: true,
+            score_adjustments: Vec::new(),
+            use_config_file: true,
+            access_weight: None,
+            worktree_weight: None,
+            reflog_weight: None,
+            scorer: Arc::new(DefaultScorer::default()),
+            cancellation: CancellationToken::new(),
+            stats: RunStats::new(),
+            partial_on_cancel: false,
+            index_windowed_runs: false,
+        }
+    }
 }
 
-/// Top-level: analyze repo at `repo_path`, optional filter paths, limit to max_commits newest commits
+impl AnalyzeOptions {
+    /// Whether a commit made at `time` falls inside `since`..=`until`
+    fn in_time_window(&self, time: i64) -> bool {
+        self.since.is_none_or(|since| time >= since) && self.until.is_none_or(|until| time <= until)
+    }
+
+    /// Whether any option depends on commit author identities
+    fn uses_authors(&self) -> bool {
+        self.scorer.needs_author()
+            || !self.authors.is_empty()
+            || !self.author_boosts.is_empty()
+            || !self.exclude_authors.is_empty()
+    }
+}
+
+/// Top-level: analyze repo at `repo_path`, optional filter paths, limit to max_commits newest commits.
+/// Keeps the results of earlier versions: no ignore-revs file, default excludes or config file.
 pub fn analyze_repo(
     repo_path: &Path,
     paths: Option<HashSet<PathBuf>>, // files to include; None = all
     max_commits: Option<usize>,
 ) -> Result<Vec<(PathBuf, f64)>> {
-    let repo = Repository::discover(repo_path)?;
-    let cache = Arc::new(open_repo_cache(repo_path));
-    let oids = collect_commit_ids(&repo, max_commits)?;
-    let now_secs = Utc::now().timestamp();
-    let paths_arc = paths.map(Arc::new);
-
-    let scores = compute_scores_parallel(&oids, repo_path, &paths_arc, now_secs, cache);
-    Ok(scores.into_iter().collect())
+    let options = AnalyzeOptions {
+        paths,
+        max_commits,
+        use_blame_ignore_revs: false,
+        default_excludes: false,
+        use_config_file: false,
+        ..AnalyzeOptions::default()
+    };
+    let scores = analyze_repo_with_options(repo_path, &options)?;
+    Ok(scores.into_iter().map(|f| (f.path, f.score)).collect())
 }
 
-/// Collect commit OIDs (newest first), up to max_commits
-fn collect_commit_ids(
-    repo: &Repository,
-    max_commits: Option<usize>,
-) -> Result<Vec<Oid>, git2::Error> {
-    let mut revwalk = repo.revwalk()?;
-    revwalk.push_head()?;
-    revwalk.set_sorting(Sort::TIME)?;
-    revwalk.simplify_first_parent()?;
-
-    let limit = max_commits.unwrap_or(usize::MAX);
-    let mut oids = Vec::with_capacity(limit.min(1024));
-    for oid_res in revwalk.take(limit) {
-        let oid = oid_res?;
-        oids.push(oid);
-    }
-    Ok(oids)
+/// Analyze repo at `repo_path` using the given options
+pub fn analyze_repo_with_options(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+) -> Result<Vec<FileScore>> {
+    Ok(analyze_repo_with_metadata(repo_path, options)?.scores)
 }
 
-/// Parallel scoring: chunk OIDs to workers
-fn compute_scores_parallel(
-    oids: &[Oid],
-    repo_path: &Path,
-    paths: &Option<Arc<HashSet<PathBuf>>>,
-    now_secs: i64,
-    cache: Arc<sled::Db>,
-) -> HashMap<PathBuf, f64> {
-    const COMMITS_PER_WORKER: usize = 250;
-
-    oids.par_chunks(COMMITS_PER_WORKER)
-        .map(|chunk| process_chunk(chunk, repo_path, paths, now_secs, cache.clone()))
-        .reduce(HashMap::default, |mut acc, local| {
-            for (k, v) in local {
-                *acc.entry(k).or_default() += v;
-            }
-            acc
-        })
+/// Describes the run that produced a set of scores
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AnalysisMetadata {
+    /// Work tree (or git directory of a bare repository)
+    pub repo: PathBuf,
+    /// Commit id of HEAD; None on an unborn branch
+    pub head: Option<String>,
+    /// Number of commits selected for scoring, after ignored revisions (including submodules')
+    pub commits: usize,
+    /// The history is cut off by a shallow clone, so older commits are not counted
+    pub shallow: bool,
+    /// Crate version that computed the scores
+    pub version: String,
+    pub algorithm_version: u32,
+    pub format_version: u32,
 }
 
-fn get_commit_statistics(
-    repo: &Repository,
-    oid: Oid,
-    cache: &Arc<sled::Db>,
-    size_cache: &mut HashMap<Oid, u64>,
-) -> CommitStatics {
-    let key = oid.to_string();
+/// Scores together with metadata about the run that produced them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    pub metadata: AnalysisMetadata,
+    pub scores: Vec<FileScore>,
+}
 
-    if let Ok(Some(bytes)) = cache.get(&key) {
-        return bincode::deserialize(&bytes).expect("deserialize cache bytes");
-    } else {
-        let contribs = compute_statics_for_commit(&repo, oid, size_cache).unwrap_or_default();
-        let statics = CommitStatics { contribs };
-        let serialized = bincode::serialize(&statics).expect("serialize statics");
-        cache.insert(&key, serialized).expect("insert into cache");
-        return statics;
+/// Like [`analyze_repo_with_options`], also describing the analyzed repository and history
+pub fn analyze_repo_with_metadata(repo_path: &Path, options: &AnalyzeOptions) -> Result<Analysis> {
+    let repo = open_repo(repo_path)?;
+    let mut finish = Finish::new(&repo, repo_path, options)?;
+    let (scores, commits) = index::indexed_scores(repo_path, options)?;
+    let mut scores = match scores {
+        Scores::InMemory(scores) => finish.file_scores(scores, None),
+        Scores::Spilled(spilled) => {
+            let mut all = Vec::new();
+            spilled.for_each_shard(|shard, scores| {
+                all.extend(finish.file_scores(scores, Some(shard)));
+                Ok(())
+            })?;
+            all
+        }
     };
+    let submodule_commits = finish.add_submodules(&repo, &mut scores)?;
+    Ok(Analysis {
+        metadata: metadata(&repo, options, commits + submodule_commits),
+        scores,
+    })
 }
 
-/// Worker: for each OID, load from cache or compute, then filter & weight
-fn process_chunk(
-    chunk: &[Oid],
+/// Like [`analyze_repo_with_metadata`], yielding the scores in `order` one at a time. When
+/// the walk spilled to disk (see [`AnalyzeOptions::spill_to_disk`]), the spilled stats are
+/// scored and sorted one shard at a time into temporary files, which are then merged as the
+/// scores are read, so memory stays bounded by one shard.
+pub fn analyze_repo_sorted(
     repo_path: &Path,
-    paths: &Option<Arc<HashSet<PathBuf>>>,
-    now_secs: i64,
-    cache: Arc<sled::Db>,
-) -> HashMap<PathBuf, f64> {
-    let repo = Repository::open(repo_path).expect("re-open repo inside worker");
-    let mut size_cache: HashMap<Oid, u64> = HashMap::default();
-    let mut local_scores: HashMap<PathBuf, f64> = HashMap::default();
-
-    for oid in chunk {
-        let commit = match repo.find_commit(*oid) {
-            Ok(c) if c.parent_count() <= 1 => c,
-            _ => continue,
+    options: &AnalyzeOptions,
+    order: SortOrder,
+) -> Result<(AnalysisMetadata, SortedScores)> {
+    let repo = open_repo(repo_path)?;
+    let mut finish = Finish::new(&repo, repo_path, options)?;
+    let (scores, commits) = index::indexed_scores(repo_path, options)?;
+    let (scores, submodule_commits) = match scores {
+        Scores::InMemory(scores) => {
+            let mut scores = finish.file_scores(scores, None);
+            let submodule_commits = finish.add_submodules(&repo, &mut scores)?;
+            (SortedScores::in_memory(scores, order), submodule_commits)
+        }
+        Scores::Spilled(spilled) => {
+            let mut runs = Runs::create()?;
+            spilled.for_each_shard(|shard, scores| {
+                SortedScores::write_run(&mut runs, finish.file_scores(scores, Some(shard)), order)
+            })?;
+            let mut submodule_scores = Vec::new();
+            let submodule_commits = finish.add_submodules(&repo, &mut submodule_scores)?;
+            SortedScores::write_run(&mut runs, submodule_scores, order)?;
+            (SortedScores::merged(runs, order)?, submodule_commits)
+        }
+    };
+    Ok((
+        metadata(&repo, options, commits + submodule_commits),
+        scores,
+    ))
+}
+
+/// What turns the per-file stats of a walk into scores: the signals gathered outside the
+/// walk, the score adjustments and the issue references
+struct Finish<'a> {
+    options: &'a AnalyzeOptions,
+    adjustments: Vec<ScoreAdjustment>,
+    /// Editor access and reflog weights
+    access: HashMap<PathBuf, f64>,
+    worktree_changes: Vec<PathBuf>,
+    /// Issue references by path, taken out as the files are scored
+    issue_refs: Option<HashMap<PathBuf, Vec<String>>>,
+    /// Gitlink entries, which are replaced by the submodules' own files
+    submodule_paths: HashSet<PathBuf>,
+}
+
+impl<'a> Finish<'a> {
+    fn new(repo: &Repository, repo_path: &Path, options: &'a AnalyzeOptions) -> Result<Self> {
+        let mut access = access::access_scores(repo, options)?;
+        for (path, weight) in reflog::reflog_scores(repo, options)? {
+            *access.entry(path).or_default() += weight;
+        }
+        let submodule_paths = if options.recurse_submodules {
+            repo.submodules()?
+                .iter()
+                .map(|submodule| submodule.path().to_path_buf())
+                .collect()
+        } else {
+            HashSet::new()
         };
-        let statics: CommitStatics = get_commit_statistics(&repo, *oid, &cache, &mut size_cache);
-        let age_days = ((now_secs - commit.time().seconds()) / 86_400).max(0) as f64;
-        let weight = 1.0 / (age_days + 1.0).powi(2);
+        Ok(Self {
+            options,
+            adjustments: score_adjustments(repo_path, options)?,
+            access,
+            worktree_changes: worktree::changed_files(repo, options)?,
+            issue_refs: options
+                .issue_refs
+                .then(|| issues::issue_refs(repo_path, options))
+                .transpose()?,
+            submodule_paths,
+        })
+    }
 
-        for (path, penalty) in statics.contribs.into_iter() {
-            if paths.as_ref().map_or(true, |set| set.contains(&path)) {
-                *local_scores.entry(path).or_default() += penalty * weight;
+    /// Scores of the files in `scores`: every file of the walk, or those of spilled shard
+    /// `shard`
+    fn file_scores(&mut self, scores: ScoreMap, shard: Option<usize>) -> Vec<FileScore> {
+        let options = self.options;
+        let mut scores = if options.ignore_case {
+            pipeline::merge_case_variants(scores)
+        } else {
+            scores
+        };
+        access::blend(&mut scores, &self.access);
+        match shard {
+            None => worktree::blend(&mut scores, &self.worktree_changes, options),
+            Some(shard) => {
+                let changed: Vec<PathBuf> = self
+                    .worktree_changes
+                    .iter()
+                    .filter(|path| pipeline::shard_of_path(path) == shard)
+                    .cloned()
+                    .collect();
+                worktree::blend(&mut scores, &changed, options);
             }
         }
+
+        scores
+            .iter()
+            .filter(|(path, _)| !self.submodule_paths.contains(&**path))
+            .map(|(path, stats)| {
+                let mut file = file_score(path.to_path_buf(), stats, options, &self.adjustments);
+                if let Some(refs) = &mut self.issue_refs {
+                    file.issues = Some(refs.remove(&file.path).unwrap_or_default());
+                }
+                file
+            })
+            .collect()
     }
 
-    local_scores
+    /// Add the scores of the submodules' files to `scores`; returns the number of commits
+    /// scored across them
+    fn add_submodules(&self, repo: &Repository, scores: &mut Vec<FileScore>) -> Result<usize> {
+        if !self.options.recurse_submodules {
+            return Ok(0);
+        }
+        let (submodule_scores, commits) =
+            submodules::submodule_scores(repo, self.options, &self.adjustments)?;
+        scores.extend(submodule_scores);
+        Ok(commits)
+    }
 }
 
-/// Compute the static penalties for all files in a given commit
-fn compute_statics_for_commit(
-    repo: &Repository,
-    oid: Oid,
-    size_cache: &mut HashMap<Oid, u64>,
-) -> Result<Vec<(PathBuf, f64)>, git2::Error> {
-    let mut out = Vec::new();
-    let commit = repo.find_commit(oid)?;
-    if commit.parent_count() > 1 {
-        return Ok(out);
+fn metadata(repo: &Repository, options: &AnalyzeOptions, commits: usize) -> AnalysisMetadata {
+    AnalysisMetadata {
+        repo: repo.workdir().unwrap_or(repo.path()).to_path_buf(),
+        head: revwalk::start_commit(repo, options.rev.as_deref())
+            .ok()
+            .map(|oid| oid.to_string()),
+        commits,
+        shallow: repo.is_shallow(),
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        algorithm_version: ALGORITHM_VERSION,
+        format_version: FORMAT_VERSION,
     }
-    let tree = commit.tree()?;
-
-    let mut diff_opts = DiffOptions::new();
-    diff_opts.context_lines(0);
-    diff_opts.interhunk_lines(0);
-    diff_opts.skip_binary_check(true);
-    diff_opts.include_typechange(false);
-
-    let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
-    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut diff_opts))?;
-
-    for delta in diff.deltas() {
-        if let Some(path) = delta.new_file().path() {
-            let blob_oid = delta.new_file().id();
-            if blob_oid.is_zero() {
-                continue;
-            }
-            let size_bytes = *size_cache.entry(blob_oid).or_insert_with(|| {
-                repo.find_blob(blob_oid)
-                    .map(|b| b.size() as u64)
-                    .unwrap_or(0)
-            });
-            let penalty = size_penalty(size_bytes);
-            out.push((path.to_path_buf(), penalty));
-        }
+}
+
+/// Open the repository containing `repo_path`; for the default `.`, `GIT_DIR` and
+/// `GIT_WORK_TREE` are honored when set (as inside git hooks)
+pub(crate) fn open_repo(repo_path: &Path) -> Result<Repository, git2::Error> {
+    if repo_path == Path::new(".") && env::var_os("GIT_DIR").is_some() {
+        Repository::open_from_env()
+    } else {
+        Repository::discover(repo_path)
+    }
+}
+
+/// Contents of the file at `path` in HEAD's tree, for bare repositories, which have no work
+/// tree to read it from
+pub(crate) fn read_bare_head_file(repo: &Repository, path: &str) -> Option<String> {
+    if !repo.is_bare() {
+        return None;
     }
+    let entry = repo
+        .head()
+        .ok()?
+        .peel_to_tree()
+        .ok()?
+        .get_path(Path::new(path))
+        .ok()?;
+    let blob = entry.to_object(repo).ok()?.peel_to_blob().ok()?;
+    String::from_utf8(blob.content().to_vec()).ok()
+}
 
-    Ok(out)
+/// Adjustments from the repo's config file (if enabled), followed by the explicit ones
+pub(crate) fn score_adjustments(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+) -> Result<Vec<ScoreAdjustment>> {
+    let mut adjustments = Vec::new();
+    if options.use_config_file {
+        let repo = open_repo(repo_path)?;
+        adjustments = config::repo_config(&repo, options)?.adjustments;
+    }
+    adjustments.extend(options.score_adjustments.iter().cloned());
+    Ok(adjustments)
+}
+
+pub(crate) fn file_score(
+    path: PathBuf,
+    stats: &pipeline::FileStats,
+    options: &AnalyzeOptions,
+    adjustments: &[ScoreAdjustment],
+) -> FileScore {
+    let now = options.as_of.unwrap_or_else(|| Utc::now().timestamp());
+    let secs_since_change = (now - stats.last_modified).max(0);
+    let score = match options.mode {
+        ScoreMode::Frecency => stats.score,
+        ScoreMode::Recency => 1.0 / ((secs_since_change / 86_400) as f64 + 1.0),
+        ScoreMode::Frequency => f64::from(stats.commits),
+    };
+    FileScore {
+        score: config::adjust_score(adjustments, &path, score),
+        path,
+        commits: stats.commits,
+        last_modified: stats.last_modified,
+        churn: options.churn.then_some(stats.churn),
+        breakdown: options.breakdown.then(|| ScoreBreakdown {
+            frequency: stats.commits,
+            recency_days: secs_since_change as f64 / 86_400.0,
+        }),
+        issues: None,
+    }
 }
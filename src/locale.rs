@@ -0,0 +1,122 @@
+/// Locale-specific separators for human-readable numbers. Machine-readable output
+/// never goes through this and always uses `.` without grouping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub decimal: char,
+    pub group: Option<char>,
+}
+
+impl Default for NumberFormat {
+    /// The locale-independent "C" format: `1234.5678`
+    fn default() -> Self {
+        Self {
+            decimal: '.',
+            group: None,
+        }
+    }
+}
+
+impl NumberFormat {
+    /// Separators for a locale name such as `de`, `fr_FR.UTF-8` or `en-US`
+    pub fn for_locale(locale: &str) -> Self {
+        let name = locale.split(['.', '@']).next().unwrap_or("");
+        let mut parts = name.split(['_', '-']);
+        let language = parts.next().unwrap_or("").to_ascii_lowercase();
+        let region = parts.next().unwrap_or("").to_ascii_uppercase();
+
+        let (decimal, group) = match (language.as_str(), region.as_str()) {
+            ("" | "c" | "posix", _) => return Self::default(),
+            ("de" | "it" | "fr", "CH") => ('.', '\''),
+            ("es", "MX" | "US") | ("pt", "") => ('.', ','),
+            (
+                "de" | "nl" | "it" | "es" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "hr" | "sl",
+                _,
+            ) => (',', '.'),
+            (
+                "fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "nn" | "no" | "uk" | "hu"
+                | "bg" | "lt" | "lv" | "et",
+                _,
+            ) => (',', '\u{a0}'),
+            _ => ('.', ','),
+        };
+        Self {
+            decimal,
+            group: Some(group),
+        }
+    }
+
+    /// Format `value` with `precision` fractional digits using this locale's separators
+    pub fn format(&self, value: f64, precision: usize) -> String {
+        let plain = format!("{:.*}", precision, value.abs());
+        let (int_part, frac_part) = match plain.split_once('.') {
+            Some((int_part, frac_part)) => (int_part, Some(frac_part)),
+            None => (plain.as_str(), None),
+        };
+
+        let mut out = String::with_capacity(plain.len() + int_part.len() / 3 + 1);
+        if value.is_sign_negative() && value != 0.0 {
+            out.push('-');
+        }
+        for (idx, digit) in int_part.chars().enumerate() {
+            let remaining = int_part.len() - idx;
+            if let Some(group) = self.group
+                && idx > 0
+                && remaining % 3 == 0
+            {
+                out.push(group);
+            }
+            out.push(digit);
+        }
+        if let Some(frac_part) = frac_part {
+            out.push(self.decimal);
+            out.push_str(frac_part);
+        }
+        out
+    }
+}
+
+/// Locale from the environment, following POSIX precedence for numeric formatting
+pub fn env_locale() -> Option<String> {
+    ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separators_follow_the_locale() {
+        let value = -1234567.891;
+        assert_eq!(
+            NumberFormat::for_locale("C").format(value, 2),
+            "-1234567.89"
+        );
+        assert_eq!(
+            NumberFormat::for_locale("en_US.UTF-8").format(value, 2),
+            "-1,234,567.89"
+        );
+        assert_eq!(
+            NumberFormat::for_locale("de-DE").format(value, 2),
+            "-1.234.567,89"
+        );
+        assert_eq!(
+            NumberFormat::for_locale("de_CH").format(value, 2),
+            "-1'234'567.89"
+        );
+        assert_eq!(
+            NumberFormat::for_locale("fr_FR@euro").format(value, 2),
+            "-1\u{a0}234\u{a0}567,89"
+        );
+    }
+
+    #[test]
+    fn small_and_whole_numbers() {
+        let format = NumberFormat::for_locale("de");
+        assert_eq!(format.format(999.0, 0), "999");
+        assert_eq!(format.format(1000.0, 0), "1.000");
+        assert_eq!(format.format(1234.5, 1), "1.234,5");
+    }
+}
@@ -1,95 +1,1215 @@
-use clap::Parser;
-use frecenfile::analyze_repo;
-use std::path::PathBuf;
+use anyhow::bail;
+use frecenfile::{
+    Analysis, AnalysisMetadata, AnalyzeOptions, CancellationToken, CodeOwners, ComplexityMetric,
+    Contribution, FORMAT_VERSION, FileScore, GroupScore, NumberFormat, Packages, PathQuery, Phase,
+    RunStats, ScoreDelta, ScoreDisplay, SortOrder, SortedScores, StatsReport, TrendEntry,
+    analyze_authors, analyze_bus_factor, analyze_contributions, analyze_history, analyze_hotspots,
+    analyze_owners, analyze_repo_sorted, analyze_repo_with_metadata, analyze_repo_with_options,
+    analyze_repos, analyze_risk, analyze_stale, analyze_trend, best_match, diff_revs,
+    directory_scores, env_locale, even_tier_boundaries, export_cache, folded_stacks, gc_cache,
+    group_scores, import_cache, install_hooks, language, new_hot_files, path_bytes, percentiles,
+    ranks, record_access, score_tree, tiers, to_native_path, uninstall_hooks, warm_cache,
+    write_score_notes,
+};
+use serde::Serialize;
+use std::env;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, BufReader, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::Duration;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
 
-#[derive(Parser, Debug)]
-#[command(
-    name = "frecenfile",
-    version,
-    about = "Compute frecency scores for files in a Git repository"
-)]
-struct Args {
-    /// Path to the Git repository (defaults to current directory)
-    #[arg(short = 'D', long = "repo", value_name = "REPO", default_value = ".")]
-    repo: PathBuf,
-
-    /// Relative paths to include; omit to include all files.
-    #[arg(short, long = "paths", value_name = "PATH", num_args = 1..)]
-    paths: Vec<PathBuf>,
-
-    /// Maximum number of commits to inspect (newest first). \
-    /// Use 0 for “no limit”.
-    #[arg(
-        short = 'n',
-        long = "max-commits",
-        value_name = "N",
-        default_value_t = 3000
-    )]
-    max_commits: usize,
-
-    /// Sort ascending (lowest score first)
-    #[arg(
-        short = 'a',
-        long = "ascending",
-        help = "Sort ascending (lowest score first)"
-    )]
-    ascending: bool,
-
-    /// Sort descending (highest score first)
-    #[arg(
-        short = 'd',
-        long = "descending",
-        help = "Sort descending (highest score first)"
-    )]
-    descending: bool,
-
-    /// Print only file paths, without scores
-    #[arg(
-        short = 'P',
-        long = "path-only",
-        help = "Print only paths, omit scores"
-    )]
-    path_only: bool,
-}
+mod cli;
+mod color;
+mod dates;
+mod mcp;
+#[cfg(feature = "parquet")]
+mod parquet;
+mod report;
+mod serve;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod template;
+#[cfg(test)]
+#[path = "../tests/support/mod.rs"]
+mod test_support;
+#[cfg(feature = "tui")]
+mod tui;
+mod watch;
+
+use cli::{
+    AuthorsArgs, BusFactorArgs, CacheAction, CacheGcArgs, CacheTransferArgs, Cli, Column, Command,
+    DiffArgs, ExportArgs, GateArgs, GroupBy, HistoryArgs, HistoryFormat, HookAction,
+    HookInstallArgs, HotspotArgs, LogArgs, LogFormat, OutputArgs, OutputFormat, OwnersArgs,
+    QueryArgs, RecordAccessArgs, ReportArgs, ReportFormat, RiskArgs, ServeArgs, StaleArgs,
+    TrendArgs, TuiArgs, WarmArgs,
+};
+use serve::Server;
+use template::Row;
 
 fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
+    let args = Cli::parse_invoked();
+    init_logging(&args.log);
 
-    if args.ascending && args.descending {
-        eprintln!("Error: --ascending and --descending cannot be used together");
-        process::exit(1);
+    if args.version {
+        return print_version(args.output.format);
     }
 
-    let filter = if args.paths.is_empty() {
+    match args.command {
+        Some(Command::Gate(gate)) => run_gate(&gate),
+        Some(Command::Trend(trend)) => run_trend(&trend),
+        Some(Command::History(history)) => run_history(&history),
+        Some(Command::Diff(diff)) => run_diff(&diff),
+        Some(Command::Hotspot(hotspot)) => run_hotspot(&hotspot),
+        Some(Command::Stale(stale)) => run_stale(&stale),
+        Some(Command::Risk(risk)) => run_risk(&risk),
+        Some(Command::Owners(owners)) => run_owners(&owners),
+        Some(Command::Authors(authors)) => run_authors(&authors),
+        Some(Command::BusFactor(bus_factor)) => run_bus_factor(&bus_factor),
+        Some(Command::Export(export)) => run_export(&export),
+        Some(Command::Report(report)) => run_report(&report),
+        Some(Command::Serve(serve)) => run_serve(&serve),
+        Some(Command::Tui(tui)) => run_tui(&tui),
+        Some(Command::RecordAccess(access)) => run_record_access(&access),
+        Some(Command::Query(query)) => run_query(&query),
+        Some(Command::Cache(cache)) => match cache.action {
+            CacheAction::Gc(gc) => run_cache_gc(&gc),
+            CacheAction::Export(export) => run_cache_export(&export),
+            CacheAction::Import(import) => run_cache_import(&import),
+        },
+        Some(Command::Warm(warm)) => run_warm(&warm),
+        Some(Command::Hook(hook)) => match hook.action {
+            HookAction::Install(install) => run_hook_install(&install),
+            HookAction::Uninstall(uninstall) => {
+                report_hooks(uninstall_hooks(&uninstall.repo)?, "Removed frecenfile from")
+            }
+        },
+        None if args.watch => {
+            let mut options = args.analysis.to_options()?;
+            // Every update after the first one then walks only the new commits
+            options.index_windowed_runs = true;
+            let clear = args.output.format == OutputFormat::Human && io::stdout().is_terminal();
+            watch::watch(
+                &args.analysis.repos,
+                options.rev.as_deref(),
+                Duration::from_secs(args.watch_interval),
+                || {
+                    if clear {
+                        print!("\x1b[2J\x1b[H");
+                    }
+                    run_analysis(&args, &options)
+                },
+            )
+        }
+        None => {
+            let mut options = args.analysis.to_options()?;
+            options.partial_on_cancel = true;
+            install_interrupt_handler(&options.cancellation)?;
+            let result = run_analysis(&args, &options);
+            if args.output.stats {
+                print_stats(&options.stats.report());
+            }
+            result
+        }
+    }
+}
+
+/// Analyze the repositories and print or export the scores
+fn run_analysis(args: &Cli, options: &AnalyzeOptions) -> anyhow::Result<()> {
+    // Spilled scores stay on disk until they are printed
+    if let ([repo], true) = (args.analysis.repos.as_slice(), options.spill_to_disk)
+        && streams(&args.output)
+    {
+        let (metadata, scores) = analyze_repo_sorted(repo, options, sort_order(&args.output))?;
+        warn_about_coverage(&metadata, options);
+        return print_sorted(scores, &metadata, &args.output);
+    }
+    let analysis = match args.analysis.repos.as_slice() {
+        [repo] => analyze_repo_with_metadata(repo, options)?,
+        repos => analyze_repos(repos, options)?,
+    };
+    warn_about_coverage(&analysis.metadata, options);
+    let exporting = args.output.export_sqlite.is_some() || args.output.export_parquet.is_some();
+    let contributions = if exporting && args.output.export_contributions {
+        Some(analyze_contributions(args.analysis.repo()?, options)?)
+    } else {
         None
+    };
+    if let Some(path) = &args.output.export_sqlite {
+        export_sqlite(
+            path,
+            &analysis,
+            contributions.as_deref().unwrap_or_default(),
+        )?;
+    }
+    if let Some(dir) = &args.output.export_parquet {
+        export_parquet(dir, &analysis, contributions.as_deref())?;
+    }
+    let mut scores = analysis.scores;
+    if args.output.owners.is_empty() && args.output.group_by.is_none() {
+        return print_results(scores, &analysis.metadata, &args.output, &options.stats);
+    }
+
+    let repo = args.analysis.repo()?;
+    // CODEOWNERS patterns and package roots are relative to the repository root, scored paths
+    // to the prefix
+    let root_path = |file: &FileScore| match &options.prefix {
+        Some(prefix) => prefix.join(&file.path),
+        None => file.path.clone(),
+    };
+    let codeowners = if !args.output.owners.is_empty()
+        || args.output.group_by == Some(GroupBy::Owner)
+    {
+        CodeOwners::for_repo(repo)?.ok_or_else(|| anyhow::anyhow!("no CODEOWNERS file found"))?
     } else {
-        Some(args.paths.into_iter().collect())
+        CodeOwners::default()
+    };
+    if !args.output.owners.is_empty() {
+        scores.retain(|file| {
+            codeowners.owners(&root_path(file)).iter().any(|owner| {
+                args.output
+                    .owners
+                    .iter()
+                    .any(|wanted| wanted.eq_ignore_ascii_case(owner))
+            })
+        });
+    }
+    let groups = match args.output.group_by {
+        Some(GroupBy::Owner) => {
+            group_scores(&scores, |file| match codeowners.owners(&root_path(file)) {
+                [] => vec!["(unowned)".to_string()],
+                owners => owners.to_vec(),
+            })
+        }
+        Some(GroupBy::Package) => {
+            let packages = Packages::for_repo(repo)?;
+            group_scores(&scores, |file| {
+                let group = match packages.package(&root_path(file)) {
+                    None => "(no package)".to_string(),
+                    Some(root) if root.as_os_str().is_empty() => ".".to_string(),
+                    Some(root) => root.to_string_lossy().into_owned(),
+                };
+                vec![group]
+            })
+        }
+        Some(GroupBy::Language) => group_scores(&scores, |file| {
+            vec![language(&file.path).unwrap_or("(other)").to_string()]
+        }),
+        None => {
+            return print_results(scores, &analysis.metadata, &args.output, &options.stats);
+        }
     };
+    print_groups(&groups, &args.output)
+}
 
-    // When max_commits == 0 we process the entire commit history
-    let max_commits_opt = if args.max_commits == 0 {
-        None
+/// Warn when the scores do not cover the whole history
+fn warn_about_coverage(metadata: &AnalysisMetadata, options: &AnalyzeOptions) {
+    if options.cancellation.is_cancelled() {
+        eprintln!("Warning: interrupted; scores only cover the commits processed so far");
+    }
+    if metadata.shallow {
+        eprintln!(
+            "Warning: shallow clone; commits before the shallow boundary are not counted \
+             (run `git fetch --unshallow` for full history)"
+        );
+    }
+}
+
+/// Print the `--stats` report to stderr, so it never mixes with the results
+fn print_stats(report: &StatsReport) {
+    eprintln!("commits walked  {}", report.commits_walked);
+    eprintln!(
+        "cache           {} hits, {} misses",
+        report.cache_hits, report.cache_misses
+    );
+    if report.index_hits > 0 {
+        eprintln!("score index     reused; only commits it lacked were walked");
+    }
+    eprintln!("blobs loaded    {}", report.blobs_loaded);
+    for (phase, secs) in [
+        ("revwalk", report.revwalk_secs),
+        ("diff", report.diff_secs),
+        ("reduce", report.reduce_secs),
+        ("sort", report.sort_secs),
+    ] {
+        eprintln!("{phase:<16}{secs:.3}s");
+    }
+    if let Some(bytes) = report.peak_memory_bytes {
+        eprintln!(
+            "peak memory     {:.1} MiB",
+            bytes as f64 / (1024.0 * 1024.0)
+        );
+    }
+}
+
+/// Print group totals, hottest first (or coldest first with --ascending)
+fn print_groups(groups: &[GroupScore], output: &OutputArgs) -> anyhow::Result<()> {
+    let groups: Vec<&GroupScore> = if output.ascending {
+        groups.iter().rev().collect()
+    } else {
+        groups.iter().collect()
+    };
+    if output.format != OutputFormat::Human {
+        return print_json(&groups, output.format);
+    }
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    for group in groups {
+        writeln!(
+            stdout,
+            "{:<10.4}  {:>5} files  {}",
+            group.score, group.files, group.group
+        )?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Send the library's `tracing` events to stderr: warnings only by default, more with each -v
+fn init_logging(log: &LogArgs) {
+    let level = match log.verbose {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    // Span timings are what -v is mostly for
+    let span_events = if log.verbose > 0 {
+        FmtSpan::CLOSE
     } else {
-        Some(args.max_commits)
+        FmtSpan::NONE
     };
+    let builder = tracing_subscriber::fmt()
+        .with_writer(io::stderr)
+        .with_max_level(level)
+        .with_span_events(span_events);
+    match log.log_format {
+        LogFormat::Json => builder.json().init(),
+        // Plain warnings, like the CLI's own
+        LogFormat::Human if log.verbose == 0 => builder.without_time().with_target(false).init(),
+        LogFormat::Human => builder.init(),
+    }
+}
+
+/// Ctrl-C stops the analysis early so the partial scores can still be printed; a second
+/// Ctrl-C quits right away
+fn install_interrupt_handler(cancellation: &CancellationToken) -> anyhow::Result<()> {
+    let cancellation = cancellation.clone();
+    ctrlc::set_handler(move || {
+        if cancellation.is_cancelled() {
+            process::exit(130);
+        }
+        cancellation.cancel();
+    })?;
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+fn export_sqlite(
+    path: &Path,
+    analysis: &Analysis,
+    contributions: &[Contribution],
+) -> anyhow::Result<()> {
+    sqlite::export(path, &analysis.metadata, &analysis.scores, contributions)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn export_sqlite(_: &Path, _: &Analysis, _: &[Contribution]) -> anyhow::Result<()> {
+    bail!("this build of frecenfile has no SQLite support; rebuild with the `sqlite` feature")
+}
+
+#[cfg(feature = "parquet")]
+fn export_parquet(
+    dir: &Path,
+    analysis: &Analysis,
+    contributions: Option<&[Contribution]>,
+) -> anyhow::Result<()> {
+    parquet::export(dir, &analysis.scores, contributions)
+}
+
+#[cfg(not(feature = "parquet"))]
+fn export_parquet(_: &Path, _: &Analysis, _: Option<&[Contribution]>) -> anyhow::Result<()> {
+    bail!("this build of frecenfile has no Parquet support; rebuild with the `parquet` feature")
+}
+
+fn run_serve(args: &ServeArgs) -> anyhow::Result<()> {
+    if !args.stdio && !args.mcp && args.http.is_none() {
+        bail!("choose how to serve requests: --stdio, --mcp or --http ADDR");
+    }
+    let mut server = Server::new(args.analysis.repo()?, args.analysis.to_options()?)?;
+    match &args.http {
+        Some(addr) => server.serve_http(addr),
+        None => server.serve_stdio(args.mcp),
+    }
+}
 
-    let mut results = analyze_repo(&args.repo, filter, max_commits_opt)?;
+fn run_record_access(args: &RecordAccessArgs) -> anyhow::Result<()> {
+    let options = args.analysis.to_options()?;
+    let repo = match args.analysis.repo()? {
+        // Editors run this from anywhere, so default to the file's own repository
+        repo if repo == Path::new(".") => args
+            .path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(repo),
+        repo => repo,
+    };
+    record_access(repo, &args.path, &options.cache_location)
+}
 
+/// Print the absolute path of the best match, so it can be opened from any directory; exit
+/// with status 1 when nothing matches
+fn run_query(args: &QueryArgs) -> anyhow::Result<()> {
+    let query = PathQuery::new(&args.pattern)?;
+    let options = args.analysis.to_options()?;
+    let analysis = analyze_repo_with_metadata(args.analysis.repo()?, &options)?;
+    let Some(file) = best_match(&analysis.scores, &query) else {
+        process::exit(1);
+    };
+    let path = match &options.prefix {
+        Some(prefix) => analysis.metadata.repo.join(prefix).join(&file.path),
+        None => analysis.metadata.repo.join(&file.path),
+    };
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(path_bytes(&to_native_path(&path)))?;
+    writeln!(stdout)?;
+    Ok(())
+}
+
+fn run_tui(args: &TuiArgs) -> anyhow::Result<()> {
+    let options = args.analysis.to_options()?;
+    let analysis = analyze_repo_with_metadata(args.analysis.repo()?, &options)?;
+    let mut files = analysis.scores;
+    files.sort_by(FileScore::cmp_hottest_first);
+    // Paths are relative to the prefix
+    let root = match &options.prefix {
+        Some(prefix) => analysis.metadata.repo.join(prefix),
+        None => analysis.metadata.repo,
+    };
+
+    // Absolute, as `query` prints it, so the pick can be opened from any directory
+    if let Some(path) = browse(&files, &root)? {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(path_bytes(&to_native_path(&root.join(path))))?;
+        writeln!(stdout)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "tui")]
+fn browse(files: &[FileScore], root: &Path) -> anyhow::Result<Option<PathBuf>> {
+    tui::run(files, root)
+}
+
+#[cfg(not(feature = "tui"))]
+fn browse(_: &[FileScore], _: &Path) -> anyhow::Result<Option<PathBuf>> {
+    bail!("this build of frecenfile has no TUI; rebuild with the `tui` feature")
+}
+
+/// A record of `--format jsonl` output, tagged with its `kind`
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum JsonLine<'a> {
+    Metadata(&'a AnalysisMetadata),
+    File(&'a FileScore),
+}
+
+/// Print `items` as a pretty JSON array, or for `jsonl` as one compact object per line
+fn print_json<T: Serialize>(items: &[T], format: OutputFormat) -> anyhow::Result<()> {
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    match format {
+        OutputFormat::Jsonl => {
+            for item in items {
+                serde_json::to_writer(&mut stdout, item)?;
+                writeln!(stdout)?;
+            }
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut stdout, items)?;
+            writeln!(stdout)?;
+        }
+        OutputFormat::Human
+        | OutputFormat::Tree
+        | OutputFormat::Folded
+        | OutputFormat::Quickfix => {
+            bail!("--format {format:?} is only supported when scoring files")
+        }
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn print_version(format: OutputFormat) -> anyhow::Result<()> {
+    let name = env!("CARGO_PKG_NAME");
+    let version = env!("CARGO_PKG_VERSION");
+    if matches!(format, OutputFormat::Json | OutputFormat::Jsonl) {
+        let info = serde_json::json!({
+            "name": name,
+            "version": version,
+            "format_version": FORMAT_VERSION,
+        });
+        println!("{info}");
+    } else {
+        println!("{name} {version}");
+    }
+    Ok(())
+}
+
+fn print_results(
+    mut results: Vec<FileScore>,
+    metadata: &AnalysisMetadata,
+    output: &OutputArgs,
+    stats: &RunStats,
+) -> anyhow::Result<()> {
     // Default sort: descending, unless --ascending passed.
-    if args.ascending {
-        results.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    let order = sort_order(output);
+    stats.time(Phase::Sort, || results.sort_by(|a, b| order.compare(a, b)));
+    if output.native_separators {
+        for file in &mut results {
+            file.path = to_native_path(&file.path);
+        }
+    }
+
+    if output.format == OutputFormat::Jsonl {
+        let mut stdout = io::BufWriter::new(io::stdout().lock());
+        serde_json::to_writer(&mut stdout, &JsonLine::Metadata(metadata))?;
+        writeln!(stdout)?;
+        for file in &results {
+            serde_json::to_writer(&mut stdout, &JsonLine::File(file))?;
+            writeln!(stdout)?;
+        }
+        stdout.flush()?;
+        return Ok(());
+    }
+    if output.format == OutputFormat::Json {
+        return print_json(&results, output.format);
+    }
+    if output.format == OutputFormat::Tree {
+        let root = metadata.repo.file_name().unwrap_or(OsStr::new("."));
+        let mut stdout = io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &score_tree(&results, root))?;
+        writeln!(stdout)?;
+        return Ok(());
+    }
+    if output.format == OutputFormat::Folded {
+        io::stdout().lock().write_all(&folded_stacks(&results))?;
+        return Ok(());
+    }
+
+    let display = ScoreDisplay::from(output.score_display);
+    let scores: Vec<f64> = results.iter().map(|f| f.score).collect();
+    let values = display.values(&scores);
+    let (ranks, percentiles) = if output.columns.is_empty() {
+        (Vec::new(), Vec::new())
+    } else {
+        (ranks(&scores), percentiles(&scores))
+    };
+    let tier_boundaries = match output.tier_boundaries()? {
+        Some(boundaries) => Some(boundaries),
+        None if output.columns.contains(&Column::Tier) => Some(even_tier_boundaries(3)),
+        None => None,
+    };
+    let tier_labels: Vec<String> = match &tier_boundaries {
+        Some(boundaries) => {
+            let count = boundaries.len() + 1;
+            tiers(&scores, boundaries)
+                .into_iter()
+                .map(|tier| tier_label(tier, count))
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    let rows = Rows {
+        output,
+        number_format: number_format(output),
+        precision: display.precision(),
+        color: color::enabled(output.color),
+        max_score: scores.iter().copied().fold(0.0, f64::max),
+        ranks,
+        percentiles,
+        tier_labels,
+    };
+
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    for (index, (file, value)) in results.iter().zip(values).enumerate() {
+        rows.write(&mut stdout, index, file, value)?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Whether the output can be printed as the sorted scores come in, without holding them all:
+/// plain scores, with nothing computed over every file
+fn streams(output: &OutputArgs) -> bool {
+    output.export_sqlite.is_none()
+        && output.export_parquet.is_none()
+        && output.owners.is_empty()
+        && output.group_by.is_none()
+        && matches!(
+            output.format,
+            OutputFormat::Human | OutputFormat::Jsonl | OutputFormat::Quickfix
+        )
+        && output.columns.is_empty()
+        && output.tiers.is_none()
+        && output.tier_boundaries.is_empty()
+        && ScoreDisplay::from(output.score_display) == ScoreDisplay::Raw
+        // The color gradient is relative to the top score, which only comes first hottest-first
+        && (!color::enabled(output.color) || !output.ascending)
+}
+
+/// Print scores as [`print_results`] does, one at a time as they are read; only for outputs
+/// that [`streams`] accepts
+fn print_sorted(
+    scores: SortedScores,
+    metadata: &AnalysisMetadata,
+    output: &OutputArgs,
+) -> anyhow::Result<()> {
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    if output.format == OutputFormat::Jsonl {
+        serde_json::to_writer(&mut stdout, &JsonLine::Metadata(metadata))?;
+        writeln!(stdout)?;
+    }
+    let mut rows = Rows {
+        output,
+        number_format: number_format(output),
+        precision: ScoreDisplay::Raw.precision(),
+        color: color::enabled(output.color),
+        max_score: 0.0,
+        ranks: Vec::new(),
+        percentiles: Vec::new(),
+        tier_labels: Vec::new(),
+    };
+    for (index, file) in scores.enumerate() {
+        let mut file = file?;
+        if output.native_separators {
+            file.path = to_native_path(&file.path);
+        }
+        if output.format == OutputFormat::Jsonl {
+            serde_json::to_writer(&mut stdout, &JsonLine::File(&file))?;
+            writeln!(stdout)?;
+            continue;
+        }
+        if index == 0 {
+            rows.max_score = file.score;
+        }
+        rows.write(&mut stdout, index, &file, file.score)?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn sort_order(output: &OutputArgs) -> SortOrder {
+    if output.ascending && output.descending {
+        eprintln!("Error: --ascending and --descending cannot be used together");
+        process::exit(1);
+    }
+    if output.ascending {
+        SortOrder::ColdestFirst
     } else {
-        results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        SortOrder::HottestFirst
+    }
+}
+
+fn number_format(output: &OutputArgs) -> NumberFormat {
+    match output.locale.as_deref() {
+        None => NumberFormat::default(),
+        Some("auto") => env_locale()
+            .map(|locale| NumberFormat::for_locale(&locale))
+            .unwrap_or_default(),
+        Some(locale) => NumberFormat::for_locale(locale),
     }
+}
+
+/// How the lines of the line-based formats are written; the per-file columns are indexed by
+/// position in the output
+struct Rows<'a> {
+    output: &'a OutputArgs,
+    number_format: NumberFormat,
+    precision: usize,
+    color: bool,
+    max_score: f64,
+    ranks: Vec<usize>,
+    percentiles: Vec<f64>,
+    tier_labels: Vec<String>,
+}
 
-    for (path, score) in results {
-        if args.path_only {
-            println!("{}", path.display());
+impl Rows<'_> {
+    fn write(
+        &self,
+        stdout: &mut impl Write,
+        index: usize,
+        file: &FileScore,
+        value: f64,
+    ) -> anyhow::Result<()> {
+        let output = self.output;
+        let number_format = &self.number_format;
+        if output.format == OutputFormat::Quickfix {
+            stdout.write_all(path_bytes(&file.path))?;
+            writeln!(
+                stdout,
+                ":1:1: score {} ({} commits)",
+                number_format.format(value, self.precision),
+                file.commits
+            )?;
+        } else if let Some(template) = &output.format_string {
+            stdout.write_all(&template.render(&Row {
+                file,
+                rank: index + 1,
+                value,
+                precision: self.precision,
+                number_format,
+            }))?;
+            writeln!(stdout)?;
+        } else if output.print0 {
+            stdout.write_all(path_bytes(&file.path))?;
+            stdout.write_all(b"\0")?;
+        } else if output.path_only {
+            stdout.write_all(path_bytes(&file.path))?;
+            writeln!(stdout)?;
+        } else if !output.columns.is_empty() {
+            for (position, column) in output.columns.iter().enumerate() {
+                if position > 0 {
+                    stdout.write_all(b"  ")?;
+                }
+                match column {
+                    Column::Rank => write!(stdout, "{:>5}", self.ranks[index])?,
+                    Column::Score => write!(
+                        stdout,
+                        "{:>10}",
+                        number_format.format(value, self.precision)
+                    )?,
+                    Column::Percentile => write!(
+                        stdout,
+                        "{:>5}",
+                        number_format.format(self.percentiles[index], 1)
+                    )?,
+                    Column::Tier => write!(stdout, "{:<5}", self.tier_labels[index])?,
+                    Column::Commits => write!(stdout, "{:>6}", file.commits)?,
+                    Column::LastModified => write!(stdout, "{}", file.last_modified)?,
+                    Column::Path => stdout.write_all(path_bytes(&file.path))?,
+                }
+            }
+            writeln!(stdout)?;
         } else {
-            println!("{:<10.4}  {}", score, path.display());
+            let shown = match self.tier_labels.get(index) {
+                Some(label) if output.columns.is_empty() => label.clone(),
+                _ => number_format.format(value, self.precision),
+            };
+            if self.color {
+                let heat = if self.max_score > 0.0 {
+                    file.score / self.max_score
+                } else {
+                    0.0
+                };
+                let shown = format!("{shown:<10}");
+                write!(
+                    stdout,
+                    "{}  {}  ",
+                    color::paint(&shown, heat),
+                    color::bar(heat)
+                )?;
+            } else {
+                write!(stdout, "{:<10}  ", shown)?;
+            }
+            stdout.write_all(path_bytes(&file.path))?;
+            writeln!(stdout)?;
         }
+        Ok(())
+    }
+}
+
+/// Name of `tier` (0 = hottest) out of `count` tiers
+fn tier_label(tier: usize, count: usize) -> String {
+    const NAMES: [&[&str]; 3] = [
+        &["hot", "cold"],
+        &["hot", "warm", "cold"],
+        &["hot", "warm", "cool", "cold"],
+    ];
+    match count {
+        2..=4 => NAMES[count - 2][tier].to_owned(),
+        _ => (tier + 1).to_string(),
+    }
+}
+
+fn run_gate(args: &GateArgs) -> anyhow::Result<()> {
+    let baseline: Vec<FileScore> =
+        serde_json::from_reader(BufReader::new(File::open(&args.snapshot)?))?;
+    let current = analyze_repo_with_options(args.analysis.repo()?, &args.analysis.to_options()?)?;
+
+    let new_hot = new_hot_files(&baseline, &current, args.top_percent);
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    for file in &new_hot {
+        write!(stdout, "{:<10.4}  ", file.score)?;
+        stdout.write_all(path_bytes(&file.path))?;
+        writeln!(stdout)?;
+    }
+    stdout.flush()?;
+
+    if new_hot.len() > args.max_new_hot {
+        eprintln!(
+            "Error: {} files entered the top {}% (allowed: {})",
+            new_hot.len(),
+            args.top_percent,
+            args.max_new_hot
+        );
+        process::exit(1);
     }
     Ok(())
 }
 
+fn run_trend(args: &TrendArgs) -> anyhow::Result<()> {
+    let window_secs = i64::from(args.window_days) * 86_400;
+    let entries = analyze_trend(
+        args.analysis.repo()?,
+        &args.analysis.to_options()?,
+        window_secs,
+    )?;
+
+    let rising: Vec<&TrendEntry> = entries
+        .iter()
+        .filter(|e| e.change() > 0.0)
+        .take(args.limit)
+        .collect();
+    let falling: Vec<&TrendEntry> = entries
+        .iter()
+        .rev()
+        .filter(|e| e.change() < 0.0)
+        .take(args.limit)
+        .collect();
+
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    for (title, group) in [("Rising", rising), ("Falling", falling)] {
+        writeln!(
+            stdout,
+            "{title} (last {} days vs the {} before):",
+            args.window_days, args.window_days
+        )?;
+        for entry in group {
+            write!(
+                stdout,
+                "{:>+10.4}  {:<10.4} -> {:<10.4}  ",
+                entry.change(),
+                entry.previous,
+                entry.current
+            )?;
+            stdout.write_all(path_bytes(&entry.path))?;
+            writeln!(stdout)?;
+        }
+        writeln!(stdout)?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn run_history(args: &HistoryArgs) -> anyhow::Result<()> {
+    let points = analyze_history(
+        args.analysis.repo()?,
+        &args.path,
+        &args.analysis.to_options()?,
+        args.interval.into(),
+    )?;
+
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    match args.format {
+        HistoryFormat::Json => return print_json(&points, OutputFormat::Json),
+        HistoryFormat::Jsonl => return print_json(&points, OutputFormat::Jsonl),
+        HistoryFormat::Csv => {
+            writeln!(stdout, "start,end,score,activity,commits")?;
+            for point in points {
+                writeln!(
+                    stdout,
+                    "{},{},{},{},{}",
+                    dates::date(point.start),
+                    dates::date(point.end),
+                    point.score,
+                    point.activity,
+                    point.commits
+                )?;
+            }
+        }
+        HistoryFormat::Human => {
+            for point in points {
+                writeln!(
+                    stdout,
+                    "{}  {:<10.4}  {:<10.4}  {:>4} commits",
+                    dates::date(point.start),
+                    point.score,
+                    point.activity,
+                    point.commits
+                )?;
+            }
+        }
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn run_diff(args: &DiffArgs) -> anyhow::Result<()> {
+    let deltas = diff_revs(
+        args.analysis.repo()?,
+        &args.analysis.to_options()?,
+        &args.base,
+        &args.head,
+    )?;
+
+    if args.format != OutputFormat::Human {
+        return print_json(&deltas, args.format);
+    }
+
+    let limit = if args.limit == 0 {
+        usize::MAX
+    } else {
+        args.limit
+    };
+    let gaining: Vec<&ScoreDelta> = deltas
+        .iter()
+        .filter(|d| d.change() > 0.0)
+        .take(limit)
+        .collect();
+    let losing: Vec<&ScoreDelta> = deltas
+        .iter()
+        .rev()
+        .filter(|d| d.change() < 0.0)
+        .take(limit)
+        .collect();
+
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    for (title, group) in [
+        (
+            format!("Hotter in {} than in {}:", args.head, args.base),
+            gaining,
+        ),
+        (
+            format!("Cooler in {} than in {}:", args.head, args.base),
+            losing,
+        ),
+    ] {
+        writeln!(stdout, "{title}")?;
+        for delta in group {
+            write!(
+                stdout,
+                "{:>+10.4}  {:<10.4} -> {:<10.4}  ",
+                delta.change(),
+                delta.base,
+                delta.head
+            )?;
+            stdout.write_all(path_bytes(&delta.path))?;
+            writeln!(stdout)?;
+        }
+        writeln!(stdout)?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn run_hotspot(args: &HotspotArgs) -> anyhow::Result<()> {
+    let mut hotspots = analyze_hotspots(
+        args.analysis.repo()?,
+        &args.analysis.to_options()?,
+        args.metric.into(),
+    )?;
+    if args.limit > 0 {
+        hotspots.truncate(args.limit);
+    }
+
+    if args.format != OutputFormat::Human {
+        return print_json(&hotspots, args.format);
+    }
+
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    for hotspot in hotspots {
+        write!(
+            stdout,
+            "{:<8.4}  {:<10.4}  {:>8} lines  ",
+            hotspot.hotspot, hotspot.score, hotspot.lines
+        )?;
+        stdout.write_all(path_bytes(&hotspot.path))?;
+        writeln!(stdout)?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn run_stale(args: &StaleArgs) -> anyhow::Result<()> {
+    let mut files = analyze_stale(
+        args.analysis.repo()?,
+        &args.analysis.to_options()?,
+        args.older_than,
+        args.metric.into(),
+        args.min_size,
+    )?;
+    if args.limit > 0 {
+        files.truncate(args.limit);
+    }
+
+    if args.format != OutputFormat::Human {
+        return print_json(&files, args.format);
+    }
+
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    for file in files {
+        write!(
+            stdout,
+            "{:>6} days  {:>8} lines  {:>4} commits  ",
+            file.age_days, file.lines, file.commits
+        )?;
+        stdout.write_all(path_bytes(&file.path))?;
+        writeln!(stdout)?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn run_risk(args: &RiskArgs) -> anyhow::Result<()> {
+    let mut risks = analyze_risk(
+        args.analysis.repo()?,
+        &args.analysis.to_options()?,
+        &args.fix_pattern,
+    )?;
+    if args.limit > 0 {
+        risks.truncate(args.limit);
+    }
+
+    if args.format != OutputFormat::Human {
+        return print_json(&risks, args.format);
+    }
+
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    for risk in risks {
+        write!(
+            stdout,
+            "{:<10.4}  {:>3}/{:<3} fixes  ",
+            risk.risk, risk.fixes, risk.commits
+        )?;
+        stdout.write_all(path_bytes(&risk.path))?;
+        writeln!(stdout)?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn run_owners(args: &OwnersArgs) -> anyhow::Result<()> {
+    let mut options = args.analysis.to_options()?;
+    if !args.files.is_empty() {
+        options.paths = Some(args.files.iter().cloned().collect());
+    }
+
+    let mut files = analyze_owners(args.analysis.repo()?, &options)?;
+    for file in &mut files {
+        file.contributors.truncate(args.top);
+    }
+
+    if args.format != OutputFormat::Human {
+        return print_json(&files, args.format);
+    }
+
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    for file in files {
+        stdout.write_all(path_bytes(&file.path))?;
+        writeln!(stdout)?;
+        for contributor in file.contributors {
+            writeln!(
+                stdout,
+                "  {:>5.1}%  {}",
+                contributor.share * 100.0,
+                contributor.author
+            )?;
+        }
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn run_authors(args: &AuthorsArgs) -> anyhow::Result<()> {
+    let mut options = args.analysis.to_options()?;
+    if !args.paths.is_empty() {
+        options.paths = Some(args.paths.iter().cloned().collect());
+    }
+
+    let mut authors = analyze_authors(args.analysis.repo()?, &options)?;
+    if args.limit > 0 {
+        authors.truncate(args.limit);
+    }
+
+    if args.format != OutputFormat::Human {
+        return print_json(&authors, args.format);
+    }
+
+    for author in authors {
+        println!(
+            "{:<10.4}  {:>5} commits  {:>5} files  {}",
+            author.score, author.commits, author.files, author.author
+        );
+    }
+    Ok(())
+}
+
+fn run_bus_factor(args: &BusFactorArgs) -> anyhow::Result<()> {
+    let mut risks = analyze_bus_factor(
+        args.analysis.repo()?,
+        &args.analysis.to_options()?,
+        args.threshold / 100.0,
+        args.depth,
+    )?;
+    if args.limit > 0 {
+        risks.truncate(args.limit);
+    }
+
+    if args.format != OutputFormat::Human {
+        return print_json(&risks, args.format);
+    }
+
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    for risk in risks {
+        write!(
+            stdout,
+            "{:>5.1}%  {:<10.4}  ",
+            risk.share * 100.0,
+            risk.weight
+        )?;
+        stdout.write_all(path_bytes(&risk.path))?;
+        writeln!(stdout, "  {}", risk.author)?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn run_cache_gc(args: &CacheGcArgs) -> anyhow::Result<()> {
+    let stats = gc_cache(args.analysis.repo()?, &args.analysis.to_options()?)?;
+
+    if args.format == OutputFormat::Jsonl {
+        println!("{}", serde_json::to_string(&stats)?);
+        return Ok(());
+    }
+    if args.format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    println!(
+        "Removed {} entries, kept {}; cache size {} -> {} bytes",
+        stats.removed, stats.kept, stats.bytes_before, stats.bytes_after
+    );
+    Ok(())
+}
+
+fn run_cache_export(args: &CacheTransferArgs) -> anyhow::Result<()> {
+    let entries = export_cache(
+        args.analysis.repo()?,
+        &args.analysis.to_options()?,
+        &args.file,
+    )?;
+    println!("Exported {entries} entries to {}", args.file.display());
+    Ok(())
+}
+
+fn run_cache_import(args: &CacheTransferArgs) -> anyhow::Result<()> {
+    let entries = import_cache(
+        args.analysis.repo()?,
+        &args.analysis.to_options()?,
+        &args.file,
+    )?;
+    println!("Imported {entries} entries from {}", args.file.display());
+    Ok(())
+}
+
+fn run_warm(args: &WarmArgs) -> anyhow::Result<()> {
+    let options = AnalyzeOptions {
+        since: args.since,
+        ..args.analysis.to_options()?
+    };
+    let commits = warm_cache(args.analysis.repo()?, &options)?;
+    println!("Cached the statistics of {commits} commits");
+    Ok(())
+}
+
+fn run_hook_install(args: &HookInstallArgs) -> anyhow::Result<()> {
+    // The absolute path keeps working for git clients that run hooks without the user's PATH
+    let program = env::current_exe()
+        .map(|exe| exe.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "frecenfile".to_owned());
+    let command = [program.as_str(), "warm"]
+        .into_iter()
+        .chain(args.args.iter().map(String::as_str))
+        .map(shell_quote)
+        .collect::<Vec<_>>()
+        .join(" ");
+    report_hooks(install_hooks(&args.repo, &command)?, "Installed")
+}
+
+fn report_hooks(changed: Vec<PathBuf>, action: &str) -> anyhow::Result<()> {
+    if changed.is_empty() {
+        println!("Hooks are already up to date");
+    }
+    for hook in changed {
+        println!("{action} {}", hook.display());
+    }
+    Ok(())
+}
+
+/// `word` quoted for a POSIX shell
+fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+fn run_export(args: &ExportArgs) -> anyhow::Result<()> {
+    let repo = args.analysis.repo()?;
+    let mut scores = analyze_repo_with_options(repo, &args.analysis.to_options()?)?;
+    scores.sort_by(FileScore::cmp_hottest_first);
+    write_score_notes(repo, &args.notes, &scores)?;
+    println!(
+        "Wrote the scores of {} files to {} on HEAD",
+        scores.len(),
+        args.notes
+    );
+    Ok(())
+}
+
+fn run_report(args: &ReportArgs) -> anyhow::Result<()> {
+    let options = args.analysis.to_options()?;
+    let repo = args.analysis.repo()?;
+
+    let mut analysis = analyze_repo_with_metadata(repo, &options)?;
+    analysis.scores.sort_by(FileScore::cmp_hottest_first);
+    let mut directories = directory_scores(&analysis.scores, args.depth);
+    directories.truncate(args.top);
+    analysis.scores.truncate(args.top);
+
+    let mut hotspots = analyze_hotspots(repo, &options, ComplexityMetric::Lines)?;
+    hotspots.truncate(args.top);
+
+    let trend = analyze_trend(repo, &options, i64::from(args.window_days) * 86_400)?;
+    let rising = trend
+        .iter()
+        .filter(|e| e.change() > 0.0)
+        .take(args.top)
+        .cloned()
+        .collect();
+    let falling = trend
+        .iter()
+        .rev()
+        .filter(|e| e.change() < 0.0)
+        .take(args.top)
+        .cloned()
+        .collect();
+
+    let report = report::Report {
+        metadata: analysis.metadata,
+        files: analysis.scores,
+        directories,
+        hotspots,
+        rising,
+        falling,
+        window_days: args.window_days,
+    };
+    let rendered = match args.format {
+        ReportFormat::Md => report::markdown(&report),
+        ReportFormat::Html => report::html(&report),
+    };
+    match &args.output {
+        Some(path) => fs::write(path, rendered)?,
+        None => io::stdout().lock().write_all(rendered.as_bytes())?,
+    }
+    Ok(())
+}
@@ -0,0 +1,353 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, bail};
+use frecenfile::{AnalyzeOptions, analyze_contributions, quote_path, related_files};
+use rustc_hash::FxHashMap as HashMap;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+
+use crate::dates::date;
+use crate::serve::{METHOD_NOT_FOUND, RpcError, Server, parse_params};
+
+/// Protocol revision this server implements
+const PROTOCOL_VERSION: &str = "2025-06-18";
+/// Most commits listed by `file_history_summary`
+const RECENT_COMMITS: usize = 5;
+
+#[derive(Deserialize)]
+struct ToolCall {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize)]
+struct TopFilesArgs {
+    #[serde(default = "default_limit")]
+    limit: usize,
+    filter: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RelatedFilesArgs {
+    path: PathBuf,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+#[derive(Deserialize)]
+struct HistoryArgs {
+    path: PathBuf,
+}
+
+fn default_limit() -> usize {
+    20
+}
+
+/// Answer one Model Context Protocol request
+pub fn call(server: &mut Server, method: &str, params: Value) -> Result<Value, RpcError> {
+    match method {
+        "initialize" => Ok(json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": { "tools": {} },
+            "serverInfo": {
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tools() })),
+        "tools/call" => {
+            let call: ToolCall = parse_params(params)?;
+            // Tool failures, unknown tools and invalid arguments included, are reported to
+            // the model rather than as protocol errors
+            let (text, is_error) = match call_tool(server, &call.name, call.arguments) {
+                Ok(text) => (text, false),
+                Err(err) => (format!("{err:#}"), true),
+            };
+            Ok(json!({
+                "content": [{ "type": "text", "text": text }],
+                "isError": is_error,
+            }))
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method {method}"),
+        )),
+    }
+}
+
+fn tools() -> Value {
+    json!([
+        {
+            "name": "top_files",
+            "description": "The most actively changed files of the repository, hottest first, \
+                            ranked by frecency (how often and how recently they were changed)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "limit": { "type": "integer", "description": "Number of files (default 20)" },
+                    "filter": {
+                        "type": "string",
+                        "description": "Only files whose path contains this, ignoring case",
+                    },
+                },
+            },
+        },
+        {
+            "name": "related_files",
+            "description": "Files that are usually changed together with the given file",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Path relative to the repository root" },
+                    "limit": { "type": "integer", "description": "Number of files (default 20)" },
+                },
+                "required": ["path"],
+            },
+        },
+        {
+            "name": "file_history_summary",
+            "description": "How active a file is: its rank, number of commits, last change, \
+                            main recent contributors and latest commits",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Path relative to the repository root" },
+                },
+                "required": ["path"],
+            },
+        },
+    ])
+}
+
+/// Text answer of a tool
+fn call_tool(server: &Server, name: &str, arguments: Value) -> anyhow::Result<String> {
+    match name {
+        "top_files" => {
+            let args: TopFilesArgs = parse_arguments(arguments)?;
+            let mut text = String::new();
+            for (index, file) in server
+                .matching(Some(args.limit), args.filter.as_deref())
+                .into_iter()
+                .enumerate()
+            {
+                let _ = writeln!(
+                    text,
+                    "{}. {}  score {:.4}, {} commits, last changed {}",
+                    index + 1,
+                    quote_path(&file.path),
+                    file.score,
+                    file.commits,
+                    date(file.last_modified)
+                );
+            }
+            if text.is_empty() {
+                text.push_str("No matching files.");
+            }
+            Ok(text)
+        }
+        "related_files" => {
+            let args: RelatedFilesArgs = parse_arguments(arguments)?;
+            related_summary(server, &args.path, args.limit)
+        }
+        "file_history_summary" => {
+            let args: HistoryArgs = parse_arguments(arguments)?;
+            history_summary(server, &args.path)
+        }
+        _ => bail!("unknown tool {name}"),
+    }
+}
+
+/// A tool's arguments, which may be left out when all of them are optional
+fn parse_arguments<T: DeserializeOwned>(arguments: Value) -> anyhow::Result<T> {
+    let arguments = if arguments.is_null() {
+        json!({})
+    } else {
+        arguments
+    };
+    serde_json::from_value(arguments).context("invalid arguments")
+}
+
+fn related_summary(server: &Server, path: &Path, limit: usize) -> anyhow::Result<String> {
+    let related = related_files(server.repo(), path, limit, server.options())?;
+    let mut text = String::new();
+    for file in &related {
+        let _ = writeln!(
+            text,
+            "{}  (changed together in {} commits)",
+            quote_path(&file.path),
+            file.commits
+        );
+    }
+    if text.is_empty() {
+        text.push_str("No files were changed together with it.");
+    }
+    Ok(text)
+}
+
+fn history_summary(server: &Server, path: &Path) -> anyhow::Result<String> {
+    let scores = &server.analysis().scores;
+    let Some((rank, file)) = scores
+        .iter()
+        .enumerate()
+        .find(|(_, file)| file.path == path)
+    else {
+        return Ok(format!(
+            "{} has no counted changes in the analyzed history.",
+            quote_path(path)
+        ));
+    };
+
+    let mut text = format!(
+        "{}: rank {} of {} files (score {:.4}), {} commits, last changed {}.\n",
+        quote_path(path),
+        rank + 1,
+        scores.len(),
+        file.score,
+        file.commits,
+        date(file.last_modified)
+    );
+
+    let options = AnalyzeOptions {
+        paths: Some(HashSet::from([path.to_path_buf()])),
+        ..server.options().clone()
+    };
+    let contributions = analyze_contributions(server.repo(), &options)?;
+    let mut authors: HashMap<&str, f64> = HashMap::default();
+    for contribution in &contributions {
+        *authors.entry(&contribution.author).or_default() += contribution.weight;
+    }
+    let total: f64 = authors.values().sum();
+    let mut authors: Vec<(&str, f64)> = authors.into_iter().collect();
+    authors.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    if total > 0.0 {
+        text.push_str("Main recent contributors:\n");
+        for (author, weight) in authors.iter().take(RECENT_COMMITS) {
+            let _ = writeln!(text, "- {author}: {:.0}%", weight / total * 100.0);
+        }
+    }
+
+    // Contributions are newest first
+    text.push_str("Latest commits:\n");
+    for contribution in contributions.iter().take(RECENT_COMMITS) {
+        let _ = writeln!(
+            text,
+            "- {} {} {}",
+            &contribution.commit[..contribution.commit.len().min(10)],
+            date(contribution.time),
+            contribution.author
+        );
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serve::tests::TestRepo;
+
+    /// Text and `isError` of a `tools/call` result
+    fn call_tool_text(server: &mut Server, name: &str, arguments: Value) -> (String, bool) {
+        let params = json!({ "name": name, "arguments": arguments });
+        let result = call(server, "tools/call", params).ok().unwrap();
+        let text = result["content"][0]["text"].as_str().unwrap().to_owned();
+        (text, result["isError"].as_bool().unwrap())
+    }
+
+    #[test]
+    fn initialize_and_list_the_tools() {
+        let repo = TestRepo::new();
+        let mut server = repo.server();
+
+        let result = call(&mut server, "initialize", json!({})).ok().unwrap();
+        assert_eq!(result["protocolVersion"], PROTOCOL_VERSION);
+        assert_eq!(result["serverInfo"]["name"], env!("CARGO_PKG_NAME"));
+        assert!(result["capabilities"]["tools"].is_object());
+
+        let result = call(&mut server, "tools/list", Value::Null).ok().unwrap();
+        let names: Vec<_> = result["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            ["top_files", "related_files", "file_history_summary"]
+        );
+
+        let error = call(&mut server, "resources/list", Value::Null)
+            .err()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(error).unwrap()["code"],
+            METHOD_NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn tools_answer_with_text() {
+        let repo = TestRepo::new();
+        let mut server = repo.server();
+
+        let (text, is_error) = call_tool_text(&mut server, "top_files", json!({ "limit": 2 }));
+        assert!(!is_error);
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("1. src/main.rs  score "), "{text}");
+        assert!(
+            lines[0].ends_with("3 commits, last changed 2023-11-14"),
+            "{text}"
+        );
+        let (text, _) = call_tool_text(&mut server, "top_files", json!({ "filter": "nothing" }));
+        assert_eq!(text, "No matching files.");
+
+        let arguments = json!({ "path": "src/lib.rs" });
+        let (text, is_error) = call_tool_text(&mut server, "related_files", arguments);
+        assert!(!is_error);
+        assert_eq!(text, "src/main.rs  (changed together in 1 commits)\n");
+
+        let arguments = json!({ "path": "src/main.rs" });
+        let (text, is_error) = call_tool_text(&mut server, "file_history_summary", arguments);
+        assert!(!is_error);
+        assert!(text.starts_with("src/main.rs: rank 1 of 3 files"), "{text}");
+        assert!(text.contains("- Test <test@example.com>: 100%"), "{text}");
+        assert_eq!(text.matches("2023-11-1").count(), 4, "{text}");
+
+        let arguments = json!({ "path": "missing.rs" });
+        let (text, is_error) = call_tool_text(&mut server, "file_history_summary", arguments);
+        assert!(!is_error);
+        assert_eq!(
+            text,
+            "missing.rs has no counted changes in the analyzed history."
+        );
+    }
+
+    #[test]
+    fn tool_failures_are_reported_to_the_model() {
+        let repo = TestRepo::new();
+        let mut server = repo.server();
+
+        let (text, is_error) = call_tool_text(&mut server, "rewrite_history", json!({}));
+        assert!(is_error);
+        assert_eq!(text, "unknown tool rewrite_history");
+
+        let (text, is_error) = call_tool_text(&mut server, "related_files", json!({}));
+        assert!(is_error);
+        assert!(
+            text.starts_with("invalid arguments: missing field `path`"),
+            "{text}"
+        );
+
+        // Only a malformed call itself is a protocol error
+        let error = call(&mut server, "tools/call", json!({})).err().unwrap();
+        assert_eq!(
+            serde_json::to_value(error).unwrap()["code"],
+            crate::serve::INVALID_PARAMS
+        );
+    }
+}
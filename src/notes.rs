@@ -0,0 +1,33 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use git2::{Oid, Signature};
+
+use crate::{FileScore, open_repo};
+
+/// Attach `scores` to HEAD as a git note under `notes_ref` (e.g. `refs/notes/frecenfile`),
+/// replacing any note already there. The note holds the same JSON array as `--format json`,
+/// so it can be pushed, fetched and read back with `git notes --ref <ref> show`.
+/// Returns the id of the note blob.
+pub fn write_score_notes(repo_path: &Path, notes_ref: &str, scores: &[FileScore]) -> Result<Oid> {
+    let repo = open_repo(repo_path)?;
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .context("HEAD does not point to a commit")?;
+    let signature = repo
+        .signature()
+        .or_else(|_| Signature::now("frecenfile", "frecenfile@localhost"))?;
+    let note = serde_json::to_string_pretty(scores)?;
+    let id = repo
+        .note(
+            &signature,
+            &signature,
+            Some(notes_ref),
+            head.id(),
+            &note,
+            true,
+        )
+        .with_context(|| format!("failed to write the note to {notes_ref}"))?;
+    Ok(id)
+}
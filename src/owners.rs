@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use rustc_hash::FxHashMap as HashMap;
+use serde::Serialize;
+
+use crate::AnalyzeOptions;
+use crate::pipeline::{self, Accumulator, WeightedCommit};
+
+/// An author's recency-weighted share of the changes to a file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Contributor {
+    /// `Name <email>`, after applying `.mailmap`
+    pub author: String,
+    /// Sum of decayed, size-penalized contributions, on the same scale as file scores
+    pub weight: f64,
+    /// Fraction of the file's total weight, 0–1
+    pub share: f64,
+}
+
+/// Contributors to one file, largest share first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileOwnership {
+    #[serde(serialize_with = "crate::paths::serialize")]
+    pub path: PathBuf,
+    pub contributors: Vec<Contributor>,
+}
+
+/// Weight per (path, author), accumulated with the same decay as file scores
+#[derive(Default)]
+pub(crate) struct OwnershipMap(pub(crate) HashMap<PathBuf, HashMap<String, f64>>);
+
+impl Accumulator for OwnershipMap {
+    const NEEDS_AUTHOR: bool = true;
+
+    fn add(&mut self, commit: &WeightedCommit) {
+        let Some(author) = commit.author else {
+            return;
+        };
+        for change in commit.changes {
+            *self
+                .0
+                .entry(change.path.to_path_buf())
+                .or_default()
+                .entry(author.to_owned())
+                .or_default() += commit.file_score(change);
+        }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        for (path, authors) in other.0 {
+            let entry = self.0.entry(path).or_default();
+            for (author, weight) in authors {
+                *entry.entry(author).or_default() += weight;
+            }
+        }
+        self
+    }
+}
+
+/// Recency-weighted contributors for every file selected by `options`
+pub fn analyze_owners(repo_path: &Path, options: &AnalyzeOptions) -> Result<Vec<FileOwnership>> {
+    let OwnershipMap(map) = pipeline::run(repo_path, options)?;
+
+    let mut files: Vec<FileOwnership> = map
+        .into_iter()
+        .map(|(path, authors)| {
+            let total: f64 = authors.values().sum();
+            let mut contributors: Vec<Contributor> = authors
+                .into_iter()
+                .map(|(author, weight)| Contributor {
+                    author,
+                    weight,
+                    share: if total > 0.0 { weight / total } else { 0.0 },
+                })
+                .collect();
+            contributors.sort_by(|a, b| {
+                b.weight
+                    .total_cmp(&a.weight)
+                    .then_with(|| a.author.cmp(&b.author))
+            });
+            FileOwnership { path, contributors }
+        })
+        .collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
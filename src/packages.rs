@@ -0,0 +1,63 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use git2::{ObjectType, TreeWalkMode, TreeWalkResult};
+
+use crate::open_repo;
+
+/// Files that mark the root of a package
+const MANIFESTS: [&str; 3] = ["Cargo.toml", "package.json", "go.mod"];
+
+/// Package roots of a (monorepo) repository: directories at HEAD with a `Cargo.toml` that
+/// declares a `[package]`, a `package.json` or a `go.mod`
+#[derive(Debug, Clone, Default)]
+pub struct Packages {
+    /// Relative to the repository root, deepest first
+    roots: Vec<PathBuf>,
+}
+
+impl Packages {
+    /// Find the package roots in the HEAD tree, skipping vendored `node_modules`
+    pub fn for_repo(repo_path: &Path) -> Result<Self> {
+        let repo = open_repo(repo_path)?;
+        let tree = repo.head()?.peel_to_tree()?;
+        let mut roots = Vec::new();
+        tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+            let Some(name) = entry.name() else {
+                return TreeWalkResult::Ok;
+            };
+            if entry.kind() == Some(ObjectType::Tree) {
+                return match name {
+                    "node_modules" => TreeWalkResult::Skip,
+                    _ => TreeWalkResult::Ok,
+                };
+            }
+            if !MANIFESTS.contains(&name) {
+                return TreeWalkResult::Ok;
+            }
+            // A virtual Cargo workspace manifest only lists members
+            let is_package = name != "Cargo.toml"
+                || repo.find_blob(entry.id()).is_ok_and(|blob| {
+                    String::from_utf8_lossy(blob.content())
+                        .lines()
+                        .any(|line| line.trim() == "[package]")
+                });
+            let root = PathBuf::from(dir.trim_end_matches('/'));
+            if is_package && !roots.contains(&root) {
+                roots.push(root);
+            }
+            TreeWalkResult::Ok
+        })?;
+        roots.sort_by_key(|root| std::cmp::Reverse(root.components().count()));
+        Ok(Self { roots })
+    }
+
+    /// Root of the package containing `path` (relative to the repository root); empty for a
+    /// package at the root, None when the path is in no package
+    pub fn package(&self, path: &Path) -> Option<&Path> {
+        self.roots
+            .iter()
+            .find(|root| path.starts_with(root))
+            .map(PathBuf::as_path)
+    }
+}
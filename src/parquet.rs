@@ -0,0 +1,100 @@
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow_array::{
+    Array, ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray, UInt32Array, UInt64Array,
+};
+use frecenfile::{Contribution, FileScore, quote_path};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+/// Write `scores.parquet` and, when `contributions` is given, `contributions.parquet` into `dir`;
+/// paths are [`quote_path`] text, so paths that are not valid UTF-8 stay distinct
+pub fn export(
+    dir: &Path,
+    scores: &[FileScore],
+    contributions: Option<&[Contribution]>,
+) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)?;
+    write(&dir.join("scores.parquet"), scores_batch(scores)?)?;
+    if let Some(contributions) = contributions {
+        write(
+            &dir.join("contributions.parquet"),
+            contributions_batch(contributions)?,
+        )?;
+    }
+    Ok(())
+}
+
+fn scores_batch(scores: &[FileScore]) -> anyhow::Result<RecordBatch> {
+    Ok(RecordBatch::try_from_iter([
+        (
+            "path",
+            column::<StringArray, _>(scores.iter().map(|file| quoted(&file.path))),
+        ),
+        (
+            "score",
+            column::<Float64Array, _>(scores.iter().map(|file| file.score)),
+        ),
+        (
+            "commits",
+            column::<UInt32Array, _>(scores.iter().map(|file| file.commits)),
+        ),
+        (
+            "last_modified",
+            column::<Int64Array, _>(scores.iter().map(|file| file.last_modified)),
+        ),
+        (
+            "churn",
+            column::<UInt64Array, _>(scores.iter().map(|file| file.churn)),
+        ),
+    ])?)
+}
+
+fn contributions_batch(contributions: &[Contribution]) -> anyhow::Result<RecordBatch> {
+    Ok(RecordBatch::try_from_iter([
+        (
+            "commit_id",
+            column::<StringArray, _>(contributions.iter().map(|c| c.commit.clone())),
+        ),
+        (
+            "time",
+            column::<Int64Array, _>(contributions.iter().map(|c| c.time)),
+        ),
+        (
+            "author",
+            column::<StringArray, _>(contributions.iter().map(|c| c.author.clone())),
+        ),
+        (
+            "path",
+            column::<StringArray, _>(contributions.iter().map(|c| quoted(&c.path))),
+        ),
+        (
+            "weight",
+            column::<Float64Array, _>(contributions.iter().map(|c| c.weight)),
+        ),
+    ])?)
+}
+
+fn column<A, T>(values: impl Iterator<Item = T>) -> ArrayRef
+where
+    A: Array + From<Vec<T>> + 'static,
+{
+    Arc::new(A::from(values.collect::<Vec<_>>()))
+}
+
+fn quoted(path: &Path) -> String {
+    quote_path(path).into_owned()
+}
+
+fn write(path: &Path, batch: RecordBatch) -> anyhow::Result<()> {
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(File::create(path)?, batch.schema(), Some(properties))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
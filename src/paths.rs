@@ -0,0 +1,150 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The bytes of `path` as the OS stores them; raw bytes on Unix. Unlike
+/// `to_string_lossy`, distinct paths never map to the same bytes.
+pub fn path_bytes(path: &Path) -> &[u8] {
+    path.as_os_str().as_encoded_bytes()
+}
+
+/// Inverse of [`path_bytes`] for bytes read from outside, e.g. a NUL-separated path
+/// list. Non-UTF-8 bytes are only representable on Unix.
+pub fn path_from_bytes(bytes: &[u8]) -> Result<PathBuf> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Ok(PathBuf::from(std::ffi::OsStr::from_bytes(bytes)))
+    }
+    #[cfg(not(unix))]
+    {
+        Ok(PathBuf::from(std::str::from_utf8(bytes)?))
+    }
+}
+
+/// `path` as text without losing bytes, quoted the way git's `core.quotePath` does: paths that
+/// are valid UTF-8 without quotes, backslashes or control characters are kept as they are,
+/// others are wrapped in double quotes with C escapes and octal escapes for non-UTF-8 bytes,
+/// e.g. `"a\377"`
+pub fn quote_path(path: &Path) -> Cow<'_, str> {
+    let needs_quoting = |c: char| c == '"' || c == '\\' || c.is_ascii_control();
+    if let Some(text) = path.to_str()
+        && !text.contains(needs_quoting)
+    {
+        return Cow::Borrowed(text);
+    }
+    let mut quoted = String::from('"');
+    for chunk in path_bytes(path).utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '"' => quoted.push_str("\\\""),
+                '\\' => quoted.push_str("\\\\"),
+                '\t' => quoted.push_str("\\t"),
+                '\n' => quoted.push_str("\\n"),
+                c if c.is_ascii_control() => {
+                    quoted.push_str(&format!("\\{:03o}", c as u32));
+                }
+                c => quoted.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            quoted.push_str(&format!("\\{byte:03o}"));
+        }
+    }
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
+
+/// JSON form of a path: a plain string when it is valid UTF-8, otherwise
+/// `{"bytes": [...]}` holding the raw bytes
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum PathRepr<'a> {
+    Utf8(Cow<'a, str>),
+    Bytes { bytes: Cow<'a, [u8]> },
+}
+
+/// `serialize_with` helper writing paths as [`PathRepr`]
+pub(crate) fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    match path.to_str() {
+        Some(path) => PathRepr::Utf8(Cow::Borrowed(path)),
+        None => PathRepr::Bytes {
+            bytes: Cow::Borrowed(path_bytes(path)),
+        },
+    }
+    .serialize(serializer)
+}
+
+/// `deserialize_with` helper accepting either form written by [`serialize`]
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    match PathRepr::deserialize(deserializer)? {
+        PathRepr::Utf8(path) => Ok(PathBuf::from(path.into_owned())),
+        PathRepr::Bytes { bytes } => path_from_bytes(&bytes).map_err(serde::de::Error::custom),
+    }
+}
+
+/// `path` with the `/` separators git stores, so `src\lib.rs` typed on Windows matches
+/// `src/lib.rs` from a diff. Backslashes are ordinary file name bytes elsewhere.
+pub fn to_git_path(path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(path.to_string_lossy().replace('\\', "/"))
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// `path` with the platform's separator, e.g. `src\lib.rs` on Windows
+pub fn to_native_path(path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(path.to_string_lossy().replace('/', "\\"))
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// `path` lowercased for case-insensitive comparison; non-UTF-8 paths are kept as they are
+pub(crate) fn fold_case(path: &Path) -> PathBuf {
+    path.to_str().map_or_else(
+        || path.to_path_buf(),
+        |path| PathBuf::from(path.to_lowercase()),
+    )
+}
+
+/// Like `Path::strip_prefix`, comparing components case-insensitively with `folded_prefix`
+/// (already passed through [`fold_case`])
+pub(crate) fn strip_prefix_ignore_case<'a>(
+    path: &'a Path,
+    folded_prefix: &Path,
+) -> Option<&'a Path> {
+    let mut components = path.components();
+    for expected in folded_prefix.components() {
+        let component = components.next()?;
+        if fold_case(Path::new(component.as_os_str())) != Path::new(expected.as_os_str()) {
+            return None;
+        }
+    }
+    Some(components.as_path())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_only_paths_that_need_it() {
+        assert_eq!(quote_path(Path::new("src/a b.rs")), "src/a b.rs");
+        assert_eq!(quote_path(Path::new("a\"b\\c\td")), "\"a\\\"b\\\\c\\td\"");
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            let quoted = |bytes: &[u8]| {
+                quote_path(Path::new(std::ffi::OsStr::from_bytes(bytes))).into_owned()
+            };
+            assert_eq!(quoted(b"a\xff"), "\"a\\377\"");
+            assert_ne!(quoted(b"a\xff"), quoted(b"a\xfe"));
+        }
+    }
+}
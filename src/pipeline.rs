@@ -0,0 +1,930 @@
+use std::cell::{OnceCell, RefCell};
+use std::collections::hash_map::Entry;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use chrono::Utc;
+use git2::{Commit, Mailmap, Oid, Repository};
+use rayon::prelude::*;
+use rustc_hash::FxHashMap as HashMap;
+use tracing::{debug, debug_span, info_span, trace, trace_span};
+
+use crate::cache::Cache;
+use crate::config::PathWeight;
+use crate::intern::{PathTable, RunPathId};
+use crate::paths::{fold_case, path_bytes, path_from_bytes, strip_prefix_ignore_case};
+use crate::scorer::{CommitMeta, FileMeta, ScoreMode, Scorer};
+use crate::spill::{self, Spill};
+use crate::statics::{FileChange, Pathspec, StaticsMode, get_commit_statistics};
+use crate::stats::Phase;
+use crate::{
+    AnalyzeOptions, Cancelled, authors, cherry_pick, commit_graph, config, conventional, generated,
+    issues, open_repo, revwalk,
+};
+
+/// Wall time a single chunk should take; chunk sizes adapt to the measured per-commit cost
+const TARGET_CHUNK_TIME: Duration = Duration::from_millis(50);
+const INITIAL_COMMITS_PER_CHUNK: usize = 64;
+const MIN_COMMITS_PER_CHUNK: usize = 8;
+const MAX_COMMITS_PER_CHUNK: usize = 1000;
+
+/// Paths each worker remembers the [`generated::is_generated`] answer for
+const MAX_GENERATED_MEMO: usize = 1 << 16;
+
+/// Source of [`StaticsMode::run`]
+static NEXT_RUN: AtomicU64 = AtomicU64::new(0);
+
+/// Per-path accumulator used while reducing commits
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct FileStats {
+    pub(crate) score: f64,
+    pub(crate) commits: u32,
+    pub(crate) last_modified: i64,
+    pub(crate) churn: u64,
+}
+
+impl FileStats {
+    fn merge(&mut self, other: FileStats) {
+        self.score += other.score;
+        self.commits += other.commits;
+        self.last_modified = self.last_modified.max(other.last_modified);
+        self.churn += other.churn;
+    }
+
+    fn to_bytes(self) -> [u8; 28] {
+        let mut bytes = [0; 28];
+        bytes[..8].copy_from_slice(&self.score.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.commits.to_le_bytes());
+        bytes[12..20].copy_from_slice(&self.last_modified.to_le_bytes());
+        bytes[20..].copy_from_slice(&self.churn.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(Self {
+            score: f64::from_le_bytes(bytes.get(..8)?.try_into().ok()?),
+            commits: u32::from_le_bytes(bytes.get(8..12)?.try_into().ok()?),
+            last_modified: i64::from_le_bytes(bytes.get(12..20)?.try_into().ok()?),
+            churn: u64::from_le_bytes(bytes.get(20..28)?.try_into().ok()?),
+        })
+    }
+}
+
+/// Per-file stats keyed by run id; paths are only resolved when the results are output
+#[derive(Clone, Default)]
+pub(crate) struct ScoreMap {
+    /// Resolves the run ids; the run's path table, taken from the first commit added
+    paths: Option<Arc<PathTable>>,
+    stats: HashMap<RunPathId, FileStats>,
+}
+
+impl ScoreMap {
+    /// Stats keyed by the run ids of `paths`
+    pub(crate) fn new(paths: Arc<PathTable>, stats: HashMap<RunPathId, FileStats>) -> Self {
+        Self {
+            paths: Some(paths),
+            stats,
+        }
+    }
+
+    /// The stats of `path`, if it has any
+    pub(crate) fn get_mut(&mut self, path: &Path) -> Option<&mut FileStats> {
+        let run_id = self.paths.as_ref()?.run_id(path)?;
+        self.stats.get_mut(&run_id)
+    }
+
+    /// The stats of `path`, inserting `default()` when it has none
+    pub(crate) fn get_or_insert_with(
+        &mut self,
+        path: &Path,
+        default: impl FnOnce() -> FileStats,
+    ) -> &mut FileStats {
+        let (run_id, _) = self.paths.get_or_insert_default().share(path);
+        self.stats.entry(run_id).or_insert_with(default)
+    }
+
+    /// Every file with its path
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (Arc<Path>, &FileStats)> {
+        self.stats
+            .iter()
+            .filter_map(|(&run_id, stats)| Some((self.paths.as_ref()?.resolve(run_id)?, stats)))
+    }
+}
+
+/// Merge the stats of paths that only differ in case under the most recently changed spelling
+pub(crate) fn merge_case_variants(scores: ScoreMap) -> ScoreMap {
+    let Some(paths) = scores.paths else {
+        return ScoreMap::default();
+    };
+    let mut merged: HashMap<PathBuf, (RunPathId, Arc<Path>, FileStats)> = HashMap::default();
+    for (run_id, stats) in scores.stats {
+        let Some(path) = paths.resolve(run_id) else {
+            continue;
+        };
+        match merged.entry(fold_case(&path)) {
+            Entry::Vacant(entry) => {
+                entry.insert((run_id, path, stats));
+            }
+            Entry::Occupied(mut entry) => {
+                let (spelling_id, spelling, total) = entry.get_mut();
+                if (stats.last_modified, &path) > (total.last_modified, &*spelling) {
+                    (*spelling_id, *spelling) = (run_id, path);
+                }
+                total.merge(stats);
+            }
+        }
+    }
+    let stats = merged
+        .into_values()
+        .map(|(run_id, _, stats)| (run_id, stats))
+        .collect();
+    ScoreMap::new(paths, stats)
+}
+
+/// `path` (relative to the repository root) relative to `prefix`, if it is under it and among
+/// `paths`, for signals gathered outside the commit walk
+pub(crate) fn scoped_path(path: &Path, options: &AnalyzeOptions) -> Option<PathBuf> {
+    let path = match &options.prefix {
+        Some(prefix) if options.ignore_case => strip_prefix_ignore_case(path, &fold_case(prefix))?,
+        Some(prefix) => path.strip_prefix(prefix).ok()?,
+        None => path,
+    };
+    let included = options.paths.as_ref().is_none_or(|paths| {
+        if options.ignore_case {
+            let folded = fold_case(path);
+            paths.iter().any(|other| fold_case(other) == folded)
+        } else {
+            paths.contains(path)
+        }
+    });
+    included.then(|| path.to_path_buf())
+}
+
+/// One counted commit, after filtering and weighting, as seen by an accumulator
+pub(crate) struct WeightedCommit<'a> {
+    pub(crate) id: Oid,
+    pub(crate) time: i64,
+    /// Recency decay combined with any author weight
+    pub(crate) weight: f64,
+    /// The author and commit type part of `weight`, which does not depend on the analysis time
+    pub(crate) commit_factor: f64,
+    /// Mailmap-resolved `Name <email>`; only set when the accumulator needs authors
+    pub(crate) author: Option<&'a str>,
+    /// Full commit message; only set when the accumulator needs messages
+    pub(crate) message: Option<&'a str>,
+    /// Touched files that pass the path filter
+    pub(crate) changes: &'a [FileChange],
+    /// Resolves the run ids of `changes`
+    pub(crate) paths: &'a Arc<PathTable>,
+    scorer: &'a dyn Scorer,
+    path_weights: &'a [PathWeight],
+    pub(crate) max_contribution: Option<f64>,
+}
+
+impl WeightedCommit<'_> {
+    /// Contribution of one changed file: the commit weight times the scorer's file weight and
+    /// any matching path weights, capped at `AnalyzeOptions::max_commit_contribution`
+    pub(crate) fn file_score(&self, change: &FileChange) -> f64 {
+        let score = self.weight * self.file_factor(change);
+        self.max_contribution.map_or(score, |cap| score.min(cap))
+    }
+
+    /// The scorer's file weight times any matching path weights; independent of the
+    /// analysis time
+    pub(crate) fn file_factor(&self, change: &FileChange) -> f64 {
+        self.scorer.file_weight(&FileMeta {
+            path: &change.path,
+            size: change.size,
+            churn: change.churn,
+        }) * config::path_weight(self.path_weights, &change.path)
+    }
+}
+
+/// Per-worker result of a history walk; partial results are merged pairwise
+pub(crate) trait Accumulator: Send {
+    /// Resolve commit authors even when no author option asks for them
+    const NEEDS_AUTHOR: bool = false;
+
+    /// Decode commit messages for [`WeightedCommit::message`]
+    const NEEDS_MESSAGE: bool = false;
+
+    fn add(&mut self, commit: &WeightedCommit);
+
+    fn merge(self, other: Self) -> Self;
+
+    /// Approximate heap usage, checked against `AnalyzeOptions::max_memory_bytes`
+    fn estimated_memory(&self) -> u64 {
+        0
+    }
+
+    /// Move the partial result to `spill` and start over empty, to get back under the memory
+    /// limit; false when this accumulator cannot spill. Spilled results must not refer to
+    /// run ids, as the run's path table is cleared after each spill.
+    fn spill(&mut self, _spill: &mut Spill) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Merge everything [`Accumulator::spill`] wrote back in at the end of the walk, for
+    /// callers that need the whole result in memory
+    fn restore(self, _spill: Spill) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(self)
+    }
+}
+
+impl Accumulator for ScoreMap {
+    fn add(&mut self, commit: &WeightedCommit) {
+        if self.paths.is_none() {
+            self.paths = Some(commit.paths.clone());
+        }
+        for change in commit.changes {
+            let stats = FileStats {
+                score: commit.file_score(change),
+                commits: 1,
+                last_modified: commit.time,
+                churn: change.churn.unwrap_or(0),
+            };
+            self.stats.entry(change.id).or_default().merge(stats);
+        }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        if self.stats.len() < other.stats.len() {
+            return other.merge(self);
+        }
+        self.paths = self.paths.or(other.paths);
+        for (k, v) in other.stats {
+            self.stats.entry(k).or_default().merge(v);
+        }
+        self
+    }
+
+    /// Entry and hash table overhead plus the run's path table, which spilling cannot free
+    fn estimated_memory(&self) -> u64 {
+        const PER_ENTRY_OVERHEAD: usize = mem::size_of::<(RunPathId, FileStats)>() + 16;
+        let paths = self
+            .paths
+            .as_ref()
+            .map_or(0, |paths| paths.estimated_memory());
+        (self.stats.capacity() * PER_ENTRY_OVERHEAD) as u64 + paths
+    }
+
+    /// The stats move out keyed by path, in the shard of [`shard_of_path`]
+    fn spill(&mut self, spill: &mut Spill) -> Result<bool> {
+        for (path, stats) in self.iter() {
+            spill.write(
+                path_bytes(&fold_case(&path)),
+                path_bytes(&path),
+                &stats.to_bytes(),
+            )?;
+        }
+        self.stats.clear();
+        self.stats.shrink_to_fit();
+        Ok(true)
+    }
+
+    /// Merges every shard back in, so the result holds the stats of every file again
+    fn restore(mut self, spill: Spill) -> Result<Self> {
+        SpilledScores(spill).for_each_shard(|_, shard| {
+            self = mem::take(&mut self).merge_paths(shard);
+            Ok(())
+        })?;
+        Ok(self)
+    }
+}
+
+impl ScoreMap {
+    /// Merge `other`, whose run ids belong to another path table, by path
+    fn merge_paths(mut self, other: ScoreMap) -> ScoreMap {
+        for (path, &stats) in other.iter() {
+            self.get_or_insert_with(&path, FileStats::default)
+                .merge(stats);
+        }
+        self
+    }
+}
+
+/// Shard of the spilled stats of `path`; spellings that only differ in case share one, so
+/// [`merge_case_variants`] can run shard by shard
+pub(crate) fn shard_of_path(path: &Path) -> usize {
+    spill::shard_of(path_bytes(&fold_case(path)))
+}
+
+/// Per-file stats of a walk that spilled to disk, reduced one shard at a time
+pub(crate) struct SpilledScores(Spill);
+
+impl SpilledScores {
+    /// Call `f` with the number and the merged stats of each shard in turn; only one shard
+    /// is in memory at a time, with a path table of its own
+    pub(crate) fn for_each_shard(
+        self,
+        mut f: impl FnMut(usize, ScoreMap) -> Result<()>,
+    ) -> Result<()> {
+        self.0.for_each_shard(|shard, entries| {
+            let mut scores = ScoreMap::default();
+            for (path, stats) in entries {
+                let path = path_from_bytes(&path).context("corrupt spilled results")?;
+                let stats = FileStats::from_bytes(&stats).context("corrupt spilled results")?;
+                scores
+                    .get_or_insert_with(&path, FileStats::default)
+                    .merge(stats);
+            }
+            f(shard, scores)
+        })
+    }
+}
+
+/// Per-file stats of a walk, in memory or spilled to disk past the memory limit
+pub(crate) enum Scores {
+    InMemory(ScoreMap),
+    Spilled(SpilledScores),
+}
+
+/// State of one rayon split: the commit being visited plus blob sizes already looked up
+struct WorkerState<A> {
+    acc: A,
+    size_cache: HashMap<Oid, Option<u64>>,
+}
+
+/// Walk the history selected by `options` and fold every counted commit into `A`
+pub(crate) fn run<A: Accumulator + Default>(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+) -> Result<A> {
+    run_with(repo_path, options, A::default)
+}
+
+/// Like [`run`], for accumulators that need per-run state: every partial result starts
+/// from `init()`
+pub(crate) fn run_with<A: Accumulator>(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+    init: impl Fn() -> A + Send + Sync,
+) -> Result<A> {
+    run_observed(repo_path, options, init, |_, _| {})
+}
+
+/// Commits processed so far out of all commits selected for a run
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Progress {
+    pub(crate) processed: usize,
+    pub(crate) total: usize,
+}
+
+/// Like [`run_with`], calling `observe` with the partial result after every wave
+pub(crate) fn run_observed<A: Accumulator>(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+    init: impl Fn() -> A + Send + Sync,
+    observe: impl FnMut(&A, Progress) + Send,
+) -> Result<A> {
+    let repo = open_repo(repo_path)?;
+    let cache = Cache::open(&repo, options)?;
+    run_observed_with_cache(repo_path, options, &cache, init, observe)
+}
+
+/// Like [`run_observed`], with a cache the caller already opened
+pub(crate) fn run_observed_with_cache<A: Accumulator>(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+    cache: &Cache,
+    init: impl Fn() -> A + Send + Sync,
+    observe: impl FnMut(&A, Progress) + Send,
+) -> Result<A> {
+    match walk_history(repo_path, options, cache, None, init, observe)? {
+        (acc, Some(spill)) => options.stats.time(Phase::Reduce, || acc.restore(spill)),
+        (acc, None) => Ok(acc),
+    }
+}
+
+/// Like [`run_observed_with_cache`], over `commits` instead of the commits `options` selects
+pub(crate) fn run_commits_with_cache<A: Accumulator>(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+    cache: &Cache,
+    commits: Vec<Oid>,
+    init: impl Fn() -> A + Send + Sync,
+    observe: impl FnMut(&A, Progress) + Send,
+) -> Result<A> {
+    match walk_history(repo_path, options, cache, Some(commits), init, observe)? {
+        (acc, Some(spill)) => options.stats.time(Phase::Reduce, || acc.restore(spill)),
+        (acc, None) => Ok(acc),
+    }
+}
+
+/// Like [`run_observed_with_cache`] for per-file stats, leaving stats spilled to disk there
+/// to be reduced shard by shard
+pub(crate) fn run_scores(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+    cache: &Cache,
+    observe: impl FnMut(&ScoreMap, Progress) + Send,
+) -> Result<Scores> {
+    match walk_history(repo_path, options, cache, None, ScoreMap::default, observe)? {
+        (mut scores, Some(mut spill)) => {
+            scores.spill(&mut spill)?;
+            Ok(Scores::Spilled(SpilledScores(spill)))
+        }
+        (scores, None) => Ok(Scores::InMemory(scores)),
+    }
+}
+
+/// Commits `options` selects for scoring, newest first: those of the walk, without the
+/// ignored revisions
+pub(crate) fn select_commits(repo: &Repository, options: &AnalyzeOptions) -> Result<Vec<Oid>> {
+    let ignored = revwalk::collect_ignored_revs(repo, options)?;
+    let mut oids = revwalk::collect_commit_ids(repo, options)?;
+    oids.retain(|oid| !ignored.contains(oid));
+    debug!(
+        commits = oids.len(),
+        ignored = ignored.len(),
+        "selected commits"
+    );
+    Ok(oids)
+}
+
+/// Walk the history (or just `commits`) and fold every counted commit into `A`, along with
+/// whatever it spilled
+fn walk_history<A: Accumulator>(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+    cache: &Cache,
+    commits: Option<Vec<Oid>>,
+    init: impl Fn() -> A + Send + Sync,
+    mut observe: impl FnMut(&A, Progress) + Send,
+) -> Result<(A, Option<Spill>)> {
+    let _span = info_span!("analyze", repo = %repo_path.display()).entered();
+    let repo = open_repo(repo_path)?;
+    // Scoring settings of the config file; explicit options win over them
+    let config = config::repo_config(&repo, options)?;
+    let resolved;
+    let options = if config.max_commit_contribution.is_some()
+        || !config.path_weights.is_empty()
+        || !config.commit_type_weights.is_empty()
+    {
+        let mut path_weights = config.path_weights;
+        path_weights.extend(options.path_weights.iter().cloned());
+        let mut commit_type_weights = config.commit_type_weights;
+        commit_type_weights.extend(options.commit_type_weights.clone());
+        resolved = AnalyzeOptions {
+            commit_type_weights,
+            max_commit_contribution: options
+                .max_commit_contribution
+                .or(config.max_commit_contribution),
+            path_weights,
+            ..options.clone()
+        };
+        &resolved
+    } else {
+        options
+    };
+    // Case-insensitive filters compare against folded paths
+    let folded;
+    let options = if options.ignore_case {
+        folded = AnalyzeOptions {
+            paths: options
+                .paths
+                .as_ref()
+                .map(|paths| paths.iter().map(|path| fold_case(path)).collect()),
+            prefix: options.prefix.as_deref().map(fold_case),
+            ..options.clone()
+        };
+        &folded
+    } else {
+        options
+    };
+    if options.write_commit_graph && !commit_graph::has_commit_graph(&repo) {
+        commit_graph::write_commit_graph(&repo)?;
+    }
+    let started = Instant::now();
+    let walk = info_span!("revwalk").entered();
+    let mut oids = match commits {
+        Some(commits) => commits,
+        None => select_commits(&repo, options)?,
+    };
+    drop(walk);
+    options.stats.add_time(Phase::Revwalk, started.elapsed());
+    options.stats.add_commits_walked(oids.len());
+    let now_secs = options.as_of.unwrap_or_else(|| Utc::now().timestamp());
+
+    // Every worker thread keeps one repository handle open
+    let threads = match (options.threads, options.max_open_repos) {
+        (Some(threads), Some(max_open_repos)) => Some(threads.min(max_open_repos)),
+        (threads, max_open_repos) => threads.or(max_open_repos),
+    };
+    let pathspec = Pathspec::new(options);
+    let mode = StaticsMode {
+        repo_path,
+        run: NEXT_RUN.fetch_add(1, Ordering::Relaxed),
+        with_churn: options.churn
+            || (options.min_changed_lines.is_some() && !options.ignore_whitespace),
+        with_significant_churn: options.ignore_whitespace,
+        with_binary: options.skip_binary,
+        // Only the frecency blend penalizes size
+        with_sizes: (options.file_sizes && options.mode == ScoreMode::Frecency)
+            || options.max_file_size.is_some(),
+        split_large_commits: options.split_large_commits,
+        pathspec: pathspec.as_ref(),
+        stats: &options.stats,
+    };
+    run_with_threads(threads, || {
+        if options.dedup_cherry_picks {
+            oids = cherry_pick::dedup_cherry_picks(oids, mode, cache)?;
+            ensure_not_cancelled(options)?;
+        }
+        compute_parallel(&oids, mode, options, now_secs, cache, &init, &mut observe)
+    })?
+}
+
+/// Repository handle (and lazily loaded mailmap) owned by one worker thread for one run
+struct WorkerRepo {
+    run: u64,
+    repo: Repository,
+    mailmap: OnceCell<Option<Mailmap>>,
+    /// Whether each path seen so far is a generated file
+    generated: RefCell<HashMap<PathBuf, bool>>,
+}
+
+impl WorkerRepo {
+    fn mailmap(&self) -> Option<&Mailmap> {
+        self.mailmap
+            .get_or_init(|| self.repo.mailmap().ok())
+            .as_ref()
+    }
+
+    fn is_generated(&self, path: &Path) -> bool {
+        if let Some(&generated) = self.generated.borrow().get(path) {
+            return generated;
+        }
+        let generated = generated::is_generated(&self.repo, path);
+        let mut memo = self.generated.borrow_mut();
+        // Bounded like the path table, for histories with millions of paths
+        if memo.len() >= MAX_GENERATED_MEMO {
+            memo.clear();
+        }
+        memo.insert(path.to_path_buf(), generated);
+        generated
+    }
+}
+
+thread_local! {
+    /// Repository handle reused by a worker thread across the commits of a run
+    static WORKER_REPO: RefCell<Option<Rc<WorkerRepo>>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with this thread's handle for `mode`'s run, opening it on first use. Each run opens
+/// its own handles, as HEAD, the mailmap or the attributes may have changed since the last
+/// one, e.g. in a long-running server. Re-entrant, so a worker that steals nested work while
+/// inside `f` reuses the same handle.
+fn with_worker<T>(mode: StaticsMode, f: impl FnOnce(&WorkerRepo) -> T) -> Result<T> {
+    let worker = WORKER_REPO.with(|slot| -> Result<_> {
+        let mut slot = slot.borrow_mut();
+        match slot.as_ref() {
+            Some(worker) if worker.run == mode.run => Ok(worker.clone()),
+            _ => {
+                let worker = Rc::new(WorkerRepo {
+                    run: mode.run,
+                    repo: open_repo(mode.repo_path)?,
+                    mailmap: OnceCell::new(),
+                    generated: RefCell::default(),
+                });
+                *slot = Some(worker.clone());
+                Ok(worker)
+            }
+        }
+    })?;
+    Ok(f(&worker))
+}
+
+/// Run `f` with this thread's repository handle for `mode`'s run
+pub(crate) fn with_worker_repo<T>(
+    mode: StaticsMode,
+    f: impl FnOnce(&Repository) -> T,
+) -> Result<T> {
+    with_worker(mode, |worker| f(&worker.repo))
+}
+
+/// Parallel walk at commit granularity, so rayon's work stealing evens out histories where
+/// a few commits are far more expensive than the rest. Commits are processed in waves; after
+/// each wave the memory limit is checked (spilling the result so far to disk when allowed)
+/// and the next wave is resized from the measured cost. Each commit is added to an
+/// accumulator of its own, and those are merged into the result in commit order, so the
+/// floating-point sums do not depend on the thread count or the wave sizes.
+fn compute_parallel<A: Accumulator>(
+    oids: &[Oid],
+    mode: StaticsMode,
+    options: &AnalyzeOptions,
+    now_secs: i64,
+    cache: &Cache,
+    init: &(impl Fn() -> A + Sync),
+    observe: &mut impl FnMut(&A, Progress),
+) -> Result<(A, Option<Spill>)> {
+    let workers = rayon::current_num_threads().max(1);
+    let mut chunk_size = options
+        .commits_per_chunk
+        .unwrap_or(INITIAL_COMMITS_PER_CHUNK)
+        .max(1);
+    let mut acc = init();
+    let mut remaining = oids;
+    let mut spill = None;
+
+    while !remaining.is_empty() {
+        let (wave, rest) = remaining.split_at((chunk_size * workers).min(remaining.len()));
+        let wave_span = debug_span!("wave", commits = wave.len(), chunk_size);
+        let _entered = wave_span.enter();
+        let started = Instant::now();
+        let commits = wave
+            .par_iter()
+            .map_init(
+                || WorkerState {
+                    acc: init(),
+                    size_cache: HashMap::default(),
+                },
+                |state, oid| {
+                    if options.cancellation.is_cancelled() {
+                        return Ok(init());
+                    }
+                    // Worker threads do not inherit the wave's span
+                    let _span = trace_span!(parent: &wave_span, "commit", id = %oid).entered();
+                    with_worker(mode, |worker| {
+                        visit_commit(worker, *oid, mode, options, now_secs, cache, state)
+                    })
+                    .context("failed to re-open the repository")?;
+                    Ok(mem::replace(&mut state.acc, init()))
+                },
+            )
+            .collect::<Result<Vec<_>>>()?;
+        options.stats.add_time(Phase::Diff, started.elapsed());
+        acc = options
+            .stats
+            .time(Phase::Reduce, || commits.into_iter().fold(acc, A::merge));
+        ensure_not_cancelled(options)?;
+        if options.cancellation.is_cancelled() {
+            // Keep what the commits visited so far contributed
+            break;
+        }
+        if let Err(err) = check_memory_limit(&acc, options.max_memory_bytes) {
+            let spill = match (&mut spill, options.spill_to_disk) {
+                (Some(spill), _) => spill,
+                (None, true) => spill.insert(Spill::create()?),
+                (None, false) => return Err(err),
+            };
+            // The progress observer sees the result since the last spill only
+            debug!(
+                estimated_bytes = acc.estimated_memory(),
+                "spilling the aggregated results to disk"
+            );
+            if !acc.spill(spill)? {
+                return Err(err);
+            }
+            // Nothing refers to run ids between waves any more
+            cache.path_table().clear();
+        }
+        remaining = rest;
+        observe(
+            &acc,
+            Progress {
+                processed: oids.len() - remaining.len(),
+                total: oids.len(),
+            },
+        );
+        if options.commits_per_chunk.is_none() {
+            chunk_size = next_chunk_size(wave.len(), workers, started.elapsed());
+        }
+    }
+
+    Ok((acc, spill))
+}
+
+/// Load (or compute) one commit's statistics, then filter & weight them into `state`
+fn visit_commit<A: Accumulator>(
+    worker: &WorkerRepo,
+    oid: Oid,
+    mode: StaticsMode,
+    options: &AnalyzeOptions,
+    now_secs: i64,
+    cache: &Cache,
+    state: &mut WorkerState<A>,
+) {
+    let repo = &worker.repo;
+    let commit = match repo.find_commit(oid) {
+        Ok(c) if c.parent_count() <= 1 => c,
+        Ok(_) => {
+            trace!("skipped merge commit");
+            return;
+        }
+        Err(err) => {
+            trace!("skipped unreadable commit: {err}");
+            return;
+        }
+    };
+    let commit_time = commit.time().seconds();
+    if !options.in_time_window(commit_time) {
+        trace!(time = commit_time, "skipped commit outside the time window");
+        return;
+    }
+    let author = (A::NEEDS_AUTHOR || options.uses_authors())
+        .then(|| authors::commit_author(&commit, worker.mailmap()));
+    let Some(author_weight) = commit_author_weight(&commit, author.as_deref(), options) else {
+        trace!(
+            author = author.as_deref(),
+            "skipped excluded author or message"
+        );
+        return;
+    };
+    let commit_factor = author_weight
+        * conventional::commit_type_weight(&commit, &options.commit_type_weights)
+        * issues::issue_ref_weight(&commit, options);
+
+    let mut statics = get_commit_statistics(repo, oid, cache, &mut state.size_cache, mode);
+    if let Some(max_size) = options.max_file_size {
+        statics
+            .changes
+            .retain(|change| change.size.is_none_or(|size| size <= max_size));
+    }
+    if options.skip_binary {
+        statics.changes.retain(|change| change.binary != Some(true));
+    }
+    if options.default_excludes {
+        statics
+            .changes
+            .retain(|change| !worker.is_generated(&change.path));
+    }
+    if let Some(prefix) = &options.prefix {
+        statics.changes.retain_mut(|change| {
+            let relative = if options.ignore_case {
+                strip_prefix_ignore_case(&change.path, prefix)
+            } else {
+                change.path.strip_prefix(prefix).ok()
+            };
+            match relative {
+                Some(relative) => {
+                    (change.id, change.path) = cache.share_path(relative);
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+    if options.ignore_whitespace || options.min_changed_lines.is_some() {
+        let min_lines = options.min_changed_lines.unwrap_or(1);
+        statics.changes.retain(|change| {
+            let lines = if options.ignore_whitespace {
+                change.significant_churn
+            } else {
+                change.churn
+            };
+            lines.is_none_or(|lines| lines >= min_lines)
+        });
+    }
+    // Entries cached for the whole commit are narrowed to what a restricted diff would see
+    if let Some(paths) = &options.paths {
+        statics.changes.retain(|change| {
+            if options.ignore_case {
+                paths.contains(&fold_case(&change.path))
+            } else {
+                paths.contains(&*change.path)
+            }
+        });
+    }
+    let commit_weight = options.scorer.commit_weight(&CommitMeta {
+        id: oid,
+        time: commit_time,
+        age_days: ((now_secs - commit_time) / 86_400).max(0) as f64,
+        author: author.as_deref(),
+        files_changed: statics.changes.len(),
+    });
+    if !options.file_sizes {
+        // Entries cached by runs with sizes still carry them
+        for change in &mut statics.changes {
+            change.size = None;
+        }
+    }
+
+    let message = A::NEEDS_MESSAGE.then(|| String::from_utf8_lossy(commit.message_bytes()));
+    state.acc.add(&WeightedCommit {
+        id: oid,
+        time: commit_time,
+        weight: commit_factor * commit_weight,
+        commit_factor,
+        author: author.as_deref(),
+        message: message.as_deref(),
+        changes: &statics.changes,
+        paths: cache.path_table(),
+        scorer: options.scorer.as_ref(),
+        path_weights: &options.path_weights,
+        max_contribution: options.max_commit_contribution,
+    });
+}
+
+/// Author-dependent weight multiplier; None when the commit is filtered out
+fn commit_author_weight(
+    commit: &Commit,
+    author: Option<&str>,
+    options: &AnalyzeOptions,
+) -> Option<f64> {
+    if !options.exclude_messages.is_empty() {
+        let message = String::from_utf8_lossy(commit.message_bytes());
+        if options
+            .exclude_messages
+            .iter()
+            .any(|re| re.is_match(&message))
+        {
+            return None;
+        }
+    }
+
+    let Some(author) = author.filter(|_| options.uses_authors()) else {
+        return Some(1.0);
+    };
+    if options.exclude_authors.iter().any(|re| re.is_match(author)) {
+        return None;
+    }
+    authors::author_weight(author, &options.authors, &options.author_boosts)
+}
+
+/// Pick a chunk size from the average per-commit cost observed in the last wave
+fn next_chunk_size(wave_len: usize, workers: usize, elapsed: Duration) -> usize {
+    let commits_per_worker = (wave_len as f64 / workers as f64).max(1.0);
+    let secs_per_commit = elapsed.as_secs_f64() / commits_per_worker;
+    if secs_per_commit <= 0.0 {
+        return MAX_COMMITS_PER_CHUNK;
+    }
+    let ideal = TARGET_CHUNK_TIME.as_secs_f64() / secs_per_commit;
+    (ideal as usize).clamp(MIN_COMMITS_PER_CHUNK, MAX_COMMITS_PER_CHUNK)
+}
+
+/// Run `f` on a dedicated pool of `threads` workers, or on the current pool when no
+/// thread count is requested (or it already matches)
+fn run_with_threads<T: Send>(threads: Option<usize>, f: impl FnOnce() -> T + Send) -> Result<T> {
+    match threads {
+        Some(threads) if threads != rayon::current_num_threads() => {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads.max(1))
+                .build()?;
+            Ok(pool.install(f))
+        }
+        _ => Ok(f()),
+    }
+}
+
+/// Fail once cancelled, unless partial results were asked for
+fn ensure_not_cancelled(options: &AnalyzeOptions) -> Result<()> {
+    if options.cancellation.is_cancelled() && !options.partial_on_cancel {
+        return Err(Cancelled.into());
+    }
+    Ok(())
+}
+
+fn check_memory_limit<A: Accumulator>(acc: &A, limit: Option<u64>) -> Result<()> {
+    let Some(limit) = limit else {
+        return Ok(());
+    };
+    let estimate = acc.estimated_memory();
+    if estimate > limit {
+        bail!(
+            "aggregated results need an estimated {}, above the {} limit; \
+             narrow the analysis with --paths or --max-commits, or raise --max-memory",
+            byte_size(estimate),
+            byte_size(limit)
+        );
+    }
+    Ok(())
+}
+
+/// `bytes` in the largest binary unit that keeps the number at least 1, e.g. `1.5 KiB`
+fn byte_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_sizes_use_readable_units() {
+        assert_eq!(byte_size(0), "0 B");
+        assert_eq!(byte_size(1023), "1023 B");
+        assert_eq!(byte_size(1024), "1.0 KiB");
+        assert_eq!(byte_size(1536), "1.5 KiB");
+        assert_eq!(byte_size(4 << 30), "4.0 GiB");
+        assert_eq!(byte_size(u64::MAX), "16777216.0 TiB");
+    }
+}
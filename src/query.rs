@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobMatcher};
+
+use crate::FileScore;
+
+/// What `frecenfile query` looks for: a glob when the pattern contains `*`, `?`, `[` or `{`,
+/// otherwise a substring of the path, ignoring case unless the pattern has uppercase letters
+#[derive(Debug, Clone)]
+pub enum PathQuery {
+    Glob {
+        matcher: GlobMatcher,
+        /// Globs without a `/` match the file name in any directory, like `.gitignore`
+        match_file_name: bool,
+    },
+    Substring {
+        needle: String,
+        ignore_case: bool,
+    },
+}
+
+impl PathQuery {
+    pub fn new(pattern: &str) -> Result<Self> {
+        if pattern.contains(['*', '?', '[', '{']) {
+            let matcher = GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("invalid glob {pattern:?}"))?
+                .compile_matcher();
+            return Ok(PathQuery::Glob {
+                matcher,
+                match_file_name: !pattern.contains('/'),
+            });
+        }
+        let ignore_case = !pattern.chars().any(char::is_uppercase);
+        Ok(PathQuery::Substring {
+            needle: if ignore_case {
+                pattern.to_lowercase()
+            } else {
+                pattern.to_owned()
+            },
+            ignore_case,
+        })
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        match self {
+            PathQuery::Glob {
+                matcher,
+                match_file_name: true,
+            } => path.file_name().is_some_and(|name| matcher.is_match(name)),
+            PathQuery::Glob { matcher, .. } => matcher.is_match(path),
+            PathQuery::Substring {
+                needle,
+                ignore_case,
+            } => {
+                let path = path.to_string_lossy();
+                if *ignore_case {
+                    path.to_lowercase().contains(needle.as_str())
+                } else {
+                    path.contains(needle.as_str())
+                }
+            }
+        }
+    }
+}
+
+/// The highest-scoring file matching `query`, ties broken by path
+pub fn best_match<'a>(scores: &'a [FileScore], query: &PathQuery) -> Option<&'a FileScore> {
+    scores
+        .iter()
+        .filter(|file| query.matches(&file.path))
+        .min_by(|a, b| a.cmp_hottest_first(b))
+}
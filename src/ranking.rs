@@ -0,0 +1,123 @@
+/// How scores are presented in human-readable output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScoreDisplay {
+    /// The decayed frecency value itself
+    #[default]
+    Raw,
+    /// Share of files scoring at or below this one, 0–100
+    Percentile,
+    /// Position in the ranking, 1 = hottest; ties share a rank
+    Rank,
+    /// Chess-style rating centered on 1500 at the geometric mean score
+    Elo,
+}
+
+impl ScoreDisplay {
+    /// Convert raw scores into displayed values, preserving order
+    pub fn values(self, scores: &[f64]) -> Vec<f64> {
+        match self {
+            ScoreDisplay::Raw => scores.to_vec(),
+            ScoreDisplay::Percentile => percentiles(scores),
+            ScoreDisplay::Rank => ranks(scores).into_iter().map(|r| r as f64).collect(),
+            ScoreDisplay::Elo => elo_ratings(scores),
+        }
+    }
+
+    /// Fractional digits worth printing for this display
+    pub fn precision(self) -> usize {
+        match self {
+            ScoreDisplay::Raw => 4,
+            ScoreDisplay::Percentile => 1,
+            ScoreDisplay::Rank | ScoreDisplay::Elo => 0,
+        }
+    }
+}
+
+/// Competition ranking (1, 2, 2, 4, ...) with the highest score first
+pub fn ranks(scores: &[f64]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+
+    let mut out = vec![0; scores.len()];
+    for (pos, &idx) in order.iter().enumerate() {
+        out[idx] = match pos {
+            0 => 1,
+            _ if scores[order[pos - 1]] == scores[idx] => out[order[pos - 1]],
+            _ => pos + 1,
+        };
+    }
+    out
+}
+
+/// Percentage of scores less than or equal to each score
+pub fn percentiles(scores: &[f64]) -> Vec<f64> {
+    let mut sorted = scores.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let n = sorted.len() as f64;
+    scores
+        .iter()
+        .map(|s| sorted.partition_point(|x| x <= s) as f64 * 100.0 / n)
+        .collect()
+}
+
+/// 1500 + 400·log10(score / geometric mean), so a 10× hotter file is 400 points higher
+pub fn elo_ratings(scores: &[f64]) -> Vec<f64> {
+    let positive: Vec<f64> = scores.iter().copied().filter(|s| *s > 0.0).collect();
+    if positive.is_empty() {
+        return vec![1500.0; scores.len()];
+    }
+    let log_mean = positive.iter().map(|s| s.log10()).sum::<f64>() / positive.len() as f64;
+    let floor = positive.iter().copied().fold(f64::INFINITY, f64::min);
+    scores
+        .iter()
+        .map(|s| 1500.0 + 400.0 * (s.max(floor).log10() - log_mean))
+        .collect()
+}
+
+/// Percentile boundaries splitting files into `count` equally sized tiers, hottest first
+pub fn even_tier_boundaries(count: usize) -> Vec<f64> {
+    (1..count)
+        .map(|k| 100.0 * (count - k) as f64 / count as f64)
+        .collect()
+}
+
+/// Tier of each score, 0 = hottest: files at or above the percentile `boundaries[0]` are in
+/// tier 0, those at or above `boundaries[1]` in tier 1, and so on. Boundaries are descending.
+pub fn tiers(scores: &[f64], boundaries: &[f64]) -> Vec<usize> {
+    percentiles(scores)
+        .into_iter()
+        .map(|percentile| {
+            boundaries
+                .iter()
+                .take_while(|&&boundary| percentile < boundary)
+                .count()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ties_share_a_rank() {
+        assert_eq!(ranks(&[1.0, 3.0, 3.0, 2.0]), [4, 1, 1, 3]);
+        assert_eq!(
+            percentiles(&[1.0, 3.0, 3.0, 2.0]),
+            [25.0, 100.0, 100.0, 50.0]
+        );
+    }
+
+    #[test]
+    fn tiers_start_at_their_boundary_percentile() {
+        assert_eq!(even_tier_boundaries(4), [75.0, 50.0, 25.0]);
+        // Percentiles 100, 75, 50 and 25
+        assert_eq!(tiers(&[4.0, 3.0, 2.0, 1.0], &[75.0, 50.0]), [0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn elo_centers_on_the_geometric_mean() {
+        assert_eq!(elo_ratings(&[100.0, 1.0]), [1900.0, 1100.0]);
+        assert_eq!(elo_ratings(&[0.0, 0.0]), [1500.0, 1500.0]);
+    }
+}
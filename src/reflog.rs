@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use chrono::Utc;
+use git2::{Oid, Repository};
+use rustc_hash::FxHashMap as HashMap;
+
+use crate::AnalyzeOptions;
+use crate::pipeline::scoped_path;
+
+/// Reflog actions that move HEAD without committing; commits are already counted by the walk
+const ACTIONS: [&str; 3] = ["checkout:", "rebase", "reset:"];
+
+/// Weight of each file (relative to `prefix`) that differed between the two sides of a recent
+/// checkout, rebase or reset of HEAD. Every such switch spreads `reflog_weight` over the files
+/// it changed, with the same quadratic recency decay as commits.
+pub(crate) fn reflog_scores(
+    repo: &Repository,
+    options: &AnalyzeOptions,
+) -> Result<HashMap<PathBuf, f64>> {
+    let mut scores = HashMap::default();
+    let Some(weight) = options.reflog_weight.filter(|&weight| weight != 0.0) else {
+        return Ok(scores);
+    };
+    let Ok(reflog) = repo.reflog("HEAD") else {
+        return Ok(scores);
+    };
+
+    let now = options.as_of.unwrap_or_else(|| Utc::now().timestamp());
+    for entry in reflog.iter() {
+        let time = entry.committer().when().seconds();
+        let message = entry.message().unwrap_or_default();
+        if time > now
+            || !options.in_time_window(time)
+            || !ACTIONS.iter().any(|action| message.starts_with(action))
+        {
+            continue;
+        }
+        let files = switched_files(repo, entry.id_old(), entry.id_new())?;
+        if files.is_empty() {
+            continue;
+        }
+        let age_days = ((now - time) / 86_400) as f64;
+        let share = weight / (age_days + 1.0).powi(2) / files.len() as f64;
+        for file in files {
+            if let Some(path) = scoped_path(&file, options) {
+                *scores.entry(path).or_default() += share;
+            }
+        }
+    }
+    Ok(scores)
+}
+
+/// Files that differ between the trees of two commits; empty when either is gone, e.g. after
+/// garbage collection
+fn switched_files(repo: &Repository, old: Oid, new: Oid) -> Result<Vec<PathBuf>> {
+    if old == new || old.is_zero() || new.is_zero() {
+        return Ok(Vec::new());
+    }
+    let trees = repo
+        .find_commit(old)
+        .and_then(|old| old.tree())
+        .and_then(|old| Ok((old, repo.find_commit(new)?.tree()?)));
+    let Ok((old_tree, new_tree)) = trees else {
+        return Ok(Vec::new());
+    };
+    let diff = repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)?;
+    Ok(diff
+        .deltas()
+        .filter_map(|delta| delta.new_file().path().or(delta.old_file().path()))
+        .map(PathBuf::from)
+        .collect())
+}
@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use rustc_hash::FxHashMap as HashMap;
+use serde::Serialize;
+
+use crate::AnalyzeOptions;
+use crate::pipeline::{self, Accumulator, WeightedCommit};
+
+/// A file that tends to change together with a given file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RelatedFile {
+    #[serde(serialize_with = "crate::paths::serialize")]
+    pub path: PathBuf,
+    /// Decayed co-change weight; each commit's weight is split across the other files it
+    /// touched, so sweeping commits count for little
+    pub score: f64,
+    /// Fraction of the given file's decayed changes that also touched this file, 0–1
+    pub confidence: f64,
+    /// Number of counted commits that touched both files
+    pub commits: u32,
+}
+
+/// Co-change weights with one target file
+struct CoChangeMap {
+    target: PathBuf,
+    /// Total decayed weight of the commits touching `target`
+    target_weight: f64,
+    /// Per path: normalized weight, raw weight, commit count
+    related: HashMap<PathBuf, (f64, f64, u32)>,
+}
+
+impl Accumulator for CoChangeMap {
+    fn add(&mut self, commit: &WeightedCommit) {
+        if !commit.changes.iter().any(|c| *c.path == *self.target) {
+            return;
+        }
+        self.target_weight += commit.weight;
+        let others = commit.changes.len() - 1;
+        for change in commit.changes {
+            if *change.path == *self.target {
+                continue;
+            }
+            let entry = self.related.entry(change.path.to_path_buf()).or_default();
+            entry.0 += commit.weight / others as f64;
+            entry.1 += commit.weight;
+            entry.2 += 1;
+        }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        self.target_weight += other.target_weight;
+        for (path, (score, weight, commits)) in other.related {
+            let entry = self.related.entry(path).or_default();
+            entry.0 += score;
+            entry.1 += weight;
+            entry.2 += commits;
+        }
+        self
+    }
+}
+
+/// The `k` files most often changed together with `path` (relative to the repository
+/// root), recency weighted, best match first. `options.paths` must not exclude `path`.
+pub fn related_files(
+    repo_path: &Path,
+    path: &Path,
+    k: usize,
+    options: &AnalyzeOptions,
+) -> Result<Vec<RelatedFile>> {
+    let map = pipeline::run_with(repo_path, options, || CoChangeMap {
+        target: path.to_path_buf(),
+        target_weight: 0.0,
+        related: HashMap::default(),
+    })?;
+
+    let mut related: Vec<RelatedFile> = map
+        .related
+        .into_iter()
+        .map(|(path, (score, weight, commits))| RelatedFile {
+            path,
+            score,
+            confidence: if map.target_weight > 0.0 {
+                weight / map.target_weight
+            } else {
+                0.0
+            },
+            commits,
+        })
+        .collect();
+    related.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    related.truncate(k);
+    Ok(related)
+}
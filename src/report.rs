@@ -0,0 +1,269 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use frecenfile::{AnalysisMetadata, DirectoryScore, FileScore, Hotspot, TrendEntry};
+
+/// Width of the trend bars in Markdown, in characters
+const BAR_WIDTH: usize = 20;
+
+/// Everything shown in a `frecenfile report`
+pub struct Report {
+    pub metadata: AnalysisMetadata,
+    pub files: Vec<FileScore>,
+    pub directories: Vec<DirectoryScore>,
+    pub hotspots: Vec<Hotspot>,
+    pub rising: Vec<TrendEntry>,
+    pub falling: Vec<TrendEntry>,
+    pub window_days: u32,
+}
+
+impl Report {
+    fn title(&self) -> String {
+        let name = self
+            .metadata
+            .repo
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.metadata.repo.display().to_string());
+        format!("Frecency report: {name}")
+    }
+
+    fn summary(&self) -> String {
+        let head = match &self.metadata.head {
+            Some(head) => format!(" up to {}", &head[..head.len().min(12)]),
+            None => String::new(),
+        };
+        format!(
+            "Generated by frecenfile {} from {} commits{head}.",
+            self.metadata.version, self.metadata.commits
+        )
+    }
+
+    /// Largest absolute trend change, for scaling the bars
+    fn max_change(&self) -> f64 {
+        self.rising
+            .iter()
+            .chain(&self.falling)
+            .map(|entry| entry.change().abs())
+            .fold(0.0, f64::max)
+    }
+
+    fn trend_heading(&self) -> String {
+        format!(
+            "Trends (last {} days vs the {} before)",
+            self.window_days, self.window_days
+        )
+    }
+}
+
+pub fn markdown(report: &Report) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# {}\n\n{}\n", report.title(), report.summary());
+
+    let _ = writeln!(out, "## Top files\n");
+    let _ = writeln!(out, "| # | Score | Commits | File |\n|---:|---:|---:|---|");
+    for (rank, file) in report.files.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "| {} | {:.4} | {} | {} |",
+            rank + 1,
+            file.score,
+            file.commits,
+            md_path(&file.path)
+        );
+    }
+
+    let _ = writeln!(out, "\n## Top directories\n");
+    let _ = writeln!(
+        out,
+        "| # | Score | Files | Directory |\n|---:|---:|---:|---|"
+    );
+    for (rank, dir) in report.directories.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "| {} | {:.4} | {} | {} |",
+            rank + 1,
+            dir.score,
+            dir.files,
+            md_path(&dir.path)
+        );
+    }
+
+    let _ = writeln!(out, "\n## Hotspots\n");
+    let _ = writeln!(
+        out,
+        "| # | Hotspot | Score | Lines | File |\n|---:|---:|---:|---:|---|"
+    );
+    for (rank, hotspot) in report.hotspots.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "| {} | {:.4} | {:.4} | {} | {} |",
+            rank + 1,
+            hotspot.hotspot,
+            hotspot.score,
+            hotspot.lines,
+            md_path(&hotspot.path)
+        );
+    }
+
+    let _ = writeln!(out, "\n## {}", report.trend_heading());
+    let max_change = report.max_change();
+    for (title, entries) in [("Rising", &report.rising), ("Falling", &report.falling)] {
+        let _ = writeln!(out, "\n### {title}\n");
+        if entries.is_empty() {
+            let _ = writeln!(out, "_None._");
+            continue;
+        }
+        let _ = writeln!(out, "| Change | | File |\n|---:|---|---|");
+        for entry in entries {
+            let width = bar_fraction(entry.change(), max_change) * BAR_WIDTH as f64;
+            let _ = writeln!(
+                out,
+                "| {:+.4} | `{}` | {} |",
+                entry.change(),
+                "█".repeat((width.round() as usize).max(1)),
+                md_path(&entry.path)
+            );
+        }
+    }
+    out
+}
+
+pub fn html(report: &Report) -> String {
+    let mut out = String::new();
+    let title = escape_html(&report.title());
+    let _ = writeln!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>\n\
+         body {{ font-family: sans-serif; max-width: 60em; margin: 2em auto; }}\n\
+         table {{ border-collapse: collapse; margin-bottom: 1em; }}\n\
+         th, td {{ padding: 0.2em 0.6em; text-align: left; }}\n\
+         td.num {{ text-align: right; font-variant-numeric: tabular-nums; }}\n\
+         tr:nth-child(even) {{ background: #f4f4f4; }}\n\
+         .bar {{ height: 0.8em; }}\n\
+         .rising {{ background: #d9534f; }}\n\
+         .falling {{ background: #5b8dd9; }}\n\
+         </style>\n</head>\n<body>\n<h1>{title}</h1>\n<p>{}</p>",
+        escape_html(&report.summary())
+    );
+
+    let _ = writeln!(out, "<h2>Top files</h2>\n<table>");
+    let _ = writeln!(
+        out,
+        "<tr><th>#</th><th>Score</th><th>Commits</th><th>File</th></tr>"
+    );
+    for (rank, file) in report.files.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "<tr><td class=\"num\">{}</td><td class=\"num\">{:.4}</td><td class=\"num\">{}</td>\
+             <td><code>{}</code></td></tr>",
+            rank + 1,
+            file.score,
+            file.commits,
+            html_path(&file.path)
+        );
+    }
+    let _ = writeln!(out, "</table>");
+
+    let _ = writeln!(out, "<h2>Top directories</h2>\n<table>");
+    let _ = writeln!(
+        out,
+        "<tr><th>#</th><th>Score</th><th>Files</th><th>Directory</th></tr>"
+    );
+    for (rank, dir) in report.directories.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "<tr><td class=\"num\">{}</td><td class=\"num\">{:.4}</td><td class=\"num\">{}</td>\
+             <td><code>{}</code></td></tr>",
+            rank + 1,
+            dir.score,
+            dir.files,
+            html_path(&dir.path)
+        );
+    }
+    let _ = writeln!(out, "</table>");
+
+    let _ = writeln!(out, "<h2>Hotspots</h2>\n<table>");
+    let _ = writeln!(
+        out,
+        "<tr><th>#</th><th>Hotspot</th><th>Score</th><th>Lines</th><th>File</th></tr>"
+    );
+    for (rank, hotspot) in report.hotspots.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "<tr><td class=\"num\">{}</td><td class=\"num\">{:.4}</td><td class=\"num\">{:.4}</td>\
+             <td class=\"num\">{}</td><td><code>{}</code></td></tr>",
+            rank + 1,
+            hotspot.hotspot,
+            hotspot.score,
+            hotspot.lines,
+            html_path(&hotspot.path)
+        );
+    }
+    let _ = writeln!(out, "</table>");
+
+    let _ = writeln!(out, "<h2>{}</h2>", escape_html(&report.trend_heading()));
+    let max_change = report.max_change();
+    for (title, class, entries) in [
+        ("Rising", "rising", &report.rising),
+        ("Falling", "falling", &report.falling),
+    ] {
+        let _ = writeln!(out, "<h3>{title}</h3>");
+        if entries.is_empty() {
+            let _ = writeln!(out, "<p><em>None.</em></p>");
+            continue;
+        }
+        let _ = writeln!(
+            out,
+            "<table>\n<tr><th>Change</th><th></th><th>File</th></tr>"
+        );
+        for entry in entries {
+            let _ = writeln!(
+                out,
+                "<tr><td class=\"num\">{:+.4}</td>\
+                 <td style=\"width: 12em\"><div class=\"bar {class}\" style=\"width: {:.1}%\"></div></td>\
+                 <td><code>{}</code></td></tr>",
+                entry.change(),
+                bar_fraction(entry.change(), max_change) * 100.0,
+                html_path(&entry.path)
+            );
+        }
+        let _ = writeln!(out, "</table>");
+    }
+
+    let _ = writeln!(out, "</body>\n</html>");
+    out
+}
+
+fn bar_fraction(change: f64, max_change: f64) -> f64 {
+    if max_change > 0.0 {
+        change.abs() / max_change
+    } else {
+        0.0
+    }
+}
+
+/// Path as inline code, safe inside a Markdown table cell
+fn md_path(path: &Path) -> String {
+    format!("`{}`", path.display().to_string().replace('|', "\\|"))
+}
+
+fn html_path(path: &Path) -> String {
+    escape_html(&path.display().to_string())
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+use std::fs;
+
+use anyhow::{Context, Result};
+use git2::{Oid, Repository, Revwalk, Sort};
+use tracing::warn;
+
+use crate::commit_graph::{self, CommitGraph};
+use crate::ignore_revs::{self, read_ignore_revs_file};
+use crate::{AnalyzeOptions, read_bare_head_file};
+
+/// Which commits of the history are walked
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Topology {
+    /// Follow only first parents, skipping commits made on merged side branches
+    #[default]
+    FirstParent,
+    /// Every reachable commit, including those on merged side branches
+    Full,
+}
+
+/// Resolve every revision that should be excluded from scoring
+pub(crate) fn collect_ignored_revs(
+    repo: &Repository,
+    options: &AnalyzeOptions,
+) -> Result<HashSet<Oid>> {
+    let mut ignored = HashSet::new();
+    for rev in &options.ignore_revs {
+        ignored.insert(ignore_revs::resolve_rev(repo, rev)?);
+    }
+
+    for file in &options.ignore_revs_files {
+        ignored.extend(read_ignore_revs_file(repo, file)?);
+    }
+
+    // A discovered file may list commits this clone lacks; only explicit ones must resolve
+    if !options.use_blame_ignore_revs {
+        return Ok(ignored);
+    }
+    if let Some(file) = ignore_revs::default_ignore_revs_file(repo) {
+        let contents = fs::read_to_string(&file)
+            .with_context(|| format!("failed to read ignore-revs file {}", file.display()))?;
+        let source = file.display().to_string();
+        ignored.extend(ignore_revs::parse_ignore_revs_lenient(
+            repo, &contents, &source,
+        ));
+    }
+    if let Some(contents) = read_bare_head_file(repo, ignore_revs::BLAME_IGNORE_REVS_FILE) {
+        ignored.extend(ignore_revs::parse_ignore_revs_lenient(
+            repo,
+            &contents,
+            ignore_revs::BLAME_IGNORE_REVS_FILE,
+        ));
+    }
+    Ok(ignored)
+}
+
+/// The commit `rev` resolves to, or HEAD's
+pub(crate) fn start_commit(repo: &Repository, rev: Option<&str>) -> Result<Oid, git2::Error> {
+    match rev {
+        Some(rev) => Ok(repo.revparse_single(rev)?.peel_to_commit()?.id()),
+        None => Ok(repo.head()?.peel_to_commit()?.id()),
+    }
+}
+
+/// Collect commit OIDs (newest first) reachable from `options.rev` (or HEAD) or
+/// `options.ref_globs` but not from the merge base with `options.since_merge_base`, up to
+/// `options.max_commits` made at or before `options.until`. With a commit-graph, the walk
+/// reads parents and times from it instead of inflating every commit, and merges and
+/// commits outside the time window are left out here already.
+pub(crate) fn collect_commit_ids(
+    repo: &Repository,
+    options: &AnalyzeOptions,
+) -> Result<Vec<Oid>, git2::Error> {
+    let start = start_commit(repo, options.rev.as_deref())?;
+    // Parents in the graph may be cut off in a shallow clone
+    let graph = CommitGraph::open(repo).filter(|_| !repo.is_shallow());
+    let first_parent = options.topology == Topology::FirstParent;
+    // The graph walk starts from one commit and hides nothing; other walks go through libgit2
+    let walk: Box<dyn Iterator<Item = Result<Oid, git2::Error>> + '_> = match &graph {
+        Some(graph) if options.ref_globs.is_empty() && options.since_merge_base.is_none() => {
+            Box::new(graph.walk(repo, start, first_parent)?)
+        }
+        _ => Box::new(libgit2_walk(repo, start, options)?),
+    };
+
+    let limit = options.max_commits.unwrap_or(usize::MAX);
+    let mut oids = Vec::with_capacity(limit.min(1024));
+    for oid_res in walk {
+        if oids.len() >= limit {
+            break;
+        }
+        let oid = oid_res?;
+        // Commits after the window must not use up the limit
+        if let Some(until) = options.until
+            && commit_time(repo, graph.as_ref(), oid)? > until
+        {
+            continue;
+        }
+        oids.push(oid);
+    }
+
+    if oids.len() >= commit_graph::HINT_MIN_COMMITS && graph.is_none() {
+        warn!(
+            commits = oids.len(),
+            "walking this many commits is faster with a commit-graph; run `git commit-graph \
+             write --reachable` or pass --write-commit-graph"
+        );
+    }
+
+    // Boundary commits of a shallow clone look like root commits adding every file
+    let boundary = shallow_boundary(repo);
+    oids.retain(|oid| !boundary.contains(oid));
+    // Spare the workers from inflating commits they would skip anyway
+    if let Some(graph) = &graph {
+        oids.retain(|oid| {
+            graph
+                .commit(*oid)
+                .is_none_or(|commit| !commit.is_merge() && options.in_time_window(commit.time))
+        });
+    }
+    Ok(oids)
+}
+
+/// The reference pattern `Revwalk::push_glob` expands `glob` to: under `refs/`, and with
+/// `/*` appended when it has no wildcard
+pub(crate) fn ref_glob_pattern(glob: &str) -> String {
+    let mut pattern = if glob.starts_with("refs/") {
+        glob.to_owned()
+    } else {
+        format!("refs/{}", glob.trim_start_matches('/'))
+    };
+    if !glob.contains(['?', '*', '[']) {
+        pattern.push_str("/*");
+    }
+    pattern
+}
+
+/// libgit2's revwalk over the commits [`collect_commit_ids`] selects, newest first
+fn libgit2_walk<'repo>(
+    repo: &'repo Repository,
+    start: Oid,
+    options: &AnalyzeOptions,
+) -> Result<Revwalk<'repo>, git2::Error> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(start)?;
+    for glob in &options.ref_globs {
+        revwalk.push_glob(glob)?;
+    }
+    if let Some(base) = &options.since_merge_base {
+        let base = repo.revparse_single(base)?.peel_to_commit()?.id();
+        revwalk.hide(repo.merge_base(start, base)?)?;
+    }
+    revwalk.set_sorting(Sort::TIME)?;
+    if options.topology == Topology::FirstParent {
+        revwalk.simplify_first_parent()?;
+    }
+    Ok(revwalk)
+}
+
+/// Committer time of `oid`, from the commit-graph when it has the commit
+fn commit_time(
+    repo: &Repository,
+    graph: Option<&CommitGraph>,
+    oid: Oid,
+) -> Result<i64, git2::Error> {
+    match graph.and_then(|graph| graph.commit(oid)) {
+        Some(commit) => Ok(commit.time),
+        None => Ok(repo.find_commit(oid)?.time().seconds()),
+    }
+}
+
+/// Commits whose parents were cut off by a shallow clone (listed in `$GIT_DIR/shallow`)
+pub(crate) fn shallow_boundary(repo: &Repository) -> HashSet<Oid> {
+    if !repo.is_shallow() {
+        return HashSet::new();
+    }
+    fs::read_to_string(repo.commondir().join("shallow"))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| Oid::from_str(line.trim()).ok())
+        .collect()
+}
+
+/// Every commit reachable from HEAD or any ref
+pub(crate) fn collect_reachable_commits(repo: &Repository) -> Result<HashSet<Oid>, git2::Error> {
+    let mut revwalk = repo.revwalk()?;
+    if repo.head().is_ok() {
+        revwalk.push_head()?;
+    }
+    revwalk.push_glob("*")?;
+    revwalk.collect()
+}
@@ -0,0 +1,93 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use regex::Regex;
+use rustc_hash::FxHashMap as HashMap;
+use serde::Serialize;
+
+use crate::pipeline::{self, Accumulator, WeightedCommit};
+use crate::{AnalyzeOptions, analyze_repo_with_options};
+
+/// Default `fix_pattern`: commit messages mentioning a fix or a bug
+pub const DEFAULT_FIX_PATTERN: &str = r"(?i)\b(fix(e[sd]|ing)?|bug(s|fix(es)?)?|hotfix(es)?)\b";
+
+/// A file with the share of its commits that fixed something
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileRisk {
+    #[serde(serialize_with = "crate::paths::serialize")]
+    pub path: PathBuf,
+    /// Frecency score of the file
+    pub score: f64,
+    pub commits: u32,
+    /// Commits whose message matches the fix pattern
+    pub fixes: u32,
+    /// `fixes / commits`, 0–1
+    pub fix_ratio: f64,
+    /// `score * fix_ratio`: active files that keep needing fixes
+    pub risk: f64,
+}
+
+/// Number of fix commits per path
+struct FixCounts {
+    pattern: Regex,
+    fixes: HashMap<PathBuf, u32>,
+}
+
+impl Accumulator for FixCounts {
+    const NEEDS_MESSAGE: bool = true;
+
+    fn add(&mut self, commit: &WeightedCommit) {
+        if !commit
+            .message
+            .is_some_and(|message| self.pattern.is_match(message))
+        {
+            return;
+        }
+        for change in commit.changes {
+            *self.fixes.entry(change.path.to_path_buf()).or_default() += 1;
+        }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        for (path, fixes) in other.fixes {
+            *self.fixes.entry(path).or_default() += fixes;
+        }
+        self
+    }
+}
+
+/// Classify commits as fixes when their message matches `fix_pattern` and rank files by
+/// frecency times the share of their commits that were fixes, riskiest first
+pub fn analyze_risk(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+    fix_pattern: &Regex,
+) -> Result<Vec<FileRisk>> {
+    let scores = analyze_repo_with_options(repo_path, options)?;
+    let FixCounts { fixes, .. } = pipeline::run_with(repo_path, options, || FixCounts {
+        pattern: fix_pattern.clone(),
+        fixes: HashMap::default(),
+    })?;
+
+    let mut risks: Vec<FileRisk> = scores
+        .into_iter()
+        .map(|file| {
+            let fixes = fixes.get(&file.path).copied().unwrap_or(0);
+            let fix_ratio = if file.commits > 0 {
+                f64::from(fixes) / f64::from(file.commits)
+            } else {
+                0.0
+            };
+            FileRisk {
+                risk: file.score * fix_ratio,
+                path: file.path,
+                score: file.score,
+                commits: file.commits,
+                fixes,
+                fix_ratio,
+            }
+        })
+        .collect();
+    risks.sort_by(|a, b| b.risk.total_cmp(&a.risk).then_with(|| a.path.cmp(&b.path)));
+    Ok(risks)
+}
@@ -0,0 +1,126 @@
+use std::fmt::Debug;
+use std::path::Path;
+
+use git2::Oid;
+
+/// A counted commit, as seen by a [`Scorer`]
+#[derive(Debug, Clone, Copy)]
+pub struct CommitMeta<'a> {
+    pub id: Oid,
+    /// Commit time, Unix seconds
+    pub time: i64,
+    /// Whole days between the commit and the analysis time (`AnalyzeOptions::as_of` or now)
+    pub age_days: f64,
+    /// Mailmap-resolved `Name <email>`; None unless an option or [`Scorer::needs_author`]
+    /// asks for authors
+    pub author: Option<&'a str>,
+    /// Number of analyzed files the commit touched (under `prefix` and among `paths`, if set)
+    pub files_changed: usize,
+}
+
+/// A file changed by a counted commit, as seen by a [`Scorer`]
+#[derive(Debug, Clone, Copy)]
+pub struct FileMeta<'a> {
+    pub path: &'a Path,
+    /// Blob size in bytes after the change; None when `AnalyzeOptions::file_sizes` is off
+    pub size: Option<u64>,
+    /// Lines added plus removed; None unless `AnalyzeOptions::churn` is set
+    pub churn: Option<u64>,
+}
+
+/// Frecency formula: a file's score is the sum over its commits of
+/// `commit_weight * file_weight`, times any author boost
+pub trait Scorer: Debug + Send + Sync {
+    fn commit_weight(&self, commit: &CommitMeta) -> f64;
+
+    fn file_weight(&self, file: &FileMeta) -> f64;
+
+    /// Resolve commit authors even when no author option asks for them
+    fn needs_author(&self) -> bool {
+        false
+    }
+
+    /// Identity and parameters of the formula, equal exactly when two scorers weigh files
+    /// the same way, so a cached score index can be reused; None skips the index
+    fn cache_key(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Quadratic recency decay, `1 / (age_days + 1)^2`, and a penalty for large files, by default
+/// `1 / (1 + sqrt(size_in_kib))`, when sizes are known
+#[derive(Debug, Clone, Copy)]
+pub struct DefaultScorer {
+    pub size_penalty: SizePenalty,
+    /// Sizes are measured in units of this many KiB before the curve is applied
+    pub size_scale_kib: f64,
+}
+
+impl Default for DefaultScorer {
+    fn default() -> Self {
+        Self {
+            size_penalty: SizePenalty::Sqrt,
+            size_scale_kib: 1.0,
+        }
+    }
+}
+
+impl Scorer for DefaultScorer {
+    fn commit_weight(&self, commit: &CommitMeta) -> f64 {
+        1.0 / (commit.age_days + 1.0).powi(2)
+    }
+
+    fn file_weight(&self, file: &FileMeta) -> f64 {
+        file.size.map_or(1.0, |size| {
+            self.size_penalty
+                .weight(size as f64 / 1024.0 / self.size_scale_kib)
+        })
+    }
+
+    fn cache_key(&self) -> Option<String> {
+        let penalty = match self.size_penalty {
+            SizePenalty::None => "none",
+            SizePenalty::Sqrt => "sqrt",
+            SizePenalty::Log => "log",
+            SizePenalty::Linear => "linear",
+        };
+        Some(format!("default:{penalty}:{}", self.size_scale_kib))
+    }
+}
+
+/// How the weight of a change falls off with the file's size `s` (in scaled KiB)
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SizePenalty {
+    /// No penalty
+    None,
+    /// `1 / (1 + sqrt(s))`
+    #[default]
+    Sqrt,
+    /// `1 / (1 + ln(1 + s))`, gentler on large files
+    Log,
+    /// `1 / (1 + s)`, harsher on large files
+    Linear,
+}
+
+impl SizePenalty {
+    fn weight(self, size: f64) -> f64 {
+        match self {
+            SizePenalty::None => 1.0,
+            SizePenalty::Sqrt => 1.0 / (1.0 + size.sqrt()),
+            SizePenalty::Log => 1.0 / (1.0 + size.ln_1p()),
+            SizePenalty::Linear => 1.0 / (1.0 + size),
+        }
+    }
+}
+
+/// What a file's score measures
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ScoreMode {
+    /// The [`Scorer`]'s blend of how often and how recently the file changed
+    #[default]
+    Frecency,
+    /// How recently the file changed: `1 / (days_since_last_change + 1)`
+    Recency,
+    /// How often the file changed: the number of counted commits
+    Frequency,
+}
@@ -0,0 +1,577 @@
+use std::collections::HashSet;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use frecenfile::{
+    Analysis, AnalyzeOptions, ComplexityMetric, FileScore, Hotspot, analyze_contributions,
+    analyze_hotspots, analyze_repo_with_metadata, path_from_bytes, related_files,
+};
+use rustc_hash::FxHashMap as HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::mcp;
+
+const PARSE_ERROR: i32 = -32700;
+pub const METHOD_NOT_FOUND: i32 = -32601;
+pub const INVALID_PARAMS: i32 = -32602;
+/// Analysis failures, e.g. an unknown revision
+pub const ANALYSIS_ERROR: i32 = -32000;
+
+/// How long an HTTP client may take to send its request or read the response, so a stalled
+/// connection cannot hold up the requests queued behind it
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+struct Request {
+    /// Absent for notifications, which get no response
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Serialize)]
+pub struct RpcError {
+    code: i32,
+    message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i32, message: impl ToString) -> Self {
+        RpcError {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct QueryParams {
+    /// Most files to return, hottest first
+    limit: Option<usize>,
+    /// Only files whose path contains this, ignoring case
+    filter: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ExplainParams {
+    path: PathBuf,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+#[derive(Deserialize)]
+struct RelatedParams {
+    path: PathBuf,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+fn default_limit() -> usize {
+    10
+}
+
+/// Scores of one repository, kept warm between requests
+pub struct Server<'a> {
+    repo: &'a Path,
+    options: AnalyzeOptions,
+    analysis: Analysis,
+    /// Hotspots computed since the last refresh, per metric
+    hotspots: Vec<(ComplexityMetric, Vec<Hotspot>)>,
+}
+
+impl<'a> Server<'a> {
+    pub fn new(repo: &'a Path, options: AnalyzeOptions) -> anyhow::Result<Self> {
+        Ok(Server {
+            analysis: hottest_first(repo, &options)?,
+            repo,
+            options,
+            hotspots: Vec::new(),
+        })
+    }
+
+    pub fn repo(&self) -> &Path {
+        self.repo
+    }
+
+    pub fn options(&self) -> &AnalyzeOptions {
+        &self.options
+    }
+
+    pub fn analysis(&self) -> &Analysis {
+        &self.analysis
+    }
+
+    /// Recompute the scores, e.g. after new commits
+    pub fn refresh(&mut self) -> anyhow::Result<()> {
+        self.analysis = hottest_first(self.repo, &self.options)?;
+        self.hotspots.clear();
+        Ok(())
+    }
+
+    /// Files whose path contains `filter` (ignoring case), hottest first
+    pub fn matching(&self, limit: Option<usize>, filter: Option<&str>) -> Vec<&FileScore> {
+        let filter = filter.map(str::to_lowercase);
+        self.analysis
+            .scores
+            .iter()
+            .filter(|file| {
+                filter.as_ref().is_none_or(|filter| {
+                    file.path.to_string_lossy().to_lowercase().contains(filter)
+                })
+            })
+            .take(limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+
+    /// Answer JSON-RPC 2.0 requests read from stdin, one per line, until stdin closes; with
+    /// `mcp`, the methods are those of the Model Context Protocol
+    pub fn serve_stdio(&mut self, mcp: bool) -> anyhow::Result<()> {
+        let stdin = io::stdin().lock();
+        for line in stdin.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.respond(&line, mcp) {
+                let mut stdout = io::stdout().lock();
+                serde_json::to_writer(&mut stdout, &response)?;
+                writeln!(stdout)?;
+                stdout.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Response to one JSON-RPC message; None for notifications
+    fn respond(&mut self, message: &str, mcp: bool) -> Option<Response> {
+        let request: Request = match serde_json::from_str(message) {
+            Ok(request) => request,
+            Err(err) => {
+                return Some(Response {
+                    jsonrpc: "2.0",
+                    id: Value::Null,
+                    result: None,
+                    error: Some(RpcError::new(PARSE_ERROR, err)),
+                });
+            }
+        };
+        let outcome = if mcp {
+            mcp::call(self, &request.method, request.params)
+        } else {
+            self.call(&request.method, request.params)
+        };
+        let id = request.id?;
+        let (result, error) = match outcome {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Some(Response {
+            jsonrpc: "2.0",
+            id,
+            result,
+            error,
+        })
+    }
+
+    fn call(&mut self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "query" => {
+                let params: QueryParams = parse_params(params)?;
+                to_result(&self.matching(params.limit, params.filter.as_deref()))
+            }
+            "explain" => {
+                let params: ExplainParams = parse_params(params)?;
+                let file = self
+                    .analysis
+                    .scores
+                    .iter()
+                    .find(|file| file.path == params.path);
+                let options = AnalyzeOptions {
+                    paths: Some(HashSet::from([params.path.clone()])),
+                    ..self.options.clone()
+                };
+                let mut contributions =
+                    analyze_contributions(self.repo, &options).map_err(analysis_error)?;
+                contributions.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+                contributions.truncate(params.limit);
+                Ok(json!({
+                    "file": to_result(&file)?,
+                    "contributions": to_result(&contributions)?,
+                }))
+            }
+            "related" => {
+                let params: RelatedParams = parse_params(params)?;
+                let related = related_files(self.repo, &params.path, params.limit, &self.options)
+                    .map_err(analysis_error)?;
+                to_result(&related)
+            }
+            "refresh" => {
+                self.refresh().map_err(analysis_error)?;
+                to_result(&self.analysis.metadata)
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method {method}"),
+            )),
+        }
+    }
+}
+
+impl Server<'_> {
+    /// Answer `GET /scores`, `/related/<path>` and `/hotspots` with JSON on `addr`, one
+    /// request at a time. The scores are recomputed when the scored revision moves.
+    pub fn serve_http(&mut self, addr: &str) -> anyhow::Result<()> {
+        let listener =
+            TcpListener::bind(addr).with_context(|| format!("failed to listen on {addr}"))?;
+        eprintln!("Listening on http://{}", listener.local_addr()?);
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            if let Err(err) = self.handle_http(&stream) {
+                eprintln!("Warning: {err:#}");
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_http(&mut self, mut stream: &TcpStream) -> anyhow::Result<()> {
+        stream.set_read_timeout(Some(HTTP_TIMEOUT))?;
+        stream.set_write_timeout(Some(HTTP_TIMEOUT))?;
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // Only GET is served, so neither headers nor a body are needed
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+            header.clear();
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let (status, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some(target)) => match self.route(target) {
+                Ok(body) => (200, body),
+                Err((status, message)) => (status, json!({ "error": message })),
+            },
+            _ => (405, json!({ "error": "only GET requests are served" })),
+        };
+
+        let body = serde_json::to_vec(&body)?;
+        write!(
+            stream,
+            "HTTP/1.1 {status} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n",
+            reason_phrase(status),
+            body.len()
+        )?;
+        stream.write_all(&body)?;
+        Ok(())
+    }
+
+    fn route(&mut self, target: &str) -> Result<Value, (u16, String)> {
+        let internal = |err: anyhow::Error| (500, format!("{err:#}"));
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let params: HashMap<String, String> = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode_query(key), decode_query(value))
+            })
+            .collect();
+        let limit = params
+            .get("limit")
+            .map(|limit| limit.parse::<usize>())
+            .transpose()
+            .map_err(|_| (400, "limit must be a number".to_owned()))?;
+
+        if self.head_moved() {
+            self.refresh().map_err(internal)?;
+        }
+        let value = match path {
+            "/scores" => {
+                serde_json::to_value(self.matching(limit, params.get("filter").map(String::as_str)))
+            }
+            "/hotspots" => {
+                let metric = match params.get("metric").map(String::as_str) {
+                    None | Some("lines") => ComplexityMetric::Lines,
+                    Some("bytes") => ComplexityMetric::Bytes,
+                    Some(other) => return Err((400, format!("unknown metric {other}"))),
+                };
+                if !self.hotspots.iter().any(|(cached, _)| *cached == metric) {
+                    let hotspots =
+                        analyze_hotspots(self.repo, &self.options, metric).map_err(internal)?;
+                    self.hotspots.push((metric, hotspots));
+                }
+                let (_, hotspots) = self
+                    .hotspots
+                    .iter()
+                    .find(|(cached, _)| *cached == metric)
+                    .expect("hotspots were just computed");
+                let shown = limit.unwrap_or(hotspots.len()).min(hotspots.len());
+                serde_json::to_value(&hotspots[..shown])
+            }
+            _ => match path.strip_prefix("/related/") {
+                Some(file) if !file.is_empty() => {
+                    let file = path_from_bytes(&percent_decode(file)).map_err(internal)?;
+                    let related =
+                        related_files(self.repo, &file, limit.unwrap_or(10), &self.options)
+                            .map_err(internal)?;
+                    serde_json::to_value(related)
+                }
+                _ => return Err((404, format!("no such endpoint {path}"))),
+            },
+        };
+        value.map_err(|err| internal(err.into()))
+    }
+
+    /// Whether the scored revision now points to another commit than the one scored
+    fn head_moved(&self) -> bool {
+        crate::watch::head(self.repo, self.options.rev.as_deref()) != self.analysis.metadata.head
+    }
+}
+
+fn hottest_first(repo: &Path, options: &AnalyzeOptions) -> anyhow::Result<Analysis> {
+    let mut analysis = analyze_repo_with_metadata(repo, options)?;
+    analysis.scores.sort_by(FileScore::cmp_hottest_first);
+    Ok(analysis)
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    }
+}
+
+/// `%XX`-escaped bytes of a URL component decoded
+fn percent_decode(component: &str) -> Vec<u8> {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// A query string key or value, where `+` also stands for a space
+fn decode_query(component: &str) -> String {
+    String::from_utf8_lossy(&percent_decode(&component.replace('+', " "))).into_owned()
+}
+
+pub fn parse_params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+    // Methods whose parameters are all optional may be called without any
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err))
+}
+
+fn to_result<T: Serialize + ?Sized>(value: &T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|err| RpcError::new(ANALYSIS_ERROR, err))
+}
+
+pub fn analysis_error(err: anyhow::Error) -> RpcError {
+    RpcError::new(ANALYSIS_ERROR, format!("{err:#}"))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use frecenfile::CacheMode;
+
+    use super::*;
+    use crate::test_support;
+
+    /// A repository in a fresh temporary directory, removed when dropped: `src/main.rs` is
+    /// changed in all three commits, `src/lib.rs` in the first and `docs/a b.md` in the second
+    pub(crate) struct TestRepo(test_support::TestRepo);
+
+    impl TestRepo {
+        pub(crate) fn new() -> Self {
+            let repo = test_support::TestRepo::new();
+            let versions: [&[&str]; 3] = [
+                &["src/main.rs", "src/lib.rs"],
+                &["src/main.rs", "docs/a b.md"],
+                &["src/main.rs"],
+            ];
+            for (version, files) in (1..).zip(versions) {
+                let contents = format!("version {version}\n");
+                let files: Vec<(&str, &[u8])> = files
+                    .iter()
+                    .map(|file| (*file, contents.as_bytes()))
+                    .collect();
+                repo.commit(&files, 1_700_000_000 - (3 - version) * 86_400);
+            }
+            TestRepo(repo)
+        }
+
+        /// A server for the repository, computing everything without a cache
+        pub(crate) fn server(&self) -> Server<'_> {
+            let options = AnalyzeOptions {
+                cache_mode: CacheMode::Disabled,
+                as_of: Some(1_700_000_000),
+                ..AnalyzeOptions::default()
+            };
+            Server::new(self.0.path(), options).unwrap()
+        }
+    }
+
+    /// The `path` fields of a JSON array
+    pub(crate) fn paths(value: &Value) -> Vec<&str> {
+        value
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| file["path"].as_str().unwrap())
+            .collect()
+    }
+
+    /// The JSON response to `message`, if any
+    fn respond(server: &mut Server, message: &str) -> Option<Value> {
+        let response = server.respond(message, false)?;
+        Some(serde_json::to_value(response).unwrap())
+    }
+
+    #[test]
+    fn json_rpc_errors_follow_the_spec() {
+        let repo = TestRepo::new();
+        let mut server = repo.server();
+
+        let response = respond(&mut server, "{not json").unwrap();
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+
+        let response = respond(&mut server, r#"{"id":1,"method":"frobnicate"}"#).unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        assert!(response.get("result").is_none());
+
+        let response = respond(
+            &mut server,
+            r#"{"id":"a","method":"query","params":{"limit":"x"}}"#,
+        )
+        .unwrap();
+        assert_eq!(response["id"], "a");
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+
+        let response = respond(&mut server, r#"{"id":2,"method":"explain","params":{}}"#).unwrap();
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn notifications_get_no_response() {
+        let repo = TestRepo::new();
+        let mut server = repo.server();
+        assert!(respond(&mut server, r#"{"method":"query"}"#).is_none());
+        assert!(respond(&mut server, r#"{"method":"frobnicate"}"#).is_none());
+    }
+
+    #[test]
+    fn query_and_explain_take_their_params() {
+        let repo = TestRepo::new();
+        let mut server = repo.server();
+
+        let response = respond(&mut server, r#"{"id":1,"method":"query"}"#).unwrap();
+        assert_eq!(response["jsonrpc"], "2.0");
+        assert_eq!(
+            paths(&response["result"]),
+            ["src/main.rs", "docs/a b.md", "src/lib.rs"]
+        );
+        let response = respond(
+            &mut server,
+            r#"{"id":2,"method":"query","params":{"limit":1,"filter":"SRC"}}"#,
+        )
+        .unwrap();
+        assert_eq!(paths(&response["result"]), ["src/main.rs"]);
+
+        let response = respond(
+            &mut server,
+            r#"{"id":3,"method":"explain","params":{"path":"src/main.rs","limit":2}}"#,
+        )
+        .unwrap();
+        let result = &response["result"];
+        assert_eq!(result["file"]["path"], "src/main.rs");
+        assert_eq!(result["file"]["commits"], 3);
+        let contributions = result["contributions"].as_array().unwrap();
+        assert_eq!(contributions.len(), 2);
+        assert!(contributions[0]["weight"].as_f64() >= contributions[1]["weight"].as_f64());
+
+        let response = respond(
+            &mut server,
+            r#"{"id":4,"method":"explain","params":{"path":"missing.rs"}}"#,
+        )
+        .unwrap();
+        assert_eq!(response["result"]["file"], Value::Null);
+        assert_eq!(response["result"]["contributions"], json!([]));
+    }
+
+    #[test]
+    fn percent_escapes_are_decoded() {
+        assert_eq!(percent_decode("docs/a%20b.md"), b"docs/a b.md");
+        assert_eq!(percent_decode("%2Fsrc%2f"), b"/src/");
+        assert_eq!(percent_decode("%C3%A9"), "é".as_bytes());
+        // Malformed escapes are kept as they are
+        assert_eq!(percent_decode("100%"), b"100%");
+        assert_eq!(percent_decode("%zz%4"), b"%zz%4");
+        assert_eq!(decode_query("a+b%2Bc"), "a b+c");
+    }
+
+    #[test]
+    fn routes_answer_the_endpoints() {
+        let repo = TestRepo::new();
+        let mut server = repo.server();
+
+        let scores = server.route("/scores").unwrap();
+        assert_eq!(paths(&scores), ["src/main.rs", "docs/a b.md", "src/lib.rs"]);
+        let scores = server.route("/scores?limit=1").unwrap();
+        assert_eq!(paths(&scores), ["src/main.rs"]);
+        let scores = server.route("/scores?filter=LIB").unwrap();
+        assert_eq!(paths(&scores), ["src/lib.rs"]);
+
+        // Only query strings use `+` for spaces
+        let related = server.route("/related/docs%2Fa+b.md").unwrap();
+        assert_eq!(paths(&related), Vec::<&str>::new());
+        let related = server.route("/related/docs/a%20b.md").unwrap();
+        assert_eq!(paths(&related), ["src/main.rs"]);
+
+        let hotspots = server.route("/hotspots?metric=bytes&limit=2").unwrap();
+        assert_eq!(hotspots.as_array().unwrap().len(), 2);
+
+        assert_eq!(server.route("/scores?limit=many").unwrap_err().0, 400);
+        assert_eq!(server.route("/hotspots?metric=words").unwrap_err().0, 400);
+        assert_eq!(server.route("/related/").unwrap_err().0, 404);
+        assert_eq!(server.route("/nothing").unwrap_err().0, 404);
+    }
+}
@@ -0,0 +1,154 @@
+use std::cmp::Ordering;
+use std::{mem, vec};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+use crate::paths::{path_bytes, path_from_bytes};
+use crate::spill::{RunReader, Runs};
+use crate::{FileScore, ScoreBreakdown};
+
+/// Order in which [`analyze_repo_sorted`](crate::analyze_repo_sorted) yields scores
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    /// Highest score first, as [`FileScore::cmp_hottest_first`]
+    #[default]
+    HottestFirst,
+    /// Lowest score first, ties broken by path
+    ColdestFirst,
+}
+
+impl SortOrder {
+    pub fn compare(self, a: &FileScore, b: &FileScore) -> Ordering {
+        match self {
+            SortOrder::HottestFirst => a.cmp_hottest_first(b),
+            SortOrder::ColdestFirst => a
+                .score
+                .total_cmp(&b.score)
+                .then_with(|| a.path.cmp(&b.path)),
+        }
+    }
+}
+
+/// Scores of one analysis in a [`SortOrder`], one at a time. Scores spilled to disk are read
+/// from sorted runs as they are merged, so they are never all in memory.
+pub struct SortedScores(Source);
+
+enum Source {
+    InMemory(vec::IntoIter<FileScore>),
+    Merged {
+        order: SortOrder,
+        readers: Vec<RunReader>,
+        /// Next score of each run; None once it is used up
+        heads: Vec<Option<FileScore>>,
+        /// Holds the runs until the merge is done
+        _dir: TempDir,
+    },
+}
+
+impl SortedScores {
+    pub(crate) fn in_memory(mut scores: Vec<FileScore>, order: SortOrder) -> Self {
+        scores.sort_by(|a, b| order.compare(a, b));
+        SortedScores(Source::InMemory(scores.into_iter()))
+    }
+
+    /// Sort `scores` and write them as the next run of `runs`
+    pub(crate) fn write_run(
+        runs: &mut Runs,
+        mut scores: Vec<FileScore>,
+        order: SortOrder,
+    ) -> Result<()> {
+        scores.sort_by(|a, b| order.compare(a, b));
+        let entries = scores
+            .iter()
+            .map(|file| {
+                Ok((
+                    path_bytes(&file.path),
+                    bincode::serialize(&StoredScore::from(file))?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        runs.write(entries)
+    }
+
+    /// Merge the runs of `runs`, each sorted in `order`
+    pub(crate) fn merged(runs: Runs, order: SortOrder) -> Result<Self> {
+        let (mut readers, dir) = runs.open()?;
+        let heads = readers.iter_mut().map(read_score).collect::<Result<_>>()?;
+        Ok(SortedScores(Source::Merged {
+            order,
+            readers,
+            heads,
+            _dir: dir,
+        }))
+    }
+}
+
+impl Iterator for SortedScores {
+    type Item = Result<FileScore>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            Source::InMemory(scores) => scores.next().map(Ok),
+            Source::Merged {
+                order,
+                readers,
+                heads,
+                ..
+            } => {
+                let (run, _) = heads
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(run, head)| Some((run, head.as_ref()?)))
+                    .min_by(|(_, a), (_, b)| order.compare(a, b))?;
+                let next = match read_score(&mut readers[run]) {
+                    Ok(next) => next,
+                    Err(err) => return Some(Err(err)),
+                };
+                mem::replace(&mut heads[run], next).map(Ok)
+            }
+        }
+    }
+}
+
+/// A [`FileScore`] in a run, without its path, which is the key
+#[derive(Serialize, Deserialize)]
+struct StoredScore {
+    score: f64,
+    commits: u32,
+    last_modified: i64,
+    churn: Option<u64>,
+    breakdown: Option<ScoreBreakdown>,
+    issues: Option<Vec<String>>,
+}
+
+impl From<&FileScore> for StoredScore {
+    fn from(file: &FileScore) -> Self {
+        StoredScore {
+            score: file.score,
+            commits: file.commits,
+            last_modified: file.last_modified,
+            churn: file.churn,
+            breakdown: file.breakdown,
+            issues: file.issues.clone(),
+        }
+    }
+}
+
+/// The next score of a run; None at its end
+fn read_score(reader: &mut RunReader) -> Result<Option<FileScore>> {
+    let Some((path, stored)) = reader.next_entry()? else {
+        return Ok(None);
+    };
+    let stored: StoredScore = bincode::deserialize(&stored).context("corrupt sorted run")?;
+    Ok(Some(FileScore {
+        path: path_from_bytes(&path)?,
+        score: stored.score,
+        commits: stored.commits,
+        last_modified: stored.last_modified,
+        churn: stored.churn,
+        breakdown: stored.breakdown,
+        issues: stored.issues,
+    }))
+}
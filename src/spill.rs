@@ -0,0 +1,164 @@
+use std::fs::File;
+use std::hash::BuildHasher;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use rustc_hash::FxBuildHasher;
+use tempfile::TempDir;
+
+/// Number of files spilled entries are spread over by key, so reading them back buffers the
+/// entries of one shard at a time
+const SHARDS: usize = 64;
+
+/// Shard that entries with `shard_key` are written to
+pub(crate) fn shard_of(shard_key: &[u8]) -> usize {
+    FxBuildHasher.hash_one(shard_key) as usize % SHARDS
+}
+
+/// Partial results moved out of memory during a walk: length-prefixed keys and values in
+/// shard files under a private temporary directory, which is removed when dropped
+pub(crate) struct Spill {
+    dir: TempDir,
+    shards: Vec<BufWriter<File>>,
+}
+
+impl Spill {
+    /// Create the shard files in a fresh directory under the system temporary directory
+    pub(crate) fn create() -> Result<Self> {
+        let dir = temp_dir()?;
+        let mut shards = Vec::with_capacity(SHARDS);
+        for shard in 0..SHARDS {
+            let path = dir.path().join(format!("{shard:02}.spill"));
+            let file = File::create(&path)
+                .with_context(|| format!("failed to create {}", path.display()))?;
+            shards.push(BufWriter::new(file));
+        }
+        Ok(Self { dir, shards })
+    }
+
+    /// Append an entry to the shard of `shard_key`; entries with equal shard keys are read
+    /// back together
+    pub(crate) fn write(&mut self, shard_key: &[u8], key: &[u8], value: &[u8]) -> Result<()> {
+        write_entry(&mut self.shards[shard_of(shard_key)], key, value)
+    }
+
+    /// Call `f` with the number and entries of each shard in turn, in the order they were
+    /// written
+    pub(crate) fn for_each_shard(
+        mut self,
+        mut f: impl FnMut(usize, Vec<(Vec<u8>, Vec<u8>)>) -> Result<()>,
+    ) -> Result<()> {
+        for shard in &mut self.shards {
+            shard.flush().context("failed to write spilled results")?;
+        }
+        for shard in 0..SHARDS {
+            let path = self.dir.path().join(format!("{shard:02}.spill"));
+            let mut reader = RunReader::open(path)?;
+            let mut entries = Vec::new();
+            while let Some(entry) = reader.next_entry()? {
+                entries.push(entry);
+            }
+            f(shard, entries)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sorted runs of entries, written one run at a time and read back side by side, so merging
+/// them only holds the head of each run in memory
+pub(crate) struct Runs {
+    dir: TempDir,
+    count: usize,
+}
+
+impl Runs {
+    /// No runs yet, in a fresh directory under the system temporary directory
+    pub(crate) fn create() -> Result<Self> {
+        Ok(Self {
+            dir: temp_dir()?,
+            count: 0,
+        })
+    }
+
+    /// Write `entries`, which are already in order, as the next run
+    pub(crate) fn write<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        &mut self,
+        entries: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<()> {
+        let path = self.run_path(self.count);
+        let mut writer = BufWriter::new(
+            File::create(&path).with_context(|| format!("failed to create {}", path.display()))?,
+        );
+        for (key, value) in entries {
+            write_entry(&mut writer, key.as_ref(), value.as_ref())?;
+        }
+        writer.flush().context("failed to write a sorted run")?;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// A reader for each run, in the order they were written; the runs are removed once the
+    /// returned directory is dropped
+    pub(crate) fn open(self) -> Result<(Vec<RunReader>, TempDir)> {
+        let readers = (0..self.count)
+            .map(|run| RunReader::open(self.run_path(run)))
+            .collect::<Result<_>>()?;
+        Ok((readers, self.dir))
+    }
+
+    fn run_path(&self, run: usize) -> PathBuf {
+        self.dir.path().join(format!("{run}.run"))
+    }
+}
+
+/// Reads back the entries of one shard or run
+pub(crate) struct RunReader(BufReader<File>);
+
+impl RunReader {
+    fn open(path: PathBuf) -> Result<Self> {
+        let file =
+            File::open(&path).with_context(|| format!("failed to open {}", path.display()))?;
+        Ok(Self(BufReader::new(file)))
+    }
+
+    /// The next key and value; None at the end
+    pub(crate) fn next_entry(&mut self) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        let Some(key) = read_chunk(&mut self.0)? else {
+            return Ok(None);
+        };
+        let value = read_chunk(&mut self.0)?.context("spilled results are truncated")?;
+        Ok(Some((key, value)))
+    }
+}
+
+/// A directory only this process can use, created under a name no other process holds
+fn temp_dir() -> Result<TempDir> {
+    tempfile::Builder::new()
+        .prefix("frecenfile-spill-")
+        .tempdir()
+        .context("failed to create a spill directory")
+}
+
+fn write_entry(writer: &mut impl Write, key: &[u8], value: &[u8]) -> Result<()> {
+    for bytes in [key, value] {
+        writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+        writer.write_all(bytes)?;
+    }
+    Ok(())
+}
+
+/// One length-prefixed chunk; None at the end of the file
+fn read_chunk(reader: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let mut len = [0; 8];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+    let mut chunk = vec![0; u64::from_le_bytes(len) as usize];
+    reader
+        .read_exact(&mut chunk)
+        .context("spilled results are truncated")?;
+    Ok(Some(chunk))
+}
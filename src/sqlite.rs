@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use frecenfile::{AnalysisMetadata, Contribution, FileScore, quote_path};
+use rusqlite::{Connection, params};
+
+const SCHEMA: &str = "
+DROP TABLE IF EXISTS contributions;
+DROP TABLE IF EXISTS scores;
+DROP TABLE IF EXISTS files;
+DROP TABLE IF EXISTS metadata;
+CREATE TABLE metadata (key TEXT PRIMARY KEY, value TEXT);
+CREATE TABLE files (
+    path TEXT PRIMARY KEY,
+    commits INTEGER NOT NULL,
+    last_modified INTEGER NOT NULL,
+    churn INTEGER
+);
+CREATE TABLE scores (
+    path TEXT PRIMARY KEY REFERENCES files (path),
+    score REAL NOT NULL
+);
+CREATE TABLE contributions (
+    commit_id TEXT NOT NULL,
+    time INTEGER NOT NULL,
+    author TEXT NOT NULL,
+    path TEXT NOT NULL,
+    weight REAL NOT NULL
+);
+CREATE INDEX contributions_path ON contributions (path);
+";
+
+/// Write `metadata`, `files`, `scores` and (possibly empty) `contributions` tables to the
+/// SQLite database at `path`, replacing earlier exports. Paths are stored as [`quote_path`]
+/// text, so paths that are not valid UTF-8 stay distinct.
+pub fn export(
+    path: &Path,
+    metadata: &AnalysisMetadata,
+    scores: &[FileScore],
+    contributions: &[Contribution],
+) -> anyhow::Result<()> {
+    let mut conn = Connection::open(path)?;
+    let tx = conn.transaction()?;
+    tx.execute_batch(SCHEMA)?;
+
+    {
+        let mut insert = tx.prepare("INSERT INTO metadata (key, value) VALUES (?1, ?2)")?;
+        let repo = quote_path(&metadata.repo).into_owned();
+        let head = metadata.head.clone().unwrap_or_default();
+        let commits = metadata.commits.to_string();
+        let algorithm_version = metadata.algorithm_version.to_string();
+        let format_version = metadata.format_version.to_string();
+        for (key, value) in [
+            ("repo", &repo),
+            ("head", &head),
+            ("commits", &commits),
+            ("version", &metadata.version),
+            ("algorithm_version", &algorithm_version),
+            ("format_version", &format_version),
+        ] {
+            insert.execute(params![key, value])?;
+        }
+
+        let mut insert_file = tx.prepare(
+            "INSERT INTO files (path, commits, last_modified, churn) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        let mut insert_score = tx.prepare("INSERT INTO scores (path, score) VALUES (?1, ?2)")?;
+        for file in scores {
+            let path = quote_path(&file.path);
+            let churn = file.churn.map(|churn| churn as i64);
+            insert_file.execute(params![path, file.commits, file.last_modified, churn])?;
+            insert_score.execute(params![path, file.score])?;
+        }
+
+        let mut insert_contribution = tx.prepare(
+            "INSERT INTO contributions (commit_id, time, author, path, weight) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for contribution in contributions {
+            insert_contribution.execute(params![
+                contribution.commit,
+                contribution.time,
+                contribution.author,
+                quote_path(&contribution.path),
+                contribution.weight
+            ])?;
+        }
+    }
+
+    tx.commit()?;
+    Ok(())
+}
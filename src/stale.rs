@@ -0,0 +1,84 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::Utc;
+use git2::ObjectType;
+use serde::Serialize;
+
+use crate::hotspot::line_count;
+use crate::{AnalyzeOptions, ComplexityMetric, analyze_repo_with_options, open_repo, revwalk};
+
+/// A file at the analyzed revision that has not changed for a long time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StaleFile {
+    #[serde(serialize_with = "crate::paths::serialize")]
+    pub path: PathBuf,
+    /// Unix timestamp of the last counted change
+    pub last_modified: i64,
+    /// Whole days since the last change, as of `AnalyzeOptions::as_of` or now
+    pub age_days: u64,
+    pub commits: u32,
+    /// Frecency score of the file
+    pub score: f64,
+    /// Line count at the analyzed revision
+    pub lines: u64,
+    /// Size in bytes at the analyzed revision
+    pub bytes: u64,
+}
+
+/// Files at the analyzed revision (`options.rev`, or HEAD) whose last change is at least
+/// `min_age_days` old and whose `metric` is at least `min_size`: candidates for deletion or
+/// archeology. Sorted oldest first.
+pub fn analyze_stale(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+    min_age_days: u64,
+    metric: ComplexityMetric,
+    min_size: u64,
+) -> Result<Vec<StaleFile>> {
+    let now = options.as_of.unwrap_or_else(|| Utc::now().timestamp());
+    let scores = analyze_repo_with_options(repo_path, options)?;
+    let repo = open_repo(repo_path)?;
+    let tree = repo
+        .find_commit(revwalk::start_commit(&repo, options.rev.as_deref())?)?
+        .tree()?;
+    let root = options.prefix.as_deref().unwrap_or(Path::new(""));
+
+    let mut stale: Vec<StaleFile> = scores
+        .into_iter()
+        .filter_map(|file| {
+            let age_days = ((now - file.last_modified).max(0) / 86_400) as u64;
+            if age_days < min_age_days {
+                return None;
+            }
+            let entry = tree.get_path(&root.join(&file.path)).ok()?;
+            if entry.kind() != Some(ObjectType::Blob) {
+                return None;
+            }
+            let blob = repo.find_blob(entry.id()).ok()?;
+            let content = blob.content();
+            let stale = StaleFile {
+                path: file.path,
+                last_modified: file.last_modified,
+                age_days,
+                commits: file.commits,
+                score: file.score,
+                lines: line_count(content),
+                bytes: content.len() as u64,
+            };
+            let size = match metric {
+                ComplexityMetric::Lines => stale.lines,
+                ComplexityMetric::Bytes => stale.bytes,
+            };
+            (size >= min_size).then_some(stale)
+        })
+        .collect();
+
+    stale.sort_by(|a, b| {
+        a.last_modified
+            .cmp(&b.last_modified)
+            .then_with(|| b.bytes.cmp(&a.bytes))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    Ok(stale)
+}
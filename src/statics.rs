@@ -0,0 +1,374 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use git2::{DiffOptions, Oid, Repository};
+use rayon::prelude::*;
+use rustc_hash::FxHashMap as HashMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::AnalyzeOptions;
+use crate::cache::Cache;
+use crate::intern::{PathId, RunPathId};
+use crate::paths::{path_bytes, to_git_path};
+use crate::pipeline::with_worker_repo;
+use crate::stats::RunStats;
+
+/// Commits touching at least this many files look up blob sizes in parallel
+const LARGE_COMMIT_DELTAS: usize = 2000;
+
+/// Static data per commit: the files it touched
+pub(crate) struct CommitStatics {
+    pub(crate) changes: Vec<FileChange>,
+}
+
+/// One file touched by a commit
+pub(crate) struct FileChange {
+    /// Run id of `path`, which results are keyed by
+    pub(crate) id: RunPathId,
+    /// Shared by every change to the same path in a run
+    pub(crate) path: Arc<Path>,
+    /// Blob size after the change, in bytes; only looked up when sizes are requested, and None
+    /// when the blob is missing from a partial clone
+    pub(crate) size: Option<u64>,
+    /// Lines added plus removed; only computed when churn is requested
+    pub(crate) churn: Option<u64>,
+    /// Lines added plus removed, ignoring whitespace; only computed when whitespace-only
+    /// changes are skipped
+    pub(crate) significant_churn: Option<u64>,
+    /// Whether the blob after the change looks binary; only checked when binary files are
+    /// skipped, and None when the blob is missing from a partial clone
+    pub(crate) binary: Option<bool>,
+}
+
+/// [`CommitStatics`] as cached: paths are ids in the cache's path table, so each is stored once
+#[derive(Serialize, Deserialize)]
+struct StoredStatics {
+    changes: Vec<StoredChange>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredChange {
+    path: PathId,
+    size: BlobProperty<u64>,
+    churn: Option<u64>,
+    significant_churn: Option<u64>,
+    binary: BlobProperty<bool>,
+}
+
+/// A cached property of a changed file's blob. A blob missing from a partial clone is
+/// remembered as such, so the commit counts as cached instead of being diffed on every run.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+enum BlobProperty<T> {
+    NotComputed,
+    Unavailable,
+    Known(T),
+}
+
+impl<T> BlobProperty<T> {
+    fn new(computed: bool, value: Option<T>) -> Self {
+        match (computed, value) {
+            (false, _) => BlobProperty::NotComputed,
+            (true, None) => BlobProperty::Unavailable,
+            (true, Some(value)) => BlobProperty::Known(value),
+        }
+    }
+
+    fn is_computed(&self) -> bool {
+        !matches!(self, BlobProperty::NotComputed)
+    }
+
+    fn value(self) -> Option<T> {
+        match self {
+            BlobProperty::Known(value) => Some(value),
+            BlobProperty::NotComputed | BlobProperty::Unavailable => None,
+        }
+    }
+}
+
+impl StoredChange {
+    /// Whether every statistic `mode` asks for was computed
+    fn covers(&self, mode: &StaticsMode) -> bool {
+        (!mode.with_churn || self.churn.is_some())
+            && (!mode.with_significant_churn || self.significant_churn.is_some())
+            && (!mode.with_binary || self.binary.is_computed())
+            && (!mode.with_sizes || self.size.is_computed())
+    }
+}
+
+impl StoredStatics {
+    /// Statistics computed with `mode`; None when a path cannot be added to the path table
+    fn new(statics: &CommitStatics, mode: &StaticsMode, cache: &Cache) -> Option<Self> {
+        let changes = statics
+            .changes
+            .iter()
+            .map(|change| {
+                Some(StoredChange {
+                    path: cache.path_id(&change.path)?,
+                    size: BlobProperty::new(mode.with_sizes, change.size),
+                    churn: change.churn,
+                    significant_churn: change.significant_churn,
+                    binary: BlobProperty::new(mode.with_binary, change.binary),
+                })
+            })
+            .collect::<Option<_>>()?;
+        Some(Self { changes })
+    }
+
+    /// None when a path is missing from the path table
+    fn load(self, cache: &Cache) -> Option<CommitStatics> {
+        let changes = self
+            .changes
+            .into_iter()
+            .map(|change| {
+                let (id, path) = cache.path(change.path)?;
+                Some(FileChange {
+                    id,
+                    path,
+                    size: change.size.value(),
+                    churn: change.churn,
+                    significant_churn: change.significant_churn,
+                    binary: change.binary.value(),
+                })
+            })
+            .collect::<Option<_>>()?;
+        Some(CommitStatics { changes })
+    }
+}
+
+/// How per-commit statistics are computed on a cache miss
+#[derive(Clone, Copy)]
+pub(crate) struct StaticsMode<'a> {
+    pub(crate) repo_path: &'a Path,
+    /// Identifies the run, which worker threads open their own repository handles for
+    pub(crate) run: u64,
+    pub(crate) with_churn: bool,
+    pub(crate) with_significant_churn: bool,
+    pub(crate) with_binary: bool,
+    pub(crate) with_sizes: bool,
+    pub(crate) split_large_commits: bool,
+    /// Only diff these paths
+    pub(crate) pathspec: Option<&'a Pathspec>,
+    pub(crate) stats: &'a RunStats,
+}
+
+/// Paths that diffs are restricted to, so libgit2 skips unrelated deltas
+pub(crate) struct Pathspec {
+    paths: Vec<PathBuf>,
+    ignore_case: bool,
+    /// Identifies the paths in cache keys
+    tag: String,
+}
+
+impl Pathspec {
+    /// The `paths` filter (under `prefix`), or `prefix` alone; None when every path is analyzed
+    pub(crate) fn new(options: &AnalyzeOptions) -> Option<Self> {
+        let prefix = options.prefix.as_deref();
+        let mut paths: Vec<PathBuf> = match (&options.paths, prefix) {
+            (Some(paths), prefix) => paths
+                .iter()
+                .map(|path| prefix.map_or_else(|| path.clone(), |prefix| prefix.join(path)))
+                .map(|path| to_git_path(&path))
+                .collect(),
+            (None, Some(prefix)) => vec![to_git_path(prefix)],
+            (None, None) => return None,
+        };
+        paths.sort();
+
+        let mut hasher = Sha256::new();
+        for path in &paths {
+            hasher.update(path_bytes(path));
+            hasher.update([0]);
+        }
+        if options.ignore_case {
+            hasher.update(b"ignore-case");
+        }
+        let tag = hex::encode(&hasher.finalize()[0..8]);
+        Some(Pathspec {
+            paths,
+            ignore_case: options.ignore_case,
+            tag,
+        })
+    }
+}
+
+pub(crate) fn get_commit_statistics(
+    repo: &Repository,
+    oid: Oid,
+    cache: &Cache,
+    size_cache: &mut HashMap<Oid, Option<u64>>,
+    mode: StaticsMode,
+) -> CommitStatics {
+    let key = oid.to_string();
+    // Restricted diffs are cached separately; an entry for the whole commit serves them too
+    let restricted_key = mode
+        .pathspec
+        .map(|pathspec| format!("pathspec:{}:{oid}", pathspec.tag));
+
+    // Entries that fail to deserialize are corrupt; drop and recompute them
+    let cached = [Some(&key), restricted_key.as_ref()]
+        .into_iter()
+        .flatten()
+        .find_map(|key| {
+            let bytes = cache.get(key)?;
+            let Ok(stored) = bincode::deserialize::<StoredStatics>(&bytes) else {
+                cache.discard(key);
+                return None;
+            };
+            if !stored.changes.iter().all(|change| change.covers(&mode)) {
+                return None;
+            }
+            stored.load(cache)
+        });
+    if let Some(statics) = cached {
+        mode.stats.cache_hit();
+        return statics;
+    }
+    mode.stats.cache_miss();
+    let key = restricted_key.unwrap_or(key);
+
+    let changes =
+        compute_statics_for_commit(repo, oid, cache, size_cache, mode).unwrap_or_default();
+    let statics = CommitStatics { changes };
+    if let Some(stored) = StoredStatics::new(&statics, &mode, cache) {
+        let serialized = bincode::serialize(&stored).expect("serialize statics");
+        cache.insert(&key, &serialized);
+    }
+    statics
+}
+
+/// Compute the static per-file data for all files in a given commit
+fn compute_statics_for_commit(
+    repo: &Repository,
+    oid: Oid,
+    cache: &Cache,
+    size_cache: &mut HashMap<Oid, Option<u64>>,
+    mode: StaticsMode,
+) -> Result<Vec<FileChange>, git2::Error> {
+    let commit = repo.find_commit(oid)?;
+    if commit.parent_count() > 1 {
+        return Ok(Vec::new());
+    }
+    let tree = commit.tree()?;
+
+    let mut diff_opts = DiffOptions::new();
+    diff_opts.context_lines(0);
+    diff_opts.interhunk_lines(0);
+    diff_opts.skip_binary_check(true);
+    diff_opts.include_typechange(false);
+    if let Some(pathspec) = mode.pathspec {
+        for path in &pathspec.paths {
+            diff_opts.pathspec(path);
+        }
+        diff_opts.ignore_case(pathspec.ignore_case);
+    }
+
+    let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut diff_opts))?;
+    // Same trees and pathspec, so the deltas line up with `diff`'s
+    let significant_diff = if mode.with_significant_churn {
+        diff_opts.ignore_whitespace(true);
+        Some(repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut diff_opts))?)
+    } else {
+        None
+    };
+
+    let touched: Vec<(usize, (RunPathId, Arc<Path>), Oid)> = diff
+        .deltas()
+        .enumerate()
+        .filter_map(|(idx, delta)| {
+            let blob_oid = delta.new_file().id();
+            let path = delta.new_file().path()?;
+            (!blob_oid.is_zero()).then(|| (idx, cache.share_path(path), blob_oid))
+        })
+        .collect();
+
+    // Giant commits (imports, vendoring) would otherwise pin a single worker
+    let parallel_sizes =
+        if mode.with_sizes && mode.split_large_commits && touched.len() >= LARGE_COMMIT_DELTAS {
+            touched
+                .par_iter()
+                .map(|(_, _, blob_oid)| {
+                    mode.stats.blob_loaded();
+                    with_worker_repo(mode, |repo| blob_size(repo, *blob_oid))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+                // Looked up on this thread instead
+                .inspect_err(|err| debug!("failed to re-open the repository: {err:#}"))
+                .ok()
+        } else {
+            None
+        };
+    let sizes: Vec<Option<u64>> = if let Some(sizes) = parallel_sizes {
+        sizes
+    } else if !mode.with_sizes {
+        vec![None; touched.len()]
+    } else {
+        touched
+            .iter()
+            .map(|(_, _, blob_oid)| {
+                *size_cache.entry(*blob_oid).or_insert_with(|| {
+                    mode.stats.blob_loaded();
+                    blob_size(repo, *blob_oid)
+                })
+            })
+            .collect()
+    };
+
+    Ok(touched
+        .into_iter()
+        .zip(sizes)
+        .map(|((idx, (id, path), blob_oid), size)| FileChange {
+            id,
+            path,
+            size,
+            binary: mode
+                .with_binary
+                .then(|| {
+                    mode.stats.blob_loaded();
+                    is_binary(repo, blob_oid)
+                })
+                .flatten(),
+            churn: mode.with_churn.then(|| line_churn(&diff, idx).unwrap_or(0)),
+            significant_churn: significant_diff
+                .as_ref()
+                .map(|diff| line_churn(diff, idx).unwrap_or(0)),
+        })
+        .collect())
+}
+
+/// Whether the blob has a NUL byte (or too many non-text bytes) near its start, as git decides
+fn is_binary(repo: &Repository, blob_oid: Oid) -> Option<bool> {
+    repo.find_blob(blob_oid).ok().map(|blob| blob.is_binary())
+}
+
+/// Size from the object header, without inflating the whole blob; None when the blob is
+/// missing, as in blob-filtered (partial) clones, so no size penalty is applied
+fn blob_size(repo: &Repository, blob_oid: Oid) -> Option<u64> {
+    repo.odb()
+        .and_then(|odb| odb.read_header(blob_oid))
+        .map(|(size, _)| size as u64)
+        .ok()
+}
+
+/// Lines added plus removed for the delta at `idx`
+fn line_churn(diff: &git2::Diff, idx: usize) -> Option<u64> {
+    let patch = git2::Patch::from_diff(diff, idx).ok()??;
+    let (_, additions, deletions) = patch.line_stats().ok()?;
+    Some((additions + deletions) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_blobs_count_as_computed() {
+        let missing = BlobProperty::<u64>::new(true, None);
+        assert!(missing.is_computed());
+        assert_eq!(missing.value(), None);
+        assert!(!BlobProperty::<u64>::new(false, None).is_computed());
+        assert_eq!(BlobProperty::new(true, Some(7)).value(), Some(7));
+    }
+}
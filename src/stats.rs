@@ -0,0 +1,134 @@
+use std::fs;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Stage of an analysis whose wall time is recorded in [`RunStats`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Selecting the commits to score
+    Revwalk,
+    /// Diffing the commits (or loading their cached statistics) and scoring them
+    Diff,
+    /// Merging the per-worker results
+    Reduce,
+    /// Ordering the results for output
+    Sort,
+}
+
+/// Counters and phase timings of an analysis, for `--stats`; clones share state
+#[derive(Debug, Clone, Default)]
+pub struct RunStats(Arc<Counters>);
+
+#[derive(Debug, Default)]
+struct Counters {
+    commits_walked: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    index_hits: AtomicU64,
+    blobs_loaded: AtomicU64,
+    /// Nanoseconds per [`Phase`], in declaration order
+    phase_nanos: [AtomicU64; 4],
+}
+
+impl RunStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn add_commits_walked(&self, commits: usize) {
+        self.0
+            .commits_walked
+            .fetch_add(commits as u64, Ordering::Relaxed);
+    }
+
+    /// A commit's statistics were found in the cache
+    pub(crate) fn cache_hit(&self) {
+        self.0.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A commit had to be diffed
+    pub(crate) fn cache_miss(&self) {
+        self.0.cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The cached score index answered the analysis, walking at most the commits that joined
+    /// the history since it was stored
+    pub(crate) fn index_hit(&self) {
+        self.0.index_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A blob (or its header) was read from the object database
+    pub(crate) fn blob_loaded(&self) {
+        self.0.blobs_loaded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_time(&self, phase: Phase, elapsed: Duration) {
+        self.0.phase_nanos[phase as usize].fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Run `f`, adding its wall time to `phase`
+    pub fn time<T>(&self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        self.add_time(phase, started.elapsed());
+        result
+    }
+
+    /// The counts and timings so far, with the peak memory of the process
+    pub fn report(&self) -> StatsReport {
+        let counters = &self.0;
+        let seconds = |phase: Phase| {
+            counters.phase_nanos[phase as usize].load(Ordering::Relaxed) as f64 / 1e9
+        };
+        StatsReport {
+            commits_walked: counters.commits_walked.load(Ordering::Relaxed),
+            cache_hits: counters.cache_hits.load(Ordering::Relaxed),
+            cache_misses: counters.cache_misses.load(Ordering::Relaxed),
+            index_hits: counters.index_hits.load(Ordering::Relaxed),
+            blobs_loaded: counters.blobs_loaded.load(Ordering::Relaxed),
+            revwalk_secs: seconds(Phase::Revwalk),
+            diff_secs: seconds(Phase::Diff),
+            reduce_secs: seconds(Phase::Reduce),
+            sort_secs: seconds(Phase::Sort),
+            peak_memory_bytes: peak_memory_bytes(),
+        }
+    }
+}
+
+/// Snapshot of [`RunStats`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatsReport {
+    /// Commits the history walk handed to the workers
+    pub commits_walked: u64,
+    /// Commits whose statistics came from the cache
+    pub cache_hits: u64,
+    /// Commits that had to be diffed
+    pub cache_misses: u64,
+    /// Analyses answered from the cached score index, which walk only the commits it lacked
+    pub index_hits: u64,
+    /// Blobs (or blob headers) read for sizes and binary detection
+    pub blobs_loaded: u64,
+    pub revwalk_secs: f64,
+    pub diff_secs: f64,
+    pub reduce_secs: f64,
+    pub sort_secs: f64,
+    /// Peak resident memory of the process; None where the OS does not report it
+    pub peak_memory_bytes: Option<u64>,
+}
+
+/// Peak resident set size from `/proc/self/status` (Linux only)
+fn peak_memory_bytes() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kib: u64 = line
+        .trim_start_matches("VmHWM:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kib * 1024)
+}
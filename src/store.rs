@@ -0,0 +1,534 @@
+use std::fmt::{self, Debug};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use rustc_hash::FxHashMap as HashMap;
+use tempfile::TempDir;
+
+/// How often, and how long apart, to retry opening a store locked by another process
+const LOCK_RETRIES: u32 = 3;
+const LOCK_RETRY_DELAY: Duration = Duration::from_millis(20);
+
+/// Callback of [`CommitCache::for_each`]
+pub type EntryVisitor<'a> = dyn FnMut(&[u8], &[u8]) -> Result<()> + 'a;
+
+type Entries = HashMap<Vec<u8>, Vec<u8>>;
+
+/// Value length marking a removed key in a [`FileStore`] log
+const TOMBSTONE: u64 = u64::MAX;
+
+/// Key-value store behind the commit statistics cache. Worker threads use it concurrently,
+/// and every entry is only an optimization: a lost write costs a recomputation.
+pub trait CommitCache: Debug + Send + Sync {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<()>;
+
+    /// Store `value` unless `key` is set; returns the value already there, if any
+    fn insert_if_absent(&self, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Atomically add one to the little-endian `u64` counter at `key` (0 when unset) and
+    /// return the new count
+    fn increment(&self, key: &[u8]) -> Result<u64>;
+
+    fn remove(&self, key: &[u8]) -> Result<()>;
+
+    /// Call `f` with every entry, in no particular order
+    fn for_each(&self, f: &mut EntryVisitor<'_>) -> Result<()>;
+
+    fn clear(&self) -> Result<()>;
+
+    /// Make the written entries durable
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Approximate bytes used, for `AnalyzeOptions::max_cache_bytes`
+    fn size(&self) -> u64 {
+        0
+    }
+}
+
+fn decode_count(bytes: Option<&[u8]>) -> u64 {
+    bytes
+        .and_then(|bytes| bytes.try_into().ok())
+        .map_or(0, u64::from_le_bytes)
+}
+
+/// A sled database
+pub struct SledStore {
+    db: sled::Db,
+    /// Directory of the copy a snapshot was opened from, removed after the database is closed
+    _snapshot: Option<TempDir>,
+}
+
+impl SledStore {
+    /// Open (or create) the database at `path`. None while another process holds its lock,
+    /// after giving that process a moment to finish.
+    pub fn open(path: &Path) -> Result<Option<Self>> {
+        for attempt in 0..=LOCK_RETRIES {
+            match sled::open(path) {
+                Ok(db) => {
+                    return Ok(Some(Self {
+                        db,
+                        _snapshot: None,
+                    }));
+                }
+                Err(err) if is_sled_lock_error(&err) => {
+                    if attempt < LOCK_RETRIES {
+                        thread::sleep(LOCK_RETRY_DELAY);
+                    }
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Open a private copy of the database at `path`, leaving `path` itself untouched: sled
+    /// cannot open a database read-only. The copy is taken while holding the database's lock,
+    /// so no writer is halfway through a change. None while another process holds the lock,
+    /// after giving that process a moment to finish.
+    pub fn open_snapshot(path: &Path) -> Result<Option<Self>> {
+        // The lock sled itself takes when opening a database
+        let lock_path = path.join("db");
+        let lock = File::open(&lock_path)
+            .with_context(|| format!("failed to open {}", lock_path.display()))?;
+        for attempt in 0..=LOCK_RETRIES {
+            match lock.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) if attempt < LOCK_RETRIES => {
+                    thread::sleep(LOCK_RETRY_DELAY);
+                }
+                Err(TryLockError::WouldBlock) => return Ok(None),
+                Err(TryLockError::Error(err)) => {
+                    return Err(err).with_context(|| format!("failed to lock {}", path.display()));
+                }
+            }
+        }
+        let snapshot = tempfile::Builder::new()
+            .prefix("frecenfile-snapshot-")
+            .tempdir()
+            .context("failed to create a snapshot directory")?;
+        copy_dir(path, snapshot.path())
+            .with_context(|| format!("failed to copy {}", path.display()))?;
+        drop(lock);
+        Ok(Some(Self {
+            db: sled::open(snapshot.path())?,
+            _snapshot: Some(snapshot),
+        }))
+    }
+}
+
+/// Copy the files below `from` into the existing directory `to`
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            fs::create_dir(&target)?;
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// sled reports a DB locked by another process as an I/O error
+fn is_sled_lock_error(err: &sled::Error) -> bool {
+    match err {
+        sled::Error::Io(io) => {
+            io.kind() == io::ErrorKind::WouldBlock || io.to_string().contains("lock")
+        }
+        _ => false,
+    }
+}
+
+impl Debug for SledStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SledStore").finish_non_exhaustive()
+    }
+}
+
+impl CommitCache for SledStore {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.db.get(key)?.map(|value| value.to_vec()))
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.db.insert(key, value)?;
+        Ok(())
+    }
+
+    fn insert_if_absent(&self, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(
+            match self.db.compare_and_swap(key, None::<&[u8]>, Some(value))? {
+                Ok(()) => None,
+                Err(conflict) => conflict.current.map(|current| current.to_vec()),
+            },
+        )
+    }
+
+    fn increment(&self, key: &[u8]) -> Result<u64> {
+        let count = self.db.update_and_fetch(key, |count| {
+            Some((decode_count(count) + 1).to_le_bytes().to_vec())
+        })?;
+        Ok(decode_count(count.as_deref()))
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<()> {
+        self.db.remove(key)?;
+        Ok(())
+    }
+
+    fn for_each(&self, f: &mut EntryVisitor<'_>) -> Result<()> {
+        for entry in self.db.iter() {
+            let (key, value) = entry?;
+            f(&key, &value)?;
+        }
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.db.clear()?;
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        self.db.size_on_disk().unwrap_or(0)
+    }
+}
+
+/// Entries kept in memory only, e.g. for embedders that run many analyses in one process
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    entries: Mutex<Entries>,
+}
+
+impl MemoryStore {
+    fn entries(&self) -> MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl CommitCache for MemoryStore {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.entries().get(key).cloned())
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.entries().insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn insert_if_absent(&self, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        let mut entries = self.entries();
+        if let Some(current) = entries.get(key) {
+            return Ok(Some(current.clone()));
+        }
+        entries.insert(key.to_vec(), value.to_vec());
+        Ok(None)
+    }
+
+    fn increment(&self, key: &[u8]) -> Result<u64> {
+        let mut entries = self.entries();
+        let count = decode_count(entries.get(key).map(Vec::as_slice)) + 1;
+        entries.insert(key.to_vec(), count.to_le_bytes().to_vec());
+        Ok(count)
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<()> {
+        self.entries().remove(key);
+        Ok(())
+    }
+
+    fn for_each(&self, f: &mut EntryVisitor<'_>) -> Result<()> {
+        for (key, value) in self.entries().iter() {
+            f(key, value)?;
+        }
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.entries().clear();
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        self.entries()
+            .iter()
+            .map(|(key, value)| (key.len() + value.len()) as u64)
+            .sum()
+    }
+}
+
+/// An append-only log of length-prefixed keys and values, loaded into memory when opened.
+/// No background threads; one process at a time holds a lock on the file. A record cut
+/// short by a crash, or garbled, is dropped with the rest of the log on the next open.
+#[derive(Debug)]
+pub struct FileStore {
+    path: PathBuf,
+    state: Mutex<FileState>,
+}
+
+#[derive(Debug)]
+struct FileState {
+    entries: Entries,
+    /// None when opened read-only
+    log: Option<File>,
+}
+
+impl FileStore {
+    /// Open (or create) the log at `path` for writing. None while another process holds
+    /// its lock, after giving that process a moment to finish.
+    pub fn open(path: &Path) -> Result<Option<Self>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        let mut locked = false;
+        for attempt in 0..=LOCK_RETRIES {
+            if file.try_lock().is_ok() {
+                locked = true;
+                break;
+            }
+            if attempt < LOCK_RETRIES {
+                thread::sleep(LOCK_RETRY_DELAY);
+            }
+        }
+        if !locked {
+            return Ok(None);
+        }
+        let (entries, valid_len) = read_log(&file)?;
+        // Drop a torn record so the next append starts at a record boundary
+        if file.metadata()?.len() != valid_len {
+            file.set_len(valid_len)?;
+        }
+        Ok(Some(Self {
+            path: path.to_path_buf(),
+            state: Mutex::new(FileState {
+                entries,
+                log: Some(file),
+            }),
+        }))
+    }
+
+    /// Load the log at `path` without locking or ever writing it
+    pub fn open_read_only(path: &Path) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        let (entries, _) = read_log(&file)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            state: Mutex::new(FileState { entries, log: None }),
+        })
+    }
+
+    fn state(&self) -> MutexGuard<'_, FileState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Append one record with a single write
+    fn append(state: &mut FileState, key: &[u8], value: Option<&[u8]>) -> Result<()> {
+        let Some(log) = &mut state.log else {
+            return Ok(());
+        };
+        let mut record = Vec::with_capacity(16 + key.len() + value.map_or(0, <[u8]>::len));
+        record.extend_from_slice(&(key.len() as u64).to_le_bytes());
+        record.extend_from_slice(key);
+        match value {
+            Some(value) => {
+                record.extend_from_slice(&(value.len() as u64).to_le_bytes());
+                record.extend_from_slice(value);
+            }
+            None => record.extend_from_slice(&TOMBSTONE.to_le_bytes()),
+        }
+        log.write_all(&record)?;
+        Ok(())
+    }
+}
+
+/// The entries of a log and the length of its leading well-formed records. Reading stops at
+/// a torn or garbled record, which is dropped together with everything after it.
+fn read_log(file: &File) -> Result<(Entries, u64)> {
+    let file_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let mut entries = HashMap::default();
+    let mut valid_len = 0;
+    while let Some(key) =
+        read_len(&mut reader).and_then(|len| read_bytes(&mut reader, len, file_len))
+    {
+        let Some(len) = read_len(&mut reader) else {
+            break;
+        };
+        if len == TOMBSTONE {
+            entries.remove(&key);
+            valid_len += 16 + key.len() as u64;
+            continue;
+        }
+        let Some(value) = read_bytes(&mut reader, len, file_len) else {
+            break;
+        };
+        valid_len += 16 + key.len() as u64 + len;
+        entries.insert(key, value);
+    }
+    Ok((entries, valid_len))
+}
+
+fn read_len(reader: &mut impl Read) -> Option<u64> {
+    let mut len = [0; 8];
+    reader.read_exact(&mut len).ok()?;
+    Some(u64::from_le_bytes(len))
+}
+
+/// `len` bytes; None when the log ends first, or when `len` cannot be right because it
+/// exceeds the whole log
+fn read_bytes(reader: &mut impl Read, len: u64, file_len: u64) -> Option<Vec<u8>> {
+    if len > file_len {
+        return None;
+    }
+    let mut bytes = vec![0; len as usize];
+    reader.read_exact(&mut bytes).ok()?;
+    Some(bytes)
+}
+
+impl CommitCache for FileStore {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.state().entries.get(key).cloned())
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        let mut state = self.state();
+        Self::append(&mut state, key, Some(value))?;
+        state.entries.insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn insert_if_absent(&self, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        let mut state = self.state();
+        if let Some(current) = state.entries.get(key) {
+            return Ok(Some(current.clone()));
+        }
+        Self::append(&mut state, key, Some(value))?;
+        state.entries.insert(key.to_vec(), value.to_vec());
+        Ok(None)
+    }
+
+    fn increment(&self, key: &[u8]) -> Result<u64> {
+        let mut state = self.state();
+        let count = decode_count(state.entries.get(key).map(Vec::as_slice)) + 1;
+        let value = count.to_le_bytes();
+        Self::append(&mut state, key, Some(&value))?;
+        state.entries.insert(key.to_vec(), value.to_vec());
+        Ok(count)
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<()> {
+        let mut state = self.state();
+        if state.entries.remove(key).is_some() {
+            Self::append(&mut state, key, None)?;
+        }
+        Ok(())
+    }
+
+    fn for_each(&self, f: &mut EntryVisitor<'_>) -> Result<()> {
+        for (key, value) in self.state().entries.iter() {
+            f(key, value)?;
+        }
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        let mut state = self.state();
+        if let Some(log) = &state.log {
+            log.set_len(0)
+                .with_context(|| format!("failed to truncate {}", self.path.display()))?;
+        }
+        state.entries.clear();
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<()> {
+        if let Some(log) = &self.state().log {
+            log.sync_data()?;
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        fs::metadata(&self.path).map_or(0, |metadata| metadata.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    #[test]
+    fn file_store_recovers_from_a_torn_record() {
+        let dir = temp_dir();
+        let path = dir.path().join("cache.log");
+        {
+            let store = FileStore::open(&path).unwrap().unwrap();
+            store.insert(b"kept", b"value").unwrap();
+            store.insert(b"removed", b"value").unwrap();
+            store.remove(b"removed").unwrap();
+            store.flush().unwrap();
+        }
+        let valid_len = fs::metadata(&path).unwrap().len();
+        // A writer that died halfway through a record: a key length with no key after it
+        let mut log = OpenOptions::new().append(true).open(&path).unwrap();
+        log.write_all(&5u64.to_le_bytes()).unwrap();
+        drop(log);
+
+        let read_only = FileStore::open_read_only(&path).unwrap();
+        assert_eq!(read_only.get(b"kept").unwrap(), Some(b"value".to_vec()));
+        assert_eq!(read_only.get(b"removed").unwrap(), None);
+
+        {
+            let store = FileStore::open(&path).unwrap().unwrap();
+            assert_eq!(fs::metadata(&path).unwrap().len(), valid_len);
+            store.insert(b"added", b"value").unwrap();
+            store.flush().unwrap();
+        }
+        let store = FileStore::open_read_only(&path).unwrap();
+        assert_eq!(store.get(b"kept").unwrap(), Some(b"value".to_vec()));
+        assert_eq!(store.get(b"added").unwrap(), Some(b"value".to_vec()));
+    }
+
+    #[test]
+    fn sled_snapshots_leave_the_database_untouched() {
+        let dir = temp_dir();
+        let path = dir.path().join("cache.sled");
+        {
+            let store = SledStore::open(&path).unwrap().unwrap();
+            store.insert(b"kept", b"value").unwrap();
+            store.flush().unwrap();
+        }
+
+        let snapshot = SledStore::open_snapshot(&path).unwrap().unwrap();
+        assert_eq!(snapshot.get(b"kept").unwrap(), Some(b"value".to_vec()));
+        snapshot.insert(b"added", b"value").unwrap();
+        snapshot.flush().unwrap();
+        drop(snapshot);
+
+        // A writer holds the lock, so no snapshot can be taken meanwhile
+        let store = SledStore::open(&path).unwrap().unwrap();
+        assert_eq!(store.get(b"added").unwrap(), None);
+        assert!(SledStore::open_snapshot(&path).unwrap().is_none());
+    }
+}
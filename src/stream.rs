@@ -0,0 +1,111 @@
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TrySendError};
+use std::thread;
+
+use anyhow::Result;
+
+use crate::pipeline::{self, ScoreMap};
+use crate::{
+    AnalyzeOptions, FileScore, access, file_score, open_repo, reflog, score_adjustments, worktree,
+};
+
+/// Scores over the commits processed so far
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialScores {
+    pub scores: Vec<FileScore>,
+    pub commits_processed: usize,
+    pub commits_total: usize,
+}
+
+impl PartialScores {
+    /// Whether every selected commit has been counted
+    pub fn is_complete(&self) -> bool {
+        self.commits_processed == self.commits_total
+    }
+}
+
+/// Iterator over increasingly complete scores, ending with the final result (or an error)
+pub struct ScoreStream {
+    receiver: Receiver<Result<PartialScores>>,
+}
+
+impl Iterator for ScoreStream {
+    type Item = Result<PartialScores>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// Analyze on a background thread, yielding partial scores as the history is processed so
+/// consumers can render early candidates. Partial results the consumer has not picked up
+/// yet are skipped rather than queued; the final, complete result is always delivered.
+pub fn analyze_repo_iter(repo_path: &Path, options: &AnalyzeOptions) -> ScoreStream {
+    let (sender, receiver) = mpsc::sync_channel(1);
+    let repo_path = repo_path.to_path_buf();
+    let options = options.clone();
+
+    thread::spawn(move || {
+        let result = stream_scores(&repo_path, &options, |partial| {
+            match sender.try_send(Ok(partial)) {
+                Ok(()) | Err(TrySendError::Full(_)) => true,
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        });
+        let _ = sender.send(result);
+    });
+
+    ScoreStream { receiver }
+}
+
+/// Run the analysis, passing every partial result to `emit`, and return the final one
+fn stream_scores(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+    mut emit: impl FnMut(PartialScores) -> bool + Send,
+) -> Result<PartialScores> {
+    let adjustments = score_adjustments(repo_path, options)?;
+    let repo = open_repo(repo_path)?;
+    let mut access = access::access_scores(&repo, options)?;
+    for (path, weight) in reflog::reflog_scores(&repo, options)? {
+        *access.entry(path).or_default() += weight;
+    }
+    let worktree_changes = worktree::changed_files(&repo, options)?;
+    let to_scores = |scores: &ScoreMap| -> Vec<FileScore> {
+        let mut scores = Cow::Borrowed(scores);
+        if options.ignore_case {
+            scores = Cow::Owned(pipeline::merge_case_variants(scores.into_owned()));
+        }
+        if !access.is_empty() {
+            access::blend(scores.to_mut(), &access);
+        }
+        if !worktree_changes.is_empty() {
+            worktree::blend(scores.to_mut(), &worktree_changes, options);
+        }
+        scores
+            .iter()
+            .map(|(path, stats)| file_score(path.to_path_buf(), stats, options, &adjustments))
+            .collect()
+    };
+
+    let mut total = 0;
+    let mut listening = true;
+    let scores =
+        pipeline::run_observed(repo_path, options, ScoreMap::default, |scores, progress| {
+            total = progress.total;
+            if listening && progress.processed < progress.total {
+                listening = emit(PartialScores {
+                    scores: to_scores(scores),
+                    commits_processed: progress.processed,
+                    commits_total: progress.total,
+                });
+            }
+        })?;
+
+    Ok(PartialScores {
+        scores: to_scores(&scores),
+        commits_processed: total,
+        commits_total: total,
+    })
+}
@@ -0,0 +1,65 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use git2::Repository;
+
+use crate::{AnalyzeOptions, FileScore, ScoreAdjustment, analyze_repo_with_metadata, config};
+
+/// Scores of the files of every initialized submodule (recursively), with paths prefixed by
+/// the submodule path, and the number of commits scored across them
+pub(crate) fn submodule_scores(
+    repo: &Repository,
+    options: &AnalyzeOptions,
+    adjustments: &[ScoreAdjustment],
+) -> Result<(Vec<FileScore>, usize)> {
+    let Some(workdir) = repo.workdir() else {
+        return Ok((Vec::new(), 0));
+    };
+
+    let mut scores = Vec::new();
+    let mut commits = 0;
+    for submodule in repo.submodules()? {
+        // Uninitialized submodules have no repository to analyze
+        if submodule.open().is_err() {
+            continue;
+        }
+        let prefix = submodule.path().to_path_buf();
+        let paths: Option<HashSet<PathBuf>> = options.paths.as_ref().map(|paths| {
+            paths
+                .iter()
+                .filter_map(|path| path.strip_prefix(&prefix).ok())
+                .map(|path| path.to_path_buf())
+                .collect()
+        });
+        if paths.as_ref().is_some_and(HashSet::is_empty) {
+            continue;
+        }
+
+        // Revisions name superproject commits; the superproject's adjustments are applied to
+        // the prefixed paths below
+        let analysis = analyze_repo_with_metadata(
+            &workdir.join(&prefix),
+            &AnalyzeOptions {
+                paths,
+                rev: None,
+                since_merge_base: None,
+                ignore_revs: Vec::new(),
+                ignore_revs_files: Vec::new(),
+                score_adjustments: Vec::new(),
+                use_config_file: false,
+                ..options.clone()
+            },
+        )?;
+        commits += analysis.metadata.commits;
+        scores.extend(analysis.scores.into_iter().map(|file| {
+            let path = prefix.join(&file.path);
+            FileScore {
+                score: config::adjust_score(adjustments, &path, file.score),
+                path,
+                ..file
+            }
+        }));
+    }
+    Ok((scores, commits))
+}
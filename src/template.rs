@@ -0,0 +1,223 @@
+use std::str::FromStr;
+
+use frecenfile::{FileScore, NumberFormat, path_bytes};
+
+/// A `--format-string` line template, e.g. `{rank}\t{score:.2}\t{path}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Field(Field, Option<usize>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Rank,
+    Score,
+    Path,
+    LastModified,
+    Commits,
+    Churn,
+}
+
+/// Values available to a template for one file
+pub struct Row<'a> {
+    pub file: &'a FileScore,
+    /// 1-based position in the output
+    pub rank: usize,
+    /// Score as selected by `--score-display`
+    pub value: f64,
+    /// Decimals used for `{score}` without an explicit precision
+    pub precision: usize,
+    pub number_format: &'a NumberFormat,
+}
+
+impl Template {
+    /// The line for `row`, without its newline; `{path}` is the raw path bytes
+    pub fn render(&self, row: &Row) -> Vec<u8> {
+        let mut line = Vec::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => line.extend_from_slice(text.as_bytes()),
+                Part::Field(field, precision) => match field {
+                    Field::Rank => line.extend_from_slice(row.rank.to_string().as_bytes()),
+                    Field::Score => line.extend_from_slice(
+                        row.number_format
+                            .format(row.value, precision.unwrap_or(row.precision))
+                            .as_bytes(),
+                    ),
+                    Field::Path => line.extend_from_slice(path_bytes(&row.file.path)),
+                    Field::LastModified => {
+                        line.extend_from_slice(row.file.last_modified.to_string().as_bytes());
+                    }
+                    Field::Commits => {
+                        line.extend_from_slice(row.file.commits.to_string().as_bytes())
+                    }
+                    Field::Churn => {
+                        if let Some(churn) = row.file.churn {
+                            line.extend_from_slice(churn.to_string().as_bytes());
+                        }
+                    }
+                },
+            }
+        }
+        line
+    }
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    /// Placeholders: `{rank}`, `{score}` (optionally `{score:.N}`), `{path}`,
+    /// `{last_modified}`, `{commits}` and `{churn}`; `{{`/`}}` are literal braces, and
+    /// `\t`, `\n` and `\\` are escapes
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '\\' => match chars.next() {
+                    Some('t') => literal.push('\t'),
+                    Some('n') => literal.push('\n'),
+                    Some('\\') => literal.push('\\'),
+                    Some(other) => {
+                        literal.push('\\');
+                        literal.push(other);
+                    }
+                    None => literal.push('\\'),
+                },
+                '{' => {
+                    let spec: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(parse_placeholder(&spec)?);
+                }
+                '}' => return Err("unmatched `}` in format string; use `}}`".to_owned()),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Template { parts })
+    }
+}
+
+fn parse_placeholder(spec: &str) -> Result<Part, String> {
+    let (name, precision) = match spec.split_once(':') {
+        Some((name, format)) => {
+            let precision = format
+                .strip_prefix('.')
+                .and_then(|digits| digits.parse().ok())
+                .ok_or_else(|| format!("invalid format {format:?} in {{{spec}}}; expected .N"))?;
+            (name, Some(precision))
+        }
+        None => (spec, None),
+    };
+    let field = match name {
+        "rank" => Field::Rank,
+        "score" => Field::Score,
+        "path" => Field::Path,
+        "last_modified" => Field::LastModified,
+        "commits" => Field::Commits,
+        "churn" => Field::Churn,
+        _ => {
+            return Err(format!(
+                "unknown placeholder {{{name}}}; expected rank, score, path, last_modified, \
+                 commits or churn"
+            ));
+        }
+    };
+    if precision.is_some() && field != Field::Score {
+        return Err(format!("only {{score}} takes a precision, not {{{name}}}"));
+    }
+    Ok(Part::Field(field, precision))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn render(template: &str, file: &FileScore) -> String {
+        let template: Template = template.parse().unwrap();
+        let line = template.render(&Row {
+            file,
+            rank: 3,
+            value: 1234.5678,
+            precision: 4,
+            number_format: &NumberFormat::for_locale("de_DE"),
+        });
+        String::from_utf8(line).unwrap()
+    }
+
+    #[test]
+    fn renders_placeholders_and_escapes() {
+        let file = FileScore {
+            path: PathBuf::from("src/lib.rs"),
+            score: 1234.5678,
+            commits: 7,
+            last_modified: 1_700_000_000,
+            churn: None,
+            breakdown: None,
+            issues: None,
+        };
+        assert_eq!(
+            render("{rank}\\t{score:.1}\\t{path}", &file),
+            "3\t1.234,6\tsrc/lib.rs"
+        );
+        assert_eq!(
+            render("{{{commits}}} {last_modified}{churn}\\\\", &file),
+            "{7} 1700000000\\"
+        );
+        assert_eq!(render("{score}", &file), "1.234,5678");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn renders_raw_path_bytes() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let file = FileScore {
+            path: PathBuf::from(std::ffi::OsStr::from_bytes(b"a\xff")),
+            score: 1.0,
+            commits: 1,
+            last_modified: 0,
+            churn: None,
+            breakdown: None,
+            issues: None,
+        };
+        let template: Template = "{path}!".parse().unwrap();
+        let line = template.render(&Row {
+            file: &file,
+            rank: 1,
+            value: 1.0,
+            precision: 4,
+            number_format: &NumberFormat::default(),
+        });
+        assert_eq!(line, b"a\xff!");
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for template in ["{size}", "{path:.2}", "{score:2}", "score}"] {
+            assert!(template.parse::<Template>().is_err(), "{template}");
+        }
+    }
+}
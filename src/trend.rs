@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::Utc;
+use rustc_hash::FxHashMap as HashMap;
+use serde::Serialize;
+
+use crate::{AnalyzeOptions, analyze_repo_with_options};
+
+/// Score of one file in the current window compared to the window before it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrendEntry {
+    #[serde(serialize_with = "crate::paths::serialize")]
+    pub path: PathBuf,
+    /// Score over the window immediately before the current one, as of that window's end
+    pub previous: f64,
+    /// Score over the current window
+    pub current: f64,
+}
+
+impl TrendEntry {
+    /// Absolute score difference; positive when the file is heating up
+    pub fn change(&self) -> f64 {
+        self.current - self.previous
+    }
+}
+
+/// Score two consecutive windows of `window_secs` each, ending at `options.as_of` (or now),
+/// and return every file active in either, sorted from fastest rising to fastest falling
+pub fn analyze_trend(
+    repo_path: &Path,
+    options: &AnalyzeOptions,
+    window_secs: i64,
+) -> Result<Vec<TrendEntry>> {
+    let now = options.as_of.unwrap_or_else(|| Utc::now().timestamp());
+    let boundary = now - window_secs;
+
+    let current = analyze_repo_with_options(
+        repo_path,
+        &AnalyzeOptions {
+            since: Some(boundary),
+            until: Some(now),
+            as_of: Some(now),
+            ..options.clone()
+        },
+    )?;
+    let previous = analyze_repo_with_options(
+        repo_path,
+        &AnalyzeOptions {
+            since: Some(boundary - window_secs),
+            until: Some(boundary),
+            as_of: Some(boundary),
+            ..options.clone()
+        },
+    )?;
+
+    let mut entries: HashMap<PathBuf, TrendEntry> = HashMap::default();
+    for file in previous {
+        entries.insert(
+            file.path.clone(),
+            TrendEntry {
+                path: file.path,
+                previous: file.score,
+                current: 0.0,
+            },
+        );
+    }
+    for file in current {
+        entries
+            .entry(file.path.clone())
+            .or_insert_with(|| TrendEntry {
+                path: file.path,
+                previous: 0.0,
+                current: 0.0,
+            })
+            .current = file.score;
+    }
+
+    let mut entries: Vec<TrendEntry> = entries.into_values().collect();
+    entries.sort_by(|a, b| {
+        b.change()
+            .total_cmp(&a.change())
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    Ok(entries)
+}
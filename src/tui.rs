@@ -0,0 +1,203 @@
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use frecenfile::FileScore;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+/// Width of the score bar in front of each path
+const BAR_WIDTH: usize = 10;
+/// At most this much of the selected file is read for the preview pane
+const PREVIEW_BYTES: u64 = 64 * 1024;
+/// Rows skipped by PageUp/PageDown
+const PAGE: isize = 10;
+
+/// Browse `files` (hottest first) until the user picks one with Enter, returning its path,
+/// or quits. Files are previewed and opened relative to `root`.
+pub fn run(files: &[FileScore], root: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let mut browser = Browser::new(files, root);
+    let mut terminal = ratatui::init();
+    let result = browser.event_loop(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+struct Browser<'a> {
+    files: &'a [FileScore],
+    root: &'a Path,
+    max_score: f64,
+    query: String,
+    /// Indices into `files` of the paths matching `query`
+    matches: Vec<usize>,
+    state: ListState,
+}
+
+impl<'a> Browser<'a> {
+    fn new(files: &'a [FileScore], root: &'a Path) -> Self {
+        let mut browser = Browser {
+            files,
+            root,
+            max_score: files.iter().map(|file| file.score).fold(0.0, f64::max),
+            query: String::new(),
+            matches: Vec::new(),
+            state: ListState::default(),
+        };
+        browser.refilter();
+        browser
+    }
+
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> anyhow::Result<Option<PathBuf>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Char('c') if ctrl => return Ok(None),
+                KeyCode::Enter => return Ok(self.selected().map(|file| file.path.clone())),
+                KeyCode::Char('e') if ctrl => {
+                    if let Some(file) = self.selected() {
+                        let path = self.root.join(&file.path);
+                        ratatui::restore();
+                        let opened = open_in_editor(&path);
+                        *terminal = ratatui::init();
+                        opened?;
+                    }
+                }
+                KeyCode::Up => self.move_by(-1),
+                KeyCode::Char('p') if ctrl => self.move_by(-1),
+                KeyCode::Down => self.move_by(1),
+                KeyCode::Char('n') if ctrl => self.move_by(1),
+                KeyCode::PageUp => self.move_by(-PAGE),
+                KeyCode::PageDown => self.move_by(PAGE),
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    self.refilter();
+                }
+                KeyCode::Char(c) if !ctrl => {
+                    self.query.push(c);
+                    self.refilter();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Keep the files whose path contains the query, ignoring case, and select the hottest
+    fn refilter(&mut self) {
+        let query = self.query.to_lowercase();
+        self.matches = (0..self.files.len())
+            .filter(|&index| {
+                let path = self.files[index].path.to_string_lossy();
+                path.to_lowercase().contains(&query)
+            })
+            .collect();
+        self.state.select((!self.matches.is_empty()).then_some(0));
+    }
+
+    fn selected(&self) -> Option<&'a FileScore> {
+        let index = self.matches.get(self.state.selected()?)?;
+        Some(&self.files[*index])
+    }
+
+    fn move_by(&mut self, rows: isize) {
+        if let Some(selected) = self.state.selected() {
+            let last = self.matches.len() - 1;
+            self.state
+                .select(Some(selected.saturating_add_signed(rows).min(last)));
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [prompt, body] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(frame.area());
+        let [list_area, preview_area] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(body);
+
+        let status = format!(
+            "> {}  ({}/{})",
+            self.query,
+            self.matches.len(),
+            self.files.len()
+        );
+        frame.render_widget(Paragraph::new(status), prompt);
+
+        let items: Vec<ListItem> = self
+            .matches
+            .iter()
+            .map(|&index| {
+                let file = &self.files[index];
+                ListItem::new(Line::from(vec![
+                    Span::raw(score_bar(file.score, self.max_score)),
+                    Span::raw(" "),
+                    Span::raw(file.path.to_string_lossy().into_owned()),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title("Enter: print  Ctrl-E: edit  Esc: quit"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.state);
+
+        let preview = Paragraph::new(self.preview()).block(Block::bordered().title("Preview"));
+        frame.render_widget(preview, preview_area);
+    }
+
+    /// The start of the selected file in the work tree
+    fn preview(&self) -> String {
+        let Some(file) = self.selected() else {
+            return String::new();
+        };
+        let mut bytes = Vec::new();
+        let read = File::open(self.root.join(&file.path))
+            .and_then(|handle| handle.take(PREVIEW_BYTES).read_to_end(&mut bytes));
+        match read {
+            Err(_) => "(not in the work tree)".to_owned(),
+            Ok(_) if bytes.contains(&0) => "(binary file)".to_owned(),
+            Ok(_) => String::from_utf8_lossy(&bytes).into_owned(),
+        }
+    }
+}
+
+/// `score` relative to the hottest file, as a bar of `BAR_WIDTH` cells
+fn score_bar(score: f64, max_score: f64) -> String {
+    let filled = if max_score > 0.0 {
+        ((score / max_score * BAR_WIDTH as f64).round() as usize).min(BAR_WIDTH)
+    } else {
+        0
+    };
+    format!("{}{}", "█".repeat(filled), "░".repeat(BAR_WIDTH - filled))
+}
+
+/// Run `$VISUAL` or `$EDITOR` (falling back to `vi`) on `path` and wait for it
+fn open_in_editor(path: &Path) -> anyhow::Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_owned());
+    // Editors are often configured with arguments, e.g. `code --wait`
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .with_context(|| format!("failed to run editor {program}"))?;
+    if !status.success() {
+        bail!("editor {program} exited with {status}");
+    }
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use std::path::Path;
+
+use anyhow::{Result, bail};
+
+use crate::pipeline::{self, ScoreMap};
+use crate::{AnalyzeOptions, CacheMode, index};
+
+/// Fill the cache with the statistics of every commit selected by `options` (and the score
+/// index for them) without scoring anything, e.g. from cron jobs or post-clone hooks.
+/// Returns the number of selected commits. The score index covers the whole history, so
+/// with a time window only the statistics of the commits in it are stored.
+pub fn warm_cache(repo_path: &Path, options: &AnalyzeOptions) -> Result<usize> {
+    if options.cache_mode != CacheMode::ReadWrite {
+        bail!("warming the cache needs a writable cache");
+    }
+    if options.since.is_none() && options.until.is_none() {
+        let (_, selected) = index::indexed_scores(repo_path, options)?;
+        return Ok(selected);
+    }
+    let mut selected = 0;
+    pipeline::run_observed(repo_path, options, ScoreMap::default, |_, progress| {
+        selected = progress.total
+    })?;
+    Ok(selected)
+}
@@ -0,0 +1,33 @@
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// Commit that `rev` (or HEAD) of the repository at `repo` points to, if it resolves
+pub fn head(repo: &Path, rev: Option<&str>) -> Option<String> {
+    let repo = git2::Repository::discover(repo).ok()?;
+    let commit = repo
+        .revparse_single(rev.unwrap_or("HEAD"))
+        .ok()?
+        .peel_to_commit()
+        .ok()?;
+    Some(commit.id().to_string())
+}
+
+/// Call `update` now and again whenever the analyzed commit of one of `repos` changes,
+/// polling every `interval`. Only returns on errors; Ctrl-C ends the process.
+pub fn watch(
+    repos: &[PathBuf],
+    rev: Option<&str>,
+    interval: Duration,
+    mut update: impl FnMut() -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut seen = None;
+    loop {
+        let heads: Vec<Option<String>> = repos.iter().map(|repo| head(repo, rev)).collect();
+        if seen.as_ref() != Some(&heads) {
+            update()?;
+            seen = Some(heads);
+        }
+        thread::sleep(interval);
+    }
+}
@@ -0,0 +1,77 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, bail};
+use rayon::prelude::*;
+use rustc_hash::FxHashMap as HashMap;
+
+use crate::{Analysis, AnalysisMetadata, AnalyzeOptions, FileScore, analyze_repo_with_metadata};
+
+/// Analyze several repositories in parallel (each with its own cache) and merge their files,
+/// prefixed with the name of each repository's directory, into one ranking.
+///
+/// `repo` in the metadata is the closest common parent directory, and `head` is None.
+pub fn analyze_repos(repo_paths: &[PathBuf], options: &AnalyzeOptions) -> Result<Analysis> {
+    if repo_paths.is_empty() {
+        bail!("no repositories to analyze");
+    }
+    let analyses: Vec<Analysis> = repo_paths
+        .par_iter()
+        .map(|repo_path| analyze_repo_with_metadata(repo_path, options))
+        .collect::<Result<_>>()?;
+
+    let mut names: HashMap<PathBuf, &Path> = HashMap::default();
+    for analysis in &analyses {
+        let name = repo_name(&analysis.metadata.repo);
+        if let Some(other) = names.insert(name.clone(), &analysis.metadata.repo) {
+            bail!(
+                "{} and {} would both be reported as {}",
+                other.display(),
+                analysis.metadata.repo.display(),
+                name.display()
+            );
+        }
+    }
+
+    let metadata = AnalysisMetadata {
+        repo: common_parent(analyses.iter().map(|a| a.metadata.repo.as_path())),
+        head: None,
+        commits: analyses.iter().map(|a| a.metadata.commits).sum(),
+        shallow: analyses.iter().any(|a| a.metadata.shallow),
+        ..analyses[0].metadata.clone()
+    };
+    let scores = analyses
+        .into_iter()
+        .flat_map(|analysis| {
+            let name = repo_name(&analysis.metadata.repo);
+            analysis.scores.into_iter().map(move |file| FileScore {
+                path: name.join(&file.path),
+                ..file
+            })
+        })
+        .collect();
+    Ok(Analysis { metadata, scores })
+}
+
+fn repo_name(repo: &Path) -> PathBuf {
+    let name = repo
+        .file_name()
+        .unwrap_or(repo.as_os_str())
+        .to_string_lossy();
+    // Bare repositories are conventionally named `<name>.git`
+    PathBuf::from(name.strip_suffix(".git").unwrap_or(&name))
+}
+
+fn common_parent<'a>(mut paths: impl Iterator<Item = &'a Path>) -> PathBuf {
+    let Some(first) = paths.next() else {
+        return PathBuf::new();
+    };
+    let mut common = first.parent().unwrap_or(first).to_path_buf();
+    for path in paths {
+        while !path.starts_with(&common) {
+            if !common.pop() {
+                break;
+            }
+        }
+    }
+    common
+}
@@ -0,0 +1,50 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use chrono::Utc;
+use git2::{Repository, Status, StatusOptions};
+
+use crate::pipeline::{FileStats, ScoreMap, scoped_path};
+use crate::{AnalyzeOptions, path_from_bytes};
+
+/// Files with uncommitted changes (modified, staged or untracked, but not deleted), relative
+/// to `prefix` and limited to `paths`; empty unless `worktree_weight` is set
+pub(crate) fn changed_files(repo: &Repository, options: &AnalyzeOptions) -> Result<Vec<PathBuf>> {
+    if options.worktree_weight.is_none() || repo.is_bare() {
+        return Ok(Vec::new());
+    }
+    let mut status_options = StatusOptions::new();
+    status_options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false)
+        .renames_head_to_index(true);
+    let statuses = repo.statuses(Some(&mut status_options))?;
+
+    let deleted = Status::INDEX_DELETED | Status::WT_DELETED;
+    let mut files = Vec::new();
+    for entry in statuses.iter() {
+        if entry.status().intersects(deleted) || entry.status().is_conflicted() {
+            continue;
+        }
+        let path = path_from_bytes(entry.path_bytes())?;
+        files.extend(scoped_path(&path, options));
+    }
+    Ok(files)
+}
+
+/// Count each changed file like a commit made right now, weighted by `worktree_weight`;
+/// untracked files join the scores with no commits
+pub(crate) fn blend(scores: &mut ScoreMap, changed: &[PathBuf], options: &AnalyzeOptions) {
+    let Some(weight) = options.worktree_weight else {
+        return;
+    };
+    let now = options.as_of.unwrap_or_else(|| Utc::now().timestamp());
+    for path in changed {
+        let stats = scores.get_or_insert_with(path, || FileStats {
+            last_modified: now,
+            ..FileStats::default()
+        });
+        stats.score += weight;
+    }
+}
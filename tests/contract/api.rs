@@ -0,0 +1,46 @@
+//! Compile-time checks: these fail to build when a public signature changes.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use frecenfile::{AnalyzeOptions, FileScore, NumberFormat};
+
+/// Signature of `analyze_repo`, the original entry point
+type AnalyzeRepo =
+    fn(&Path, Option<HashSet<PathBuf>>, Option<usize>) -> anyhow::Result<Vec<(PathBuf, f64)>>;
+
+#[test]
+fn analysis_entry_points() {
+    let _: AnalyzeRepo = frecenfile::analyze_repo;
+    let _: fn(&Path, &AnalyzeOptions) -> anyhow::Result<Vec<FileScore>> =
+        frecenfile::analyze_repo_with_options;
+}
+
+#[test]
+fn file_score_fields() {
+    fn fields(f: &FileScore) -> (&PathBuf, f64, u32, i64, Option<u64>) {
+        (&f.path, f.score, f.commits, f.last_modified, f.churn)
+    }
+    let _ = fields;
+}
+
+#[test]
+fn analyze_options_fields() {
+    let options = AnalyzeOptions::default();
+    let _: &Option<HashSet<PathBuf>> = &options.paths;
+    let _: &Option<usize> = &options.max_commits;
+    let _: &Vec<String> = &options.ignore_revs;
+    let _: &Vec<PathBuf> = &options.ignore_revs_files;
+    let _: bool = options.use_blame_ignore_revs;
+    let _: bool = options.dedup_cherry_picks;
+    let _: bool = options.churn;
+    let _: &Option<i64> = &options.since;
+    let _: &Option<i64> = &options.until;
+    let _: &Option<i64> = &options.as_of;
+}
+
+#[test]
+fn format_version_is_exposed() {
+    let _: u32 = frecenfile::FORMAT_VERSION;
+    let _: fn(f64, usize) -> String = |v, p| NumberFormat::default().format(v, p);
+}
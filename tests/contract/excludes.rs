@@ -0,0 +1,34 @@
+//! Which files the default excludes drop.
+
+use std::fs;
+
+use frecenfile::{AnalyzeOptions, CacheMode, analyze_repo_with_options};
+
+use crate::fixture::{Fixture, NOW};
+
+fn paths(fixture: &Fixture) -> Vec<String> {
+    let options = AnalyzeOptions {
+        as_of: Some(NOW),
+        cache_mode: CacheMode::Disabled,
+        ..AnalyzeOptions::default()
+    };
+    let mut paths: Vec<_> = analyze_repo_with_options(fixture.path(), &options)
+        .unwrap()
+        .into_iter()
+        .map(|file| file.path.to_str().unwrap().to_owned())
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn attribute_changes_apply_to_the_next_run() {
+    let fixture = Fixture::new();
+    assert_eq!(paths(&fixture), ["a.txt", "b.txt", "c.txt"]);
+    fs::write(
+        fixture.path().join(".gitattributes"),
+        "b.txt linguist-generated\n",
+    )
+    .unwrap();
+    assert_eq!(paths(&fixture), ["a.txt", "c.txt"]);
+}
@@ -0,0 +1,63 @@
+use std::path::Path;
+
+use crate::support::TestRepo;
+
+/// Fixed analysis time used by snapshot tests
+pub const NOW: i64 = 1_700_000_000;
+
+const DAY: i64 = 86_400;
+
+/// A throwaway repository with three files and fully controlled commit times.
+///
+/// Sizes are 0 or 1024 bytes and ages are 0, 1 or 3 days so that every
+/// score is an exact binary fraction, independent of summation order:
+///
+/// | file  | size | touched (days before `NOW`) |
+/// |-------|------|-----------------------------|
+/// | a.txt | 1024 | 3, 1                        |
+/// | b.txt | 0    | 3                           |
+/// | c.txt | 1024 | 0                           |
+pub struct Fixture(TestRepo);
+
+impl Fixture {
+    pub fn new() -> Self {
+        let fixture = Fixture(TestRepo::new());
+        fixture.commit_files(&[("a.txt", 'a'), ("b.txt", ' ')], NOW - 3 * DAY);
+        fixture.commit_files(&[("a.txt", 'b')], NOW - DAY);
+        fixture.commit_files(&[("c.txt", 'c')], NOW);
+        fixture
+    }
+
+    pub fn path(&self) -> &Path {
+        self.0.path()
+    }
+
+    /// Commits each file filled with `fill` (1024 bytes, or empty for ' ') on top of HEAD
+    pub fn commit_files(&self, files: &[(&str, char)], time: i64) {
+        let contents: Vec<_> = files
+            .iter()
+            .map(|&(_, fill)| {
+                if fill == ' ' {
+                    String::new()
+                } else {
+                    fill.to_string().repeat(1024)
+                }
+            })
+            .collect();
+        let files: Vec<(&str, &[u8])> = files
+            .iter()
+            .zip(&contents)
+            .map(|(&(name, _), contents)| (name, contents.as_bytes()))
+            .collect();
+        self.0.commit(&files, time);
+    }
+
+    /// Moves HEAD, the index and the work tree back to the parent of HEAD
+    pub fn reset_to_parent(&self) {
+        let head = self.0.head().unwrap().peel_to_commit().unwrap();
+        let parent = head.parent(0).unwrap();
+        self.0
+            .reset(parent.as_object(), git2::ResetType::Hard, None)
+            .unwrap();
+    }
+}
@@ -0,0 +1,211 @@
+//! Scores derived from the score index stored in the cache.
+
+use std::fs;
+
+use frecenfile::{
+    AnalyzeOptions, CacheBackend, CacheLocation, CacheMode, analyze_repo_with_options,
+};
+
+use tempfile::TempDir;
+
+use crate::fixture::{Fixture, NOW};
+use crate::support::temp_dir;
+
+const DAY: i64 = 86_400;
+
+/// Sorted `(path, score, commits, last_modified)` and whether the index was used, with the
+/// file backend
+fn scores(
+    fixture: &Fixture,
+    cache: &TempDir,
+    cache_mode: CacheMode,
+    since: Option<i64>,
+) -> (Vec<(String, f64, u32, i64)>, bool) {
+    scores_in(fixture, cache, CacheBackend::File, cache_mode, since)
+}
+
+fn scores_in(
+    fixture: &Fixture,
+    cache: &TempDir,
+    cache_backend: CacheBackend,
+    cache_mode: CacheMode,
+    since: Option<i64>,
+) -> (Vec<(String, f64, u32, i64)>, bool) {
+    let options = AnalyzeOptions {
+        as_of: Some(NOW),
+        since,
+        cache_mode,
+        cache_location: CacheLocation::Dir(cache.path().to_path_buf()),
+        cache_backend,
+        ..AnalyzeOptions::default()
+    };
+    let mut scores: Vec<_> = analyze_repo_with_options(fixture.path(), &options)
+        .unwrap()
+        .into_iter()
+        .map(|file| {
+            let path = file.path.to_str().unwrap().to_owned();
+            (path, file.score, file.commits, file.last_modified)
+        })
+        .collect();
+    scores.sort_by(|a, b| a.0.cmp(&b.0));
+    (scores, options.stats.report().index_hits > 0)
+}
+
+#[test]
+fn indexed_scores_match_a_fresh_walk() {
+    let fixture = Fixture::new();
+    let cache = temp_dir();
+    let window = Some(NOW - 2 * DAY);
+    let fresh = |since| scores(&fixture, &cache, CacheMode::Disabled, since).0;
+
+    // A windowed walk does not build the index
+    assert_eq!(
+        scores(&fixture, &cache, CacheMode::ReadWrite, window),
+        (fresh(window), false)
+    );
+    assert_eq!(
+        scores(&fixture, &cache, CacheMode::ReadWrite, None),
+        (fresh(None), false)
+    );
+    assert_eq!(
+        scores(&fixture, &cache, CacheMode::ReadWrite, None),
+        (fresh(None), true)
+    );
+    assert_eq!(
+        scores(&fixture, &cache, CacheMode::ReadOnly, window),
+        (fresh(window), true)
+    );
+
+    // The index is extended with the commits on top of the indexed HEAD
+    fixture.commit_files(&[("b.txt", 'b')], NOW);
+    assert_eq!(
+        scores(&fixture, &cache, CacheMode::ReadOnly, window),
+        (fresh(window), true)
+    );
+    assert_eq!(
+        scores(&fixture, &cache, CacheMode::ReadWrite, None),
+        (fresh(None), true)
+    );
+    assert_eq!(
+        scores(&fixture, &cache, CacheMode::ReadWrite, window),
+        (fresh(window), true)
+    );
+
+    // and loses the commits no longer reachable from HEAD
+    fixture.reset_to_parent();
+    fixture.reset_to_parent();
+    fixture.commit_files(&[("d.txt", 'd')], NOW);
+    assert_eq!(
+        scores(&fixture, &cache, CacheMode::ReadWrite, None),
+        (fresh(None), true)
+    );
+    assert_eq!(
+        scores(&fixture, &cache, CacheMode::ReadOnly, window),
+        (fresh(window), true)
+    );
+}
+
+#[test]
+fn rewriting_most_of_the_history_rebuilds_the_index() {
+    let fixture = Fixture::new();
+    let cache = temp_dir();
+    fixture.commit_files(&[("d.txt", 'd')], NOW);
+    fixture.commit_files(&[("d.txt", 'e')], NOW);
+    let fresh = scores(&fixture, &cache, CacheMode::Disabled, None).0;
+
+    scores(&fixture, &cache, CacheMode::ReadWrite, None);
+    for _ in 0..4 {
+        fixture.reset_to_parent();
+    }
+    fixture.commit_files(&[("e.txt", 'e')], NOW);
+    let rewritten = scores(&fixture, &cache, CacheMode::Disabled, None).0;
+    assert_ne!(rewritten, fresh);
+    // Four of the five indexed commits are gone, so the history is walked afresh
+    assert_eq!(
+        scores(&fixture, &cache, CacheMode::ReadWrite, None),
+        (rewritten.clone(), false)
+    );
+    assert_eq!(
+        scores(&fixture, &cache, CacheMode::ReadWrite, None),
+        (rewritten, true)
+    );
+}
+
+#[test]
+fn windowed_runs_can_build_the_index() {
+    let fixture = Fixture::new();
+    let cache = temp_dir();
+    let window = Some(NOW - 2 * DAY);
+    let scores = |since| {
+        let options = AnalyzeOptions {
+            as_of: Some(NOW),
+            since,
+            cache_location: CacheLocation::Dir(cache.path().to_path_buf()),
+            index_windowed_runs: true,
+            ..AnalyzeOptions::default()
+        };
+        let scores = analyze_repo_with_options(fixture.path(), &options).unwrap();
+        let mut paths: Vec<String> = scores
+            .into_iter()
+            .map(|file| file.path.to_str().unwrap().to_owned())
+            .collect();
+        paths.sort();
+        (paths, options.stats.report().index_hits > 0)
+    };
+
+    assert_eq!(
+        scores(window),
+        (vec!["a.txt".to_owned(), "c.txt".to_owned()], false)
+    );
+    fixture.commit_files(&[("b.txt", 'b')], NOW);
+    let all = vec!["a.txt".to_owned(), "b.txt".to_owned(), "c.txt".to_owned()];
+    assert_eq!(scores(window), (all.clone(), true));
+    assert_eq!(scores(None), (all, true));
+}
+
+#[test]
+fn read_only_runs_use_a_sled_cache() {
+    let fixture = Fixture::new();
+    let cache = temp_dir();
+    let scores = |cache_mode| scores_in(&fixture, &cache, CacheBackend::Sled, cache_mode, None);
+    let fresh = scores(CacheMode::Disabled).0;
+
+    assert_eq!(scores(CacheMode::ReadOnly), (fresh.clone(), false));
+    scores(CacheMode::ReadWrite);
+    assert_eq!(scores(CacheMode::ReadOnly), (fresh.clone(), true));
+    assert_eq!(scores(CacheMode::ReadOnly), (fresh, true));
+}
+
+#[test]
+fn attribute_changes_invalidate_the_index() {
+    let fixture = Fixture::new();
+    let cache = temp_dir();
+    let paths = |(scores, hit): (Vec<(String, f64, u32, i64)>, bool)| {
+        let paths: Vec<String> = scores.into_iter().map(|(path, ..)| path).collect();
+        (paths, hit)
+    };
+
+    scores(&fixture, &cache, CacheMode::ReadWrite, None);
+    assert_eq!(
+        paths(scores(&fixture, &cache, CacheMode::ReadWrite, None)),
+        (
+            vec!["a.txt".to_owned(), "b.txt".to_owned(), "c.txt".to_owned()],
+            true
+        )
+    );
+
+    // An uncommitted attribute marks b.txt as generated
+    fs::write(
+        fixture.path().join(".gitattributes"),
+        "b.txt linguist-generated\n",
+    )
+    .unwrap();
+    assert_eq!(
+        paths(scores(&fixture, &cache, CacheMode::ReadWrite, None)),
+        (vec!["a.txt".to_owned(), "c.txt".to_owned()], false)
+    );
+    assert_eq!(
+        paths(scores(&fixture, &cache, CacheMode::ReadWrite, None)),
+        (vec!["a.txt".to_owned(), "c.txt".to_owned()], true)
+    );
+}
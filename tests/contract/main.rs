@@ -0,0 +1,15 @@
+//! Contract tests for the public library API and the machine-readable output.
+//!
+//! A failure here means a change is visible to integrators: either adapt the
+//! change, or update the expectation and bump `FORMAT_VERSION` when the output
+//! changed incompatibly.
+
+mod api;
+mod excludes;
+mod fixture;
+mod index;
+mod output;
+#[path = "../support/mod.rs"]
+mod support;
+mod walk;
+mod window;
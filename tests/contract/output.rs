@@ -0,0 +1,120 @@
+//! Snapshot tests of the JSON and porcelain output.
+
+use std::process::Command;
+
+use frecenfile::{AnalyzeOptions, CacheMode, FORMAT_VERSION, analyze_repo_with_options};
+
+use crate::fixture::{Fixture, NOW};
+
+/// Runs the CLI on `fixture` without reading or writing the user's cache
+fn frecenfile(fixture: &Fixture, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_frecenfile"))
+        .arg("--repo")
+        .arg(fixture.path())
+        .arg("--no-cache")
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn json_snapshot() {
+    let fixture = Fixture::new();
+    let options = AnalyzeOptions {
+        as_of: Some(NOW),
+        cache_mode: CacheMode::Disabled,
+        ..AnalyzeOptions::default()
+    };
+    let mut results = analyze_repo_with_options(fixture.path(), &options).unwrap();
+    results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+
+    let expected = format!(
+        r#"[
+  {{
+    "path": "c.txt",
+    "score": 0.5,
+    "commits": 1,
+    "last_modified": {now},
+    "churn": null
+  }},
+  {{
+    "path": "a.txt",
+    "score": 0.15625,
+    "commits": 2,
+    "last_modified": {yesterday},
+    "churn": null
+  }},
+  {{
+    "path": "b.txt",
+    "score": 0.0625,
+    "commits": 1,
+    "last_modified": {three_days_ago},
+    "churn": null
+  }}
+]"#,
+        now = NOW,
+        yesterday = NOW - 86_400,
+        three_days_ago = NOW - 3 * 86_400,
+    );
+    assert_eq!(serde_json::to_string_pretty(&results).unwrap(), expected);
+}
+
+#[test]
+fn porcelain_snapshot() {
+    // Scores depend on the wall clock here, but for a fixture this old the
+    // ordering does not, and every raw score rounds to zero.
+    let fixture = Fixture::new();
+    assert_eq!(
+        frecenfile(&fixture, &["--path-only"]),
+        "a.txt\nb.txt\nc.txt\n"
+    );
+    assert_eq!(
+        frecenfile(&fixture, &[]),
+        "0.0000      a.txt\n0.0000      b.txt\n0.0000      c.txt\n"
+    );
+}
+
+#[test]
+fn porcelain_as_of_snapshot() {
+    let fixture = Fixture::new();
+    let as_of = NOW.to_string();
+    assert_eq!(
+        frecenfile(&fixture, &["--path-only", "--as-of", &as_of]),
+        "c.txt\na.txt\nb.txt\n"
+    );
+}
+
+#[test]
+fn jsonl_records() {
+    let fixture = Fixture::new();
+    let output = frecenfile(&fixture, &["--format", "jsonl"]);
+    let records: Vec<serde_json::Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    assert_eq!(records.len(), 4);
+    assert_eq!(records[0]["kind"], "metadata");
+    assert_eq!(records[0]["commits"], 3);
+    assert_eq!(records[0]["format_version"], FORMAT_VERSION);
+    for record in &records[1..] {
+        assert_eq!(record["kind"], "file");
+    }
+    assert_eq!(records[1]["path"], "a.txt");
+}
+
+#[test]
+fn version_json() {
+    let output = Command::new(env!("CARGO_BIN_EXE_frecenfile"))
+        .args(["--version", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(info["name"], "frecenfile");
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(info["format_version"], FORMAT_VERSION);
+}
@@ -0,0 +1,119 @@
+//! Results that must not depend on how the history walk is carried out.
+
+use frecenfile::{
+    AnalyzeOptions, CacheLocation, CacheMode, FileScore, SortOrder, analyze_repo_sorted,
+    analyze_repo_with_options,
+};
+
+use crate::fixture::{Fixture, NOW};
+use crate::support::temp_dir;
+
+/// `(path, score, commits, last_modified)` sorted by path
+fn scores(fixture: &Fixture, options: AnalyzeOptions) -> Vec<(String, f64, u32, i64)> {
+    let options = AnalyzeOptions {
+        as_of: Some(NOW),
+        cache_mode: CacheMode::Disabled,
+        ..options
+    };
+    let mut scores: Vec<_> = analyze_repo_with_options(fixture.path(), &options)
+        .unwrap()
+        .into_iter()
+        .map(|file| {
+            let path = file.path.to_str().unwrap().to_owned();
+            (path, file.score, file.commits, file.last_modified)
+        })
+        .collect();
+    scores.sort_by(|a, b| a.0.cmp(&b.0));
+    scores
+}
+
+#[test]
+fn spilled_scores_match_an_unspilled_walk() {
+    let fixture = Fixture::new();
+    let unspilled = scores(&fixture, AnalyzeOptions::default());
+    // A one byte limit spills after every wave of one commit
+    let spilled = scores(&fixture, spilling(AnalyzeOptions::default()));
+    assert_eq!(spilled, unspilled);
+}
+
+/// Options that spill after every wave of one commit
+fn spilling(options: AnalyzeOptions) -> AnalyzeOptions {
+    AnalyzeOptions {
+        threads: Some(1),
+        commits_per_chunk: Some(1),
+        max_memory_bytes: Some(1),
+        spill_to_disk: true,
+        ..options
+    }
+}
+
+#[test]
+fn sorted_spilled_scores_match_an_unspilled_walk() {
+    let fixture = Fixture::new();
+    fixture.commit_files(&[("d.txt", 'd'), ("b.txt", 'b')], NOW - 2 * 86_400);
+    let options = AnalyzeOptions {
+        as_of: Some(NOW),
+        cache_mode: CacheMode::Disabled,
+        ..AnalyzeOptions::default()
+    };
+    let mut unspilled = analyze_repo_with_options(fixture.path(), &options).unwrap();
+    for order in [SortOrder::HottestFirst, SortOrder::ColdestFirst] {
+        unspilled.sort_by(|a, b| order.compare(a, b));
+        let (metadata, sorted) =
+            analyze_repo_sorted(fixture.path(), &spilling(options.clone()), order).unwrap();
+        assert_eq!(metadata.commits, 4);
+        let sorted: Vec<FileScore> = sorted.collect::<Result<_, _>>().unwrap();
+        assert_eq!(sorted, unspilled);
+    }
+}
+
+#[test]
+fn spilled_walks_resolve_cached_paths() {
+    let fixture = Fixture::new();
+    let cache = temp_dir();
+    let unspilled = scores(&fixture, AnalyzeOptions::default());
+    // The second walk reads every commit's statistics from the cache, which refer to paths
+    // by id, after the path table was cleared by a spill
+    for _ in 0..2 {
+        let options = AnalyzeOptions {
+            as_of: Some(NOW),
+            cache_location: CacheLocation::Dir(cache.path().to_path_buf()),
+            ..spilling(AnalyzeOptions::default())
+        };
+        let mut spilled: Vec<_> = analyze_repo_with_options(fixture.path(), &options)
+            .unwrap()
+            .into_iter()
+            .map(|file| {
+                let path = file.path.to_str().unwrap().to_owned();
+                (path, file.score, file.commits, file.last_modified)
+            })
+            .collect();
+        spilled.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(spilled, unspilled);
+    }
+}
+
+#[test]
+fn scores_do_not_depend_on_the_thread_count_or_wave_size() {
+    let fixture = Fixture::new();
+    // Decays such as 1/9 and 1/25 are inexact, so the sums of many of them differ when
+    // added up in another order
+    for days in (2..60).rev() {
+        let fill = if days % 2 == 0 { 'd' } else { 'e' };
+        fixture.commit_files(&[("a.txt", fill), ("b.txt", fill)], NOW - days * 86_400);
+    }
+    let walk = |threads, commits_per_chunk| {
+        scores(
+            &fixture,
+            AnalyzeOptions {
+                threads: Some(threads),
+                commits_per_chunk: Some(commits_per_chunk),
+                ..AnalyzeOptions::default()
+            },
+        )
+    };
+    let sequential = walk(1, 1);
+    for (threads, commits_per_chunk) in [(1, 64), (2, 1), (3, 2), (4, 64)] {
+        assert_eq!(walk(threads, commits_per_chunk), sequential);
+    }
+}
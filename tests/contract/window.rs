@@ -0,0 +1,24 @@
+//! Which commits the time window and commit limit select.
+
+use frecenfile::{AnalyzeOptions, CacheMode, analyze_repo_with_options};
+
+use crate::fixture::{Fixture, NOW};
+
+#[test]
+fn commit_limit_counts_commits_inside_the_window() {
+    // The newest commit (c.txt, at NOW) is after the window and must not use up the limit
+    let fixture = Fixture::new();
+    let options = AnalyzeOptions {
+        max_commits: Some(1),
+        until: Some(NOW - 86_400),
+        as_of: Some(NOW - 86_400),
+        cache_mode: CacheMode::Disabled,
+        ..AnalyzeOptions::default()
+    };
+    let results = analyze_repo_with_options(fixture.path(), &options).unwrap();
+    let paths: Vec<_> = results
+        .iter()
+        .map(|file| file.path.to_str().unwrap())
+        .collect();
+    assert_eq!(paths, ["a.txt"]);
+}
@@ -0,0 +1,74 @@
+//! Temporary repositories and directories for the unit and contract tests, which include this
+//! file with `#[path]`.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::ops::Deref;
+use std::path::Path;
+
+use git2::{Oid, Repository, Signature, Time};
+use tempfile::TempDir;
+
+/// A directory of its own under the system temporary directory, removed when dropped
+pub fn temp_dir() -> TempDir {
+    tempfile::Builder::new()
+        .prefix("frecenfile-test-")
+        .tempdir()
+        .unwrap()
+}
+
+/// A git repository in a fresh temporary directory, removed when dropped
+pub struct TestRepo {
+    repo: Repository,
+    dir: TempDir,
+}
+
+impl TestRepo {
+    pub fn new() -> Self {
+        let dir = temp_dir();
+        let repo = Repository::init(dir.path()).unwrap();
+        Self { repo, dir }
+    }
+
+    /// The work tree
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Writes each file with its contents (creating its directories), and commits them on
+    /// top of HEAD at `time`
+    pub fn commit(&self, files: &[(&str, &[u8])], time: i64) -> Oid {
+        let mut index = self.repo.index().unwrap();
+        for (name, contents) in files {
+            let path = self.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, contents).unwrap();
+            index.add_path(Path::new(name)).unwrap();
+        }
+        index.write().unwrap();
+
+        let tree = self.repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::new("Test", "test@example.com", &Time::new(time, 0)).unwrap();
+        let parent = self.repo.head().ok().map(|h| h.peel_to_commit().unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        self.repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "change",
+                &tree,
+                &parents,
+            )
+            .unwrap()
+    }
+}
+
+impl Deref for TestRepo {
+    type Target = Repository;
+
+    fn deref(&self) -> &Repository {
+        &self.repo
+    }
+}